@@ -0,0 +1,99 @@
+//! Compares the ways of counting a large file: the old approach of reading it a line at a
+//! time into a new `String`, reading it through a buffer with `count::count_lines`, and
+//! memory-mapping it and counting it with `count::count_text`.
+//!
+//! Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use memmap2::Mmap;
+use std::fs::{self, File};
+use std::hint::black_box;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tlc::count;
+use tlc::language::{self, Language};
+
+/// Writes a test file of about 32 MiB made of `lines` repeated over and over.
+fn make_file(name: &str, lines: &[&str]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("tlc-bench-{}", name));
+    let mut out = fs::File::create(&path).unwrap();
+    let mut written = 0;
+    let mut i = 0;
+    while written < 32 << 20 {
+        let line = lines[i % lines.len()];
+        writeln!(out, "{}", line).unwrap();
+        written += line.len() + 1;
+        i += 1;
+    }
+
+    path
+}
+
+/// The old way: a new `String` for every line.
+fn count_by_string(path: &Path, lang: &Language) -> count::LineCounts {
+    let mut counts = count::LineCounts::default();
+    let mut classifier = count::Classifier::new(lang);
+    for line in BufReader::new(File::open(path).unwrap()).lines() {
+        classifier.push_line(line.unwrap().as_bytes(), &mut counts);
+    }
+
+    counts
+}
+
+fn count_buffered(path: &Path, lang: &Language) -> count::LineCounts {
+    let reader = BufReader::with_capacity(1 << 20, File::open(path).unwrap());
+    count::count_lines(reader, lang).unwrap()
+}
+
+fn count_mapped(path: &Path, lang: &Language) -> count::LineCounts {
+    let file = File::open(path).unwrap();
+    // Safety: nothing else touches the benchmark's file while it's mapped.
+    let map = unsafe { Mmap::map(&file) }.unwrap();
+    count::count_text(&map, lang)
+}
+
+fn bench_file(c: &mut Criterion, name: &str, path: &Path, lang: &Language) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(fs::metadata(path).unwrap().len()));
+    group.sample_size(10);
+    group.bench_function("string per line", |b| {
+        b.iter(|| count_by_string(black_box(path), lang))
+    });
+    group.bench_function("buffered", |b| {
+        b.iter(|| count_buffered(black_box(path), lang))
+    });
+    group.bench_function("mmap", |b| b.iter(|| count_mapped(black_box(path), lang)));
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    let log = make_file(
+        "plain.txt",
+        &[
+            "2021-06-01 12:00:00 INFO request handled in 12ms",
+            "",
+            "2021-06-01 12:00:01 WARN slow query: SELECT * FROM t",
+        ],
+    );
+    bench_file(c, "plain text", &log, &language::PLAIN_TEXT);
+
+    let rust = language::from_name("rust").unwrap();
+    let source = make_file(
+        "source.rs",
+        &[
+            "/// Adds two numbers.",
+            "fn add(a: u32, b: u32) -> u32 {",
+            "    a + b // no overflow check",
+            "}",
+            "",
+            "const NAME: &str = \"tlc /* not a comment */\";",
+        ],
+    );
+    bench_file(c, "rust", &source, rust);
+
+    let _ = fs::remove_file(log);
+    let _ = fs::remove_file(source);
+}
+
+criterion_group!(count_benches, benches);
+criterion_main!(count_benches);
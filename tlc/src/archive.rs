@@ -0,0 +1,272 @@
+use flate2::read::MultiGzDecoder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+use xz2::read::XzDecoder;
+use zip::ZipArchive;
+
+/// Separates the path of an archive from the path of a file inside it, as in
+/// `foo.tar.gz!/src/lib.rs`.
+pub const SEPARATOR: &str = "!/";
+
+/// The kinds of archives and compressed files that can be looked inside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    /// A single gzipped file.
+    Gz,
+}
+
+impl Kind {
+    /// Tells what kind of archive a file is from its name.
+    ///
+    /// # Returns
+    /// The kind of archive, or `None` if the file isn't one.
+    pub fn of(path: &Path) -> Option<Kind> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        let kinds = [
+            (".tar.gz", Kind::TarGz),
+            (".tgz", Kind::TarGz),
+            (".tar.xz", Kind::TarXz),
+            (".txz", Kind::TarXz),
+            (".tar", Kind::Tar),
+            (".zip", Kind::Zip),
+            (".gz", Kind::Gz),
+        ];
+        kinds
+            .iter()
+            .find(|(ext, _)| name.len() > ext.len() && name.ends_with(ext))
+            .map(|&(_, kind)| kind)
+    }
+}
+
+/// Gets the virtual path of a file inside an archive, such as `foo.tar.gz!/src/lib.rs`.
+///
+/// # Parameters
+/// - `archive`: The path of the archive.
+/// - `name`: The path of the file inside the archive, with `/` between its components.
+pub fn member_path(archive: &Path, name: &str) -> PathBuf {
+    let mut path = OsString::from(archive);
+    path.push(SEPARATOR);
+    path.push(name);
+    PathBuf::from(path)
+}
+
+/// Reads every file in an archive, in the order they're stored in. Directories, links, and
+/// other special entries are skipped.
+///
+/// # Parameters
+/// - `path`: The archive.
+/// - `kind`: What kind of archive it is.
+/// - `each`: Called with the path of each file inside the archive (with `/` between its
+///   components), and either a reader for its contents or the error that keeps it from
+///   being read (such as an unsupported compression method).
+///
+/// # Returns
+/// Any error from reading the archive itself. Files that were read before the error was
+/// hit have already been passed to `each`.
+pub fn read_members<F>(path: &Path, kind: Kind, mut each: F) -> io::Result<()>
+where
+    F: FnMut(&str, io::Result<&mut dyn Read>),
+{
+    let file = File::open(path)?;
+    match kind {
+        Kind::Zip => read_zip(file, &mut each),
+        Kind::Tar => read_tar(BufReader::new(file), &mut each),
+        Kind::TarGz => read_tar(MultiGzDecoder::new(BufReader::new(file)), &mut each),
+        Kind::TarXz => read_tar(
+            XzDecoder::new_multi_decoder(BufReader::new(file)),
+            &mut each,
+        ),
+        Kind::Gz => {
+            // The file inside is named after the archive, without its `.gz`.
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            let name = &name[..name.len() - ".gz".len()];
+            let mut decoder = MultiGzDecoder::new(BufReader::new(file));
+            each(name, Ok(&mut decoder));
+            Ok(())
+        }
+    }
+}
+
+fn read_tar<R: Read>(
+    reader: R,
+    each: &mut dyn FnMut(&str, io::Result<&mut dyn Read>),
+) -> io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = normalize(&entry.path()?.to_string_lossy());
+        if !name.is_empty() {
+            each(&name, Ok(&mut entry));
+        }
+    }
+
+    Ok(())
+}
+
+fn read_zip(file: File, each: &mut dyn FnMut(&str, io::Result<&mut dyn Read>)) -> io::Result<()> {
+    let mut archive = ZipArchive::new(BufReader::new(file))?;
+    for i in 0..archive.len() {
+        // The raw entry can be looked at even if its contents can't be decompressed.
+        let (name, is_file) = {
+            let raw = archive.by_index_raw(i)?;
+            (normalize(raw.name()), raw.is_file())
+        };
+        if !is_file || name.is_empty() {
+            continue;
+        }
+
+        match archive.by_index(i) {
+            Ok(mut member) => each(&name, Ok(&mut member)),
+            Err(e) => each(&name, Err(e.into())),
+        }
+    }
+
+    Ok(())
+}
+
+/// Cleans up the path of a file inside an archive: backslashes become `/`, and leading
+/// `/`s and `.` components are taken out.
+fn normalize(name: &str) -> String {
+    name.replace('\\', "/")
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether a file inside an archive is an ignore file, which the files around it should be
+/// checked against.
+pub fn is_ignore_file(name: &str) -> bool {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    file_name == ".gitignore" || file_name == ".ignore"
+}
+
+/// The `.gitignore` and `.ignore` files found inside an archive.
+#[derive(Default)]
+pub struct IgnoreFiles {
+    /// The directory of each file (empty for the top of the archive), the file's path, and
+    /// its rules.
+    files: Vec<(String, String, Gitignore)>,
+}
+
+impl IgnoreFiles {
+    /// Adds an ignore file's rules.
+    ///
+    /// # Parameters
+    /// - `name`: The path of the ignore file inside the archive.
+    /// - `contents`: What's in it.
+    pub fn add(&mut self, name: &str, contents: &[u8]) {
+        let dir = name.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let mut builder = GitignoreBuilder::new("");
+        for line in String::from_utf8_lossy(contents).lines() {
+            // Lines with mistakes in them are left out, as git does.
+            let _ = builder.add_line(None, line);
+        }
+
+        if let Ok(rules) = builder.build() {
+            self.files.push((dir.to_string(), name.to_string(), rules));
+        }
+    }
+
+    /// Checks a file inside the archive against the ignore files in its directory and the
+    /// directories above it. As with git, the deepest ignore file with a matching rule
+    /// decides, and `.ignore` comes before `.gitignore` in the same directory.
+    ///
+    /// # Parameters
+    /// - `name`: The path of the file inside the archive.
+    ///
+    /// # Returns
+    /// The path of the ignore file that ignores the file, or `None` if it isn't ignored.
+    pub fn ignored_by(&self, name: &str) -> Option<&str> {
+        let mut candidates = self
+            .files
+            .iter()
+            .filter_map(|(dir, path, rules)| {
+                let rel = if dir.is_empty() {
+                    name
+                } else {
+                    name.strip_prefix(dir.as_str())?.strip_prefix('/')?
+                };
+                Some((
+                    dir.len(),
+                    path.ends_with("/.ignore") || path == ".ignore",
+                    rel,
+                    path,
+                    rules,
+                ))
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|&(depth, dot_ignore, ..)| std::cmp::Reverse((depth, dot_ignore)));
+
+        for (_, _, rel, path, rules) in candidates {
+            let matched = rules.matched_path_or_any_parents(rel, false);
+            if matched.is_ignore() {
+                return Some(path);
+            }
+
+            if matched.is_whitelist() {
+                return None;
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_come_from_the_name() {
+        let kind = |name: &str| Kind::of(Path::new(name));
+        assert_eq!(kind("dist/foo-1.0.tar.gz"), Some(Kind::TarGz));
+        assert_eq!(kind("FOO.TGZ"), Some(Kind::TarGz));
+        assert_eq!(kind("foo.tar.xz"), Some(Kind::TarXz));
+        assert_eq!(kind("foo.tar"), Some(Kind::Tar));
+        assert_eq!(kind("export.zip"), Some(Kind::Zip));
+        assert_eq!(kind("lib.rs.gz"), Some(Kind::Gz));
+        assert_eq!(kind(".gz"), None);
+        assert_eq!(kind("lib.rs"), None);
+    }
+
+    #[test]
+    fn member_paths_are_cleaned_up() {
+        assert_eq!(normalize("./foo/./bar.rs"), "foo/bar.rs");
+        assert_eq!(normalize("/abs//path\\win.rs"), "abs/path/win.rs");
+        assert_eq!(
+            member_path(Path::new("a/b.zip"), "c/d.rs"),
+            PathBuf::from("a/b.zip!/c/d.rs")
+        );
+    }
+
+    #[test]
+    fn deeper_ignore_files_win() {
+        let mut ignores = IgnoreFiles::default();
+        ignores.add(".gitignore", b"*.log\nbuild/\n");
+        ignores.add("pkg/.gitignore", b"!keep.log\n");
+        ignores.add("pkg/.ignore", b"keep.log\n");
+
+        assert_eq!(ignores.ignored_by("a.log"), Some(".gitignore"));
+        assert_eq!(ignores.ignored_by("src/build/out.rs"), Some(".gitignore"));
+        assert_eq!(ignores.ignored_by("pkg/keep.log"), Some("pkg/.ignore"));
+        assert_eq!(ignores.ignored_by("pkg/other.log"), Some(".gitignore"));
+        assert_eq!(ignores.ignored_by("src/main.rs"), None);
+        assert!(is_ignore_file("pkg/.gitignore"));
+        assert!(!is_ignore_file("pkg/gitignore"));
+    }
+}
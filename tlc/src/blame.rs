@@ -0,0 +1,268 @@
+use crate::count::{self, LineCounts};
+use crate::embedded;
+use crate::encoding;
+use crate::language::Language;
+use crate::walk::FileStats;
+use git2::{ErrorCode, Mailmap, Oid, Repository};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Someone who wrote lines, as `git blame` names them after applying the repository's
+/// `.mailmap`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Author {
+    pub name: String,
+    pub email: String,
+}
+
+impl Author {
+    /// Who lines that haven't been committed yet are attributed to, as in `git blame`.
+    pub fn uncommitted() -> Self {
+        Author {
+            name: "Not Committed Yet".to_string(),
+            email: "not.committed.yet".to_string(),
+        }
+    }
+}
+
+/// The lines of a file, split up by who last changed them, in the order each author first
+/// shows up in the file.
+pub type Authors = Vec<(Author, LineCounts)>;
+
+/// Blames files, keeping each repository open once it's been found.
+#[derive(Default)]
+pub struct Blamer {
+    /// The repository that each directory is in, as an index into `repos`, or `None` if it
+    /// isn't in one.
+    dirs: HashMap<PathBuf, Option<usize>>,
+    repos: Vec<Repo>,
+    /// The author of each commit that's been looked up.
+    commit_authors: HashMap<Oid, Author>,
+}
+
+/// A repository that files are being blamed in.
+struct Repo {
+    repo: Repository,
+    /// The full path of the repository's working tree.
+    workdir: PathBuf,
+    /// The repository's `.mailmap`, if it has one that can be read.
+    mailmap: Option<Mailmap>,
+}
+
+impl Blamer {
+    /// Finds out who last changed each line of a file, as it is now. Lines that have been
+    /// changed since the last commit, and files that aren't committed at all, belong to
+    /// `Author::uncommitted`. Lines are classified the same way as when the file is counted
+    /// (after decoding it, with embedded code in its own language), so that every author's
+    /// lines add up to the file's counts. A notebook's lines are those of its JSON rather
+    /// than of its cells, so all of them go to whoever last changed most of the notebook.
+    ///
+    /// # Parameters
+    /// - `path`: The file.
+    /// - `lang`: The file's language.
+    ///
+    /// # Returns
+    /// Who wrote how many lines of the file, `None` if the file isn't in a git repository's
+    /// working tree (such as a file inside an archive), or the error from blaming it.
+    pub fn blame(
+        &mut self,
+        path: &Path,
+        lang: &'static Language,
+    ) -> Result<Option<Authors>, git2::Error> {
+        let (repo, rel_path) = match self.repository(path) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let (repo, mailmap) = (&self.repos[repo].repo, &self.repos[repo].mailmap);
+        let data = fs::read(path).map_err(|e| git2::Error::from_str(&e.to_string()))?;
+        let text = encoding::decode(&data).ok_or_else(|| git2::Error::from_str("binary file"))?;
+
+        // Each line's author, as an index into `authors`.
+        let mut authors = vec![];
+        let mut line_authors = vec![];
+        match repo.blame_file(&rel_path, None) {
+            Ok(committed) => {
+                // Git blames the bytes as they are, which have their lines in the same places
+                // as the decoded text.
+                let blame = committed.blame_buffer(&data)?;
+                for hunk in blame.iter() {
+                    // Lines changed since the last commit have no commit. The signatures in
+                    // the hunks aren't used, since libgit2 leaves them out of some of the
+                    // hunks that it splits to fit the changes in.
+                    let id = hunk.final_commit_id();
+                    let author = if id.is_zero() {
+                        Author::uncommitted()
+                    } else {
+                        match self.commit_authors.get(&id) {
+                            Some(author) => author.clone(),
+                            None => {
+                                let author = commit_author(repo, mailmap.as_ref(), id)?;
+                                self.commit_authors.insert(id, author.clone());
+                                author
+                            }
+                        }
+                    };
+                    let index = author_index(&mut authors, author);
+                    let start = hunk.final_start_line().saturating_sub(1);
+                    let end = start + hunk.lines_in_hunk();
+                    if line_authors.len() < end {
+                        line_authors.resize(end, None);
+                    }
+                    line_authors[start..end].fill(Some(index));
+                }
+            }
+            // Files that were never committed, or repositories without any commits yet.
+            Err(e) if matches!(e.code(), ErrorCode::NotFound | ErrorCode::UnbornBranch) => {}
+            Err(e) => return Err(e),
+        }
+
+        // Any lines that blame doesn't cover haven't been committed.
+        let line_author = |authors: &mut Authors, i: usize| match line_authors.get(i) {
+            Some(&Some(index)) => index,
+            _ => author_index(authors, Author::uncommitted()),
+        };
+        match embedded::classify_lines(&text, lang) {
+            Some(lines) => {
+                for (i, counts) in lines.into_iter().enumerate() {
+                    let index = line_author(&mut authors, i);
+                    authors[index].1 += counts;
+                }
+            }
+            None => {
+                let mut written = HashMap::new();
+                for i in 0..count::lines(&text).count() {
+                    *written.entry(line_author(&mut authors, i)).or_insert(0) += 1;
+                }
+                let most = written
+                    .into_iter()
+                    .max_by_key(|&(index, lines)| (lines, Reverse(index)));
+                if let Some((index, _)) = most {
+                    authors[index].1 = embedded::count_into(&text, lang, &mut ()).counts;
+                }
+            }
+        }
+
+        authors.retain(|(_, counts)| counts.lines() > 0);
+        Ok(Some(authors))
+    }
+
+    /// Finds the repository whose working tree a file is in.
+    ///
+    /// # Returns
+    /// The repository (as an index into `repos`), and the file's path relative to its
+    /// working tree, or `None` if the file isn't in one.
+    fn repository(&mut self, path: &Path) -> Option<(usize, PathBuf)> {
+        let path = fs::canonicalize(path).ok()?;
+        let dir = path.parent()?.to_path_buf();
+        let index = match self.dirs.get(&dir) {
+            Some(index) => *index,
+            None => {
+                let index = self.discover(&dir);
+                self.dirs.insert(dir, index);
+                index
+            }
+        }?;
+
+        let rel_path = path.strip_prefix(&self.repos[index].workdir).ok()?;
+        Some((index, rel_path.to_path_buf()))
+    }
+
+    /// Opens the repository that a directory is in, unless it's already open.
+    fn discover(&mut self, dir: &Path) -> Option<usize> {
+        let repo = Repository::discover(dir).ok()?;
+        let workdir = fs::canonicalize(repo.workdir()?).ok()?;
+        match self.repos.iter().position(|r| r.workdir == workdir) {
+            Some(index) => Some(index),
+            None => {
+                let mailmap = repo.mailmap().ok();
+                self.repos.push(Repo {
+                    repo,
+                    workdir,
+                    mailmap,
+                });
+                Some(self.repos.len() - 1)
+            }
+        }
+    }
+}
+
+/// Looks up who wrote a commit, by the name and email that the mailmap gives them.
+fn commit_author(
+    repo: &Repository,
+    mailmap: Option<&Mailmap>,
+    id: Oid,
+) -> Result<Author, git2::Error> {
+    let commit = repo.find_commit(id)?;
+    let signature = match mailmap {
+        Some(mailmap) => mailmap.resolve_signature(&commit.author())?,
+        None => commit.author().to_owned(),
+    };
+    Ok(Author {
+        name: String::from_utf8_lossy(signature.name_bytes()).into_owned(),
+        email: String::from_utf8_lossy(signature.email_bytes()).to_lowercase(),
+    })
+}
+
+/// Finds an author in a file's authors, adding them if they aren't there yet.
+///
+/// # Returns
+/// The author's index.
+fn author_index(authors: &mut Authors, author: Author) -> usize {
+    match authors.iter().position(|(a, _)| *a == author) {
+        Some(index) => index,
+        None => {
+            authors.push((author, LineCounts::default()));
+            authors.len() - 1
+        }
+    }
+}
+
+/// Blames many files at once, spread over some threads. Each thread opens the repositories
+/// it needs for itself.
+///
+/// # Parameters
+/// - `files`: The files.
+/// - `threads`: How many threads to blame with.
+/// - `explain`: Whether to print the files that couldn't be blamed, and why, to standard
+///   error.
+///
+/// # Returns
+/// Who wrote how many lines of each file, in the same order as `files`, or `None` for the
+/// files that aren't in a git repository or couldn't be blamed.
+pub fn blame_files(files: &[&FileStats], threads: usize, explain: bool) -> Vec<Option<Authors>> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![None; files.len()]);
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, files.len().max(1)) {
+            scope.spawn(|| {
+                let mut blamer = Blamer::default();
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let file = match files.get(i) {
+                        Some(file) => file,
+                        None => break,
+                    };
+
+                    let authors = match blamer.blame(&file.path, file.language) {
+                        Ok(authors) => authors,
+                        Err(e) => {
+                            if explain {
+                                eprintln!("couldn't blame {}: {}", file.path.display(), e);
+                            }
+
+                            None
+                        }
+                    };
+                    results.lock().unwrap()[i] = authors;
+                }
+            });
+        }
+    });
+
+    results.into_inner().unwrap()
+}
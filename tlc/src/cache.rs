@@ -0,0 +1,209 @@
+use crate::complexity::FileComplexity;
+use crate::count::LineCounts;
+use crate::dedupe::{ContentHash, Match};
+use crate::markers::Marker;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::{self, File, Metadata};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The version of the cache file format. Cache files from another version (or another
+/// version of `tlc`, whose language rules might be different) are thrown away.
+const CACHE_VERSION: u32 = 7;
+
+/// Files modified this close to the start of a run aren't cached, in case they're modified
+/// again without their modification time changing.
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Gets the directory that caches are kept in by default: `tlc` in the user's cache
+/// directory (e.g. `~/.cache/tlc`).
+pub fn default_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("tlc"))
+}
+
+/// What a file looked like on disk. If any of this changes, the file is counted again.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    pub size: u64,
+    /// The modification time, as seconds and nanoseconds since the Unix epoch.
+    pub mtime: (u64, u32),
+    /// The inode number, on Unix; always 0 elsewhere.
+    pub inode: u64,
+}
+
+impl Stamp {
+    pub fn of(meta: &Metadata) -> Self {
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        Stamp {
+            size: meta.len(),
+            mtime: (mtime.as_secs(), mtime.subsec_nanos()),
+            inode: inode(meta),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn inode(meta: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_meta: &Metadata) -> u64 {
+    0
+}
+
+/// Everything that was worked out about a file the last time it was looked at.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub stamp: Stamp,
+    /// The name of the language detected from the file's name and contents, if any.
+    pub detected: Option<String>,
+    pub binary: bool,
+    /// The name of the language the file was counted as, and its counts. This is `None` if
+    /// the file was skipped, and can differ from `detected` when `--check` is used.
+    pub counted: Option<(String, LineCounts)>,
+    /// The name of each language embedded in the file, and the counts of its lines, if the
+    /// file was counted.
+    pub embedded: Vec<(String, LineCounts)>,
+    /// The hash of the file's contents, and how it was hashed, if it was.
+    pub hash: Option<(Match, ContentHash)>,
+    /// How complex the file's code is, if that was measured.
+    pub complexity: Option<FileComplexity>,
+    /// The tags that were looked for in the file's comments, and the markers found, if
+    /// they were looked for.
+    pub markers: Option<(Vec<String>, Vec<Marker>)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    tlc_version: String,
+    entries: HashMap<String, Entry>,
+}
+
+/// The cached results for one root, shared by every thread of a walk. Entries are looked up
+/// in what was loaded, and everything seen during the walk is collected to be saved, so
+/// files that have since been deleted are dropped from the cache.
+pub struct Cache {
+    path: PathBuf,
+    old: HashMap<String, Entry>,
+    new: Mutex<HashMap<String, Entry>>,
+    started: SystemTime,
+}
+
+impl Cache {
+    /// Loads the cache for a root. A missing, unreadable, or outdated cache file is treated
+    /// as empty.
+    ///
+    /// # Parameters
+    /// - `dir`: The cache directory.
+    /// - `paths`: The paths that make up the root. Each different set of paths gets its own
+    ///   cache file.
+    /// - `rebuild`: Whether to ignore what's in the cache file and start over.
+    pub fn load(dir: &Path, paths: &[PathBuf], rebuild: bool) -> Self {
+        let mut hasher = DefaultHasher::new();
+        for path in paths {
+            fs::canonicalize(path)
+                .unwrap_or_else(|_| path.clone())
+                .hash(&mut hasher);
+        }
+
+        let path = dir.join(format!("{:016x}.json", hasher.finish()));
+        let old = if rebuild {
+            HashMap::new()
+        } else {
+            read_entries(&path).unwrap_or_default()
+        };
+
+        Cache {
+            path,
+            old,
+            new: Mutex::new(HashMap::new()),
+            started: SystemTime::now(),
+        }
+    }
+
+    /// Looks up a file.
+    ///
+    /// # Parameters
+    /// - `path`: The path the file was reached through.
+    /// - `stamp`: What the file looks like on disk now.
+    ///
+    /// # Returns
+    /// The cached entry, or `None` if there isn't one or the file has changed since.
+    pub fn get(&self, path: &Path, stamp: &Stamp) -> Option<&Entry> {
+        self.old
+            .get(path.to_string_lossy().as_ref())
+            .filter(|entry| entry.stamp == *stamp)
+    }
+
+    /// Records what was worked out about a file, to be saved at the end of the walk.
+    pub fn insert(&self, path: &Path, entry: Entry) {
+        let (secs, nanos) = entry.stamp.mtime;
+        let mtime = UNIX_EPOCH + Duration::new(secs, nanos);
+        if mtime + RACY_WINDOW >= self.started {
+            return;
+        }
+
+        if let Ok(mut new) = self.new.lock() {
+            new.insert(path.to_string_lossy().into_owned(), entry);
+        }
+    }
+
+    /// Keeps what was loaded about a file that wasn't looked at again (because it's known
+    /// not to have changed), so that saving doesn't drop it from the cache.
+    pub fn keep(&self, path: &Path) {
+        let key = path.to_string_lossy();
+        if let Some(entry) = self.old.get(key.as_ref()) {
+            if let Ok(mut new) = self.new.lock() {
+                new.insert(key.into_owned(), entry.clone());
+            }
+        }
+    }
+
+    /// Writes everything that was seen during the walk back to the cache file, replacing
+    /// what was there.
+    pub fn save(self) -> io::Result<()> {
+        let entries = self
+            .new
+            .into_inner()
+            .map_err(|_| io::Error::other("cache lock poisoned"))?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // Write to a temporary file first, so that an interrupted run can't leave a
+        // half-written cache behind.
+        let temp = self.path.with_extension("tmp");
+        let mut out = BufWriter::new(File::create(&temp)?);
+        let file = CacheFile {
+            version: CACHE_VERSION,
+            tlc_version: env!("CARGO_PKG_VERSION").to_string(),
+            entries,
+        };
+        serde_json::to_writer(&mut out, &file)?;
+        out.flush()?;
+        drop(out);
+        fs::rename(temp, &self.path)
+    }
+}
+
+fn read_entries(path: &Path) -> Option<HashMap<String, Entry>> {
+    let file = File::open(path).ok()?;
+    let file: CacheFile = serde_json::from_reader(BufReader::new(file)).ok()?;
+    if file.version != CACHE_VERSION || file.tlc_version != env!("CARGO_PKG_VERSION") {
+        return None;
+    }
+
+    Some(file.entries)
+}
@@ -0,0 +1,732 @@
+use crate::count::CodeSink;
+use crate::language::Complexity;
+use serde::{Deserialize, Serialize};
+
+/// How complex one file is.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileComplexity {
+    /// Every named function in the file, in the order they start.
+    pub functions: Vec<FunctionComplexity>,
+    /// The deepest that blocks are nested anywhere in the file.
+    pub max_depth: usize,
+    /// The approximate cyclomatic complexity of the whole file: one for each function
+    /// (or one, if there are none), plus one for each branch.
+    pub complexity: usize,
+}
+
+/// How complex one function is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionComplexity {
+    pub name: String,
+    /// The line that the function starts on, counting from 1.
+    pub line: usize,
+    /// The number of lines from the start of the function to the end of its body.
+    pub lines: usize,
+    /// The deepest that blocks are nested in the function, counting its body as 1.
+    pub max_depth: usize,
+    /// The approximate cyclomatic complexity: one, plus one for each branch.
+    pub complexity: usize,
+}
+
+/// Words that look like a call when followed by `(`, but never name a function.
+const NOT_FUNCTIONS: &[&str] = &[
+    "if",
+    "else",
+    "for",
+    "foreach",
+    "while",
+    "do",
+    "switch",
+    "case",
+    "catch",
+    "try",
+    "when",
+    "return",
+    "throw",
+    "await",
+    "new",
+    "delete",
+    "sizeof",
+    "alignof",
+    "typeof",
+    "decltype",
+    "defined",
+    "synchronized",
+    "using",
+    "lock",
+    "fixed",
+    "with",
+    "function",
+];
+
+/// A piece of code, split up the way the analyzer needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    None,
+    /// A word, such as a name, keyword, or number.
+    Word(String),
+    /// A run of operator characters, such as `&&` or `->`.
+    Op(String),
+    /// A bracket, `;`, `,`, or string delimiter, which always stands alone.
+    Punct(u8),
+}
+
+/// A function that has been started by a keyword, but whose body hasn't started yet.
+struct Signature {
+    name: Option<String>,
+    line: usize,
+    /// How many brackets were open at the keyword.
+    parens: usize,
+    /// Whether the keyword was right before `(`, as in Go's methods and anonymous functions.
+    receiver: bool,
+    /// Whether the name has been followed by `(`.
+    named: bool,
+    /// How many `<` are open, so that generic parameters aren't taken for the name.
+    angles: usize,
+    /// Whether `=` (or, with indented blocks, `:`) came after the name, so the body might
+    /// be an expression.
+    expression: bool,
+}
+
+impl Signature {
+    /// Whether enough of the signature has been seen to know it's a named function.
+    fn ready(&self) -> bool {
+        self.named || (self.name.is_some() && !self.receiver)
+    }
+}
+
+/// A function that we're in the body of.
+struct Frame {
+    name: String,
+    line: usize,
+    /// The depth of the function's body, or `None` if the body is an expression that ends
+    /// with the line.
+    body: Option<usize>,
+    branches: usize,
+    max_depth: usize,
+}
+
+/// How far through finding a line's indentation we are.
+#[derive(PartialEq)]
+enum Indent {
+    /// No code has been seen on the line yet.
+    Fresh,
+    /// The line starts with code, but only whitespace has been seen so far.
+    Measuring,
+    /// The indentation is known, or can't be (because the line starts in a string).
+    Done,
+}
+
+/// Measures the complexity of code as it is classified, by picking out functions, blocks,
+/// and branches from the tokens on each line. It doesn't parse anything, so the results are
+/// only approximate, but they're good enough to find the hairiest code.
+pub struct Analyzer {
+    rules: &'static Complexity,
+    /// The line being analyzed, counting from 1.
+    line: usize,
+    /// The last line that had any code on it.
+    last_code_line: usize,
+    /// The part of a word or operator that might continue in the next run of code.
+    word: Vec<u8>,
+    op: Vec<u8>,
+    /// Where the next run of code has to start to continue the word or operator.
+    next_offset: usize,
+    prev: Token,
+    prev2: Token,
+    /// How many `(` and `[` (and, with indented blocks, `{`) are open.
+    parens: usize,
+    /// How deeply blocks are nested right now.
+    depth: usize,
+    max_depth: usize,
+    /// The indentation of each indented block that is open.
+    indents: Vec<usize>,
+    indent: Indent,
+    /// Whether the previous line opened an indented block.
+    block_pending: bool,
+    /// Whether the previous line was continued with `\`.
+    continued: bool,
+    signature: Option<Signature>,
+    /// A name that was followed by `(` in the current statement, as in C.
+    candidate: Option<(String, usize)>,
+    frames: Vec<Frame>,
+    branches: usize,
+    functions: Vec<FunctionComplexity>,
+}
+
+impl Analyzer {
+    pub fn new(rules: &'static Complexity) -> Self {
+        Analyzer {
+            rules,
+            line: 1,
+            last_code_line: 0,
+            word: vec![],
+            op: vec![],
+            next_offset: 0,
+            prev: Token::None,
+            prev2: Token::None,
+            parens: 0,
+            depth: 0,
+            max_depth: 0,
+            indents: vec![],
+            indent: Indent::Fresh,
+            block_pending: false,
+            continued: false,
+            signature: None,
+            candidate: None,
+            frames: vec![],
+            branches: 0,
+            functions: vec![],
+        }
+    }
+
+    /// Closes anything still open at the end of the file.
+    ///
+    /// # Returns
+    /// The complexity of everything that was analyzed.
+    pub fn finish(mut self) -> FileComplexity {
+        self.flush();
+        while !self.frames.is_empty() {
+            self.close_frame();
+        }
+
+        let mut functions = self.functions;
+        functions.sort_by_key(|f| f.line);
+        FileComplexity {
+            complexity: self.branches + functions.len().max(1),
+            functions,
+            max_depth: self.max_depth,
+        }
+    }
+
+    /// Handles the word or operator that was being built up, if there is one.
+    fn flush(&mut self) {
+        if !self.word.is_empty() {
+            let word = String::from_utf8_lossy(&self.word).into_owned();
+            self.word.clear();
+            self.token(Token::Word(word));
+        }
+
+        if !self.op.is_empty() {
+            let op = String::from_utf8_lossy(&self.op).into_owned();
+            self.op.clear();
+            self.token(Token::Op(op));
+        }
+    }
+
+    fn token(&mut self, token: Token) {
+        // Whatever follows `=` decides whether the body is a block or an expression.
+        if let Some(sig) = self.signature.as_ref().filter(|sig| sig.expression) {
+            if token != Token::Punct(b'{') || self.rules.indented_blocks {
+                let (name, line) = (sig.name.clone().unwrap_or_default(), sig.line);
+                self.signature = None;
+                self.open_frame(name, line, None);
+            }
+        }
+
+        self.count_branches(&token);
+        match &token {
+            Token::Word(word) => self.word_token(word),
+            Token::Op(op) => self.op_token(op),
+            Token::Punct(b) => self.punct_token(*b),
+            Token::None => {}
+        }
+
+        self.prev2 = std::mem::replace(&mut self.prev, token);
+    }
+
+    fn count_branches(&mut self, token: &Token) {
+        let n = match token {
+            Token::Word(word) => self.rules.branches.contains(&word.as_str()) as usize,
+            Token::Op(op) => count_operators(op.as_bytes(), self.rules.branches),
+            _ => 0,
+        };
+        if n > 0 {
+            self.branches += n;
+            if let Some(frame) = self.frames.last_mut() {
+                frame.branches += n;
+            }
+        }
+    }
+
+    fn word_token(&mut self, word: &str) {
+        if self.rules.function_keywords.contains(&word) {
+            // A keyword inside the parameters of a function that is being declared is a
+            // type, as in `fn apply(f: fn(u8))`.
+            if self
+                .signature
+                .as_ref()
+                .is_none_or(|sig| self.parens <= sig.parens)
+            {
+                self.signature = Some(Signature {
+                    name: None,
+                    line: self.line,
+                    parens: self.parens,
+                    receiver: false,
+                    named: false,
+                    angles: 0,
+                    expression: false,
+                });
+                self.candidate = None;
+            }
+
+            return;
+        }
+
+        if let Some(sig) = &mut self.signature {
+            if !sig.named && sig.angles == 0 && self.parens == sig.parens {
+                sig.name = Some(word.to_string());
+            }
+        } else if self.rules.c_style_functions && NOT_FUNCTIONS.contains(&word) {
+            self.candidate = None;
+        }
+    }
+
+    fn op_token(&mut self, op: &str) {
+        if let Some(sig) = &mut self.signature {
+            if self.parens == sig.parens {
+                // With indented blocks, `:` can also be followed by the body on the same
+                // line, as in `def f(): pass`.
+                let body = if self.rules.indented_blocks { ":" } else { "=" };
+                if op == body && sig.ready() {
+                    sig.expression = true;
+                } else if !sig.named {
+                    for b in op.bytes() {
+                        match b {
+                            b'<' => sig.angles += 1,
+                            b'>' => sig.angles = sig.angles.saturating_sub(1),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        } else if op == "=" && self.parens == 0 {
+            self.candidate = None;
+        }
+    }
+
+    fn punct_token(&mut self, b: u8) {
+        let blocks = !self.rules.indented_blocks;
+        match b {
+            b'(' | b'[' => {
+                if b == b'(' {
+                    self.open_paren();
+                }
+
+                self.parens += 1;
+            }
+            b'{' if !blocks => self.parens += 1,
+            b')' | b']' | b'}' if !blocks || b != b'}' => {
+                self.parens = self.parens.saturating_sub(1);
+            }
+            b'{' => {
+                if self.parens == 0 || self.signature.is_some() {
+                    self.open_block();
+                }
+
+                self.depth += 1;
+                self.deepen();
+            }
+            b'}' => {
+                self.depth = self.depth.saturating_sub(1);
+                while self
+                    .frames
+                    .last()
+                    .and_then(|frame| frame.body)
+                    .is_some_and(|body| self.depth < body)
+                {
+                    self.close_frame();
+                }
+
+                self.signature = None;
+                self.candidate = None;
+            }
+            b';' => {
+                if self
+                    .signature
+                    .as_ref()
+                    .is_some_and(|sig| self.parens <= sig.parens)
+                {
+                    self.signature = None;
+                }
+
+                if self.parens == 0 {
+                    self.candidate = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Notes a `(` that might start the parameters of a function.
+    fn open_paren(&mut self) {
+        if let Some(sig) = &mut self.signature {
+            if self.parens == sig.parens && sig.angles == 0 && !sig.named {
+                if sig.name.is_some() {
+                    sig.named = true;
+                } else {
+                    sig.receiver = true;
+                }
+            }
+
+            return;
+        }
+
+        if !self.rules.c_style_functions || self.parens > 0 || self.candidate.is_some() {
+            return;
+        }
+
+        // Annotations (`@Test(...)`) and constructor calls (`new Foo(...)`) aren't
+        // functions either.
+        let after = |token: &Token, s: &str| match token {
+            Token::Op(op) => op.ends_with(s),
+            Token::Word(word) => word == s,
+            _ => false,
+        };
+        if let Token::Word(word) = &self.prev {
+            if !NOT_FUNCTIONS.contains(&word.as_str())
+                && !after(&self.prev2, "@")
+                && !after(&self.prev2, "new")
+                && !word.starts_with(|c: char| c.is_ascii_digit())
+            {
+                self.candidate = Some((word.clone(), self.line));
+            }
+        }
+    }
+
+    /// Handles a `{` that opens a block, which might be the body of a function.
+    fn open_block(&mut self) {
+        let function = match self.signature.take() {
+            Some(sig) if sig.ready() => sig.name.zip(Some(sig.line)),
+            Some(_) => None,
+            None => self.candidate.take(),
+        };
+        self.candidate = None;
+        if let Some((name, line)) = function {
+            self.open_frame(name, line, Some(self.depth + 1));
+        }
+    }
+
+    fn open_frame(&mut self, name: String, line: usize, body: Option<usize>) {
+        self.frames.push(Frame {
+            name,
+            line,
+            body,
+            branches: 0,
+            max_depth: 0,
+        });
+    }
+
+    fn close_frame(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            self.functions.push(FunctionComplexity {
+                name: frame.name,
+                line: frame.line,
+                lines: self.last_code_line.max(frame.line) - frame.line + 1,
+                max_depth: frame.max_depth,
+                complexity: frame.branches + 1,
+            });
+        }
+    }
+
+    /// Updates the deepest nesting, now that another block has been opened.
+    fn deepen(&mut self) {
+        self.max_depth = self.max_depth.max(self.depth);
+        for frame in &mut self.frames {
+            if let Some(body) = frame.body {
+                frame.max_depth = frame.max_depth.max(self.depth + 1 - body);
+            }
+        }
+    }
+
+    /// Opens and closes indented blocks, now that the indentation of a line is known.
+    fn indented(&mut self, indent: usize) {
+        while self.indents.last().is_some_and(|&top| top > indent) {
+            self.indents.pop();
+            self.depth -= 1;
+            while self
+                .frames
+                .last()
+                .and_then(|frame| frame.body)
+                .is_some_and(|body| self.depth < body)
+            {
+                self.close_frame();
+            }
+        }
+
+        if self.block_pending {
+            self.block_pending = false;
+            if let Some((name, line)) = self
+                .signature
+                .take()
+                .filter(Signature::ready)
+                .and_then(|sig| sig.name.zip(Some(sig.line)))
+            {
+                self.open_frame(name, line, Some(self.depth + 1));
+            }
+
+            self.indents.push(indent);
+            self.depth += 1;
+            self.deepen();
+        }
+    }
+}
+
+impl CodeSink for Analyzer {
+    fn code(&mut self, code: &[u8], offset: usize) {
+        if offset != self.next_offset {
+            self.flush();
+        }
+        self.next_offset = offset + code.len();
+
+        if self.indent == Indent::Fresh {
+            self.indent = if offset == 0 {
+                Indent::Measuring
+            } else {
+                Indent::Done
+            };
+        }
+
+        for (i, &b) in code.iter().enumerate() {
+            if b.is_ascii_whitespace() {
+                self.flush();
+                continue;
+            }
+
+            if self.indent == Indent::Measuring {
+                self.indent = Indent::Done;
+                if self.rules.indented_blocks && self.parens == 0 && !self.continued {
+                    self.indented(offset + i);
+                }
+            }
+
+            self.last_code_line = self.line;
+            if b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80 {
+                if !self.op.is_empty() {
+                    self.flush();
+                }
+
+                self.word.push(b);
+            } else if b"()[]{};,\"'`".contains(&b) {
+                self.flush();
+                self.token(Token::Punct(b));
+            } else {
+                if !self.word.is_empty() {
+                    self.flush();
+                }
+
+                self.op.push(b);
+            }
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.flush();
+        if self.rules.indented_blocks && self.last_code_line == self.line {
+            let last = match &self.prev {
+                Token::Op(op) => op.bytes().last(),
+                Token::Punct(b) => Some(*b),
+                _ => None,
+            };
+            self.continued = last == Some(b'\\');
+            if !self.continued && self.parens == 0 {
+                self.block_pending = last == Some(b':');
+            }
+        }
+
+        // An expression body ends with its line, unless it's still in brackets.
+        while self.frames.last().is_some_and(|frame| frame.body.is_none()) && self.parens == 0 {
+            self.close_frame();
+        }
+
+        self.line += 1;
+        self.next_offset = 0;
+        self.indent = Indent::Fresh;
+    }
+}
+
+/// Counts the branch operators in a run of operator characters, such as the `&&` in `&&!`.
+fn count_operators(op: &[u8], branches: &[&str]) -> usize {
+    let mut n = 0;
+    let mut i = 0;
+    while i < op.len() {
+        let found = branches
+            .iter()
+            .filter(|branch| !branch.starts_with(|c: char| c.is_ascii_alphanumeric()))
+            .filter(|branch| op[i..].starts_with(branch.as_bytes()))
+            .map(|branch| branch.len())
+            .max();
+        match found {
+            Some(len) => {
+                n += 1;
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::count::count_text_into;
+    use crate::language::{self, Language};
+
+    fn analyze(lang: &str, text: &str) -> FileComplexity {
+        let lang: &'static Language = language::from_name(lang).unwrap();
+        let mut analyzer = Analyzer::new(lang.complexity.unwrap());
+        count_text_into(text.as_bytes(), lang, &mut analyzer);
+        analyzer.finish()
+    }
+
+    fn summary(file: &FileComplexity) -> Vec<(&str, usize, usize, usize, usize)> {
+        file.functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.line, f.lines, f.max_depth, f.complexity))
+            .collect()
+    }
+
+    #[test]
+    fn rust_functions() {
+        let file = analyze(
+            "rust",
+            "\
+struct S;
+
+impl S {
+    fn new() -> Self {
+        S
+    }
+
+    fn check<T: Into<u8>>(&self, x: T, f: fn(u8) -> bool) -> bool {
+        // if this were real code
+        let x = x.into();
+        if x > 1 && f(x) {
+            for _ in 0..x {
+                println!(\"{{ if\");
+            }
+        }
+        match x {
+            0 => true,
+            _ => false,
+        }
+    }
+}
+
+trait T {
+    fn declared(&self);
+}
+",
+        );
+        assert_eq!(
+            summary(&file),
+            [("new", 4, 3, 1, 1), ("check", 8, 13, 3, 6)]
+        );
+        assert_eq!(file.max_depth, 4);
+        assert_eq!(file.complexity, 7);
+    }
+
+    #[test]
+    fn python_functions() {
+        let file = analyze(
+            "python",
+            "\
+import os
+
+def outer(a,
+          b):
+    \"\"\"Docs, with if and for.
+\"\"\".strip()
+    if a and b:
+        return 1
+
+    # a comment at the wrong indentation
+    def inner(c):
+        while c: c -= 1
+    return inner
+
+class C:
+    def method(self): return {
+        'a': 1 if self else 2,
+    }
+",
+        );
+        assert_eq!(
+            summary(&file),
+            [
+                ("outer", 3, 11, 2, 3),
+                ("inner", 11, 2, 1, 2),
+                ("method", 16, 3, 0, 2)
+            ]
+        );
+        assert_eq!(file.max_depth, 2);
+        assert_eq!(file.complexity, 7);
+    }
+
+    #[test]
+    fn c_style_functions() {
+        let file = analyze(
+            "java",
+            "\
+@Entity(name = \"x\")
+public class Foo extends Bar {
+    @Override
+    public int size(List<String> a) throws IOException {
+        if (a.isEmpty() || a.get(0) == null) {
+            return new Thing(a) {
+                int x() { return 0; }
+            }.x();
+        }
+        for (String s : a) {
+            switch (s) {
+                case \"a\": break;
+                case \"b\": break;
+            }
+        }
+        return 1;
+    }
+}
+",
+        );
+        assert_eq!(summary(&file), [("size", 4, 14, 4, 6), ("x", 7, 1, 1, 1)]);
+        assert_eq!(file.max_depth, 5);
+    }
+
+    #[test]
+    fn go_methods_and_literals() {
+        let file = analyze(
+            "go",
+            "\
+func (s *Server) Handle(w Writer) error {
+    go func(x int) int {
+        return x
+    }(1)
+    if s == nil || w == nil {
+        return nil
+    }
+    return nil
+}
+",
+        );
+        assert_eq!(summary(&file), [("Handle", 1, 9, 2, 3)]);
+    }
+
+    #[test]
+    fn files_without_functions_count_as_one() {
+        let file = analyze("c", "int x = 1;\n");
+        assert_eq!(file.functions, []);
+        assert_eq!(file.complexity, 1);
+        assert_eq!(file.max_depth, 0);
+    }
+
+    #[test]
+    fn operators_are_found_inside_runs() {
+        let branches = &["if", "&&", "||", "?", "??"];
+        assert_eq!(count_operators(b"&&!", branches), 1);
+        assert_eq!(count_operators(b")||!(", branches), 1);
+        assert_eq!(count_operators(b"??", branches), 1);
+        assert_eq!(count_operators(b"?.", branches), 1);
+        assert_eq!(count_operators(b"->", branches), 0);
+    }
+}
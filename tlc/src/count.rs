@@ -0,0 +1,632 @@
+use crate::language::Language;
+use memchr::{memchr, memchr2, memchr_iter};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, ErrorKind};
+use std::ops::{AddAssign, SubAssign};
+
+/// The number of code, comment, and blank lines in some file (or group of files).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineCounts {
+    pub code: usize,
+    pub comment: usize,
+    pub blank: usize,
+}
+
+impl LineCounts {
+    /// The total number of lines, regardless of kind.
+    pub fn lines(&self) -> usize {
+        self.code + self.comment + self.blank
+    }
+}
+
+impl AddAssign for LineCounts {
+    fn add_assign(&mut self, rhs: Self) {
+        self.code += rhs.code;
+        self.comment += rhs.comment;
+        self.blank += rhs.blank;
+    }
+}
+
+impl SubAssign for LineCounts {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.code -= rhs.code;
+        self.comment -= rhs.comment;
+        self.blank -= rhs.blank;
+    }
+}
+
+/// Receives the code (and comments) on each line as it is classified, for measuring more
+/// than line counts.
+pub trait CodeSink {
+    /// Called with each run of code on a line, outside of comments and strings. A string
+    /// is passed as just its opening delimiter.
+    ///
+    /// # Parameters
+    /// - `code`: The run of code.
+    /// - `offset`: Where the run starts in the line.
+    fn code(&mut self, code: &[u8], offset: usize);
+
+    /// Called with each run of comment text on a line, without the comment delimiters. A
+    /// single comment can be passed in more than one run.
+    fn comment(&mut self, _text: &[u8]) {}
+
+    /// Called at the end of every line, after any runs of code on it.
+    fn end_line(&mut self);
+}
+
+/// Throws the code away, for when only line counts are wanted.
+impl CodeSink for () {
+    fn code(&mut self, _code: &[u8], _offset: usize) {}
+    fn end_line(&mut self) {}
+}
+
+impl<S: CodeSink + ?Sized> CodeSink for &mut S {
+    fn code(&mut self, code: &[u8], offset: usize) {
+        (**self).code(code, offset);
+    }
+
+    fn comment(&mut self, text: &[u8]) {
+        (**self).comment(text);
+    }
+
+    fn end_line(&mut self) {
+        (**self).end_line();
+    }
+}
+
+/// A sink that might not be there, for measurements that are only sometimes wanted.
+impl<S: CodeSink> CodeSink for Option<S> {
+    fn code(&mut self, code: &[u8], offset: usize) {
+        if let Some(sink) = self {
+            sink.code(code, offset);
+        }
+    }
+
+    fn comment(&mut self, text: &[u8]) {
+        if let Some(sink) = self {
+            sink.comment(text);
+        }
+    }
+
+    fn end_line(&mut self) {
+        if let Some(sink) = self {
+            sink.end_line();
+        }
+    }
+}
+
+/// Passes everything to both sinks.
+impl<A: CodeSink, B: CodeSink> CodeSink for (A, B) {
+    fn code(&mut self, code: &[u8], offset: usize) {
+        self.0.code(code, offset);
+        self.1.code(code, offset);
+    }
+
+    fn comment(&mut self, text: &[u8]) {
+        self.0.comment(text);
+        self.1.comment(text);
+    }
+
+    fn end_line(&mut self) {
+        self.0.end_line();
+        self.1.end_line();
+    }
+}
+
+/// What we were in the middle of when the previous line ended.
+enum State {
+    Normal,
+    /// Inside a block comment. Holds the index of the delimiter pair in
+    /// `Language::block_comments` and the current nesting depth.
+    BlockComment(usize, usize),
+    /// Inside a (multi-line) string. Holds the closing delimiter.
+    Str(&'static str),
+}
+
+/// Classifies lines one at a time. Block comments and strings can span lines, so this
+/// needs to remember where the previous line left off.
+///
+/// Lines are looked at as raw bytes. Every comment and string delimiter is ASCII, so this
+/// works the same for UTF-8, Latin-1, or any other ASCII-compatible encoding, and invalid
+/// UTF-8 is never a problem.
+pub struct Classifier<'a> {
+    lang: &'a Language,
+    state: State,
+    /// Whether the language has no comments or strings at all, so that every line is
+    /// either blank or code.
+    plain: bool,
+    /// Which bytes can start a comment or a string, so that everything else can be skipped
+    /// over quickly.
+    starts: [bool; 256],
+}
+
+impl<'a> Classifier<'a> {
+    pub fn new(lang: &'a Language) -> Self {
+        let mut starts = [false; 256];
+        let delimiters = lang
+            .line_comments
+            .iter()
+            .chain(lang.block_comments.iter().map(|(start, _)| start))
+            .chain(lang.quotes.iter().map(|(start, _)| start));
+        for delimiter in delimiters {
+            if let Some(&b) = delimiter.as_bytes().first() {
+                starts[b as usize] = true;
+            }
+        }
+        if lang.char_literals {
+            starts[b'\'' as usize] = true;
+        }
+
+        Classifier {
+            lang,
+            state: State::Normal,
+            starts,
+            plain: lang.line_comments.is_empty()
+                && lang.block_comments.is_empty()
+                && lang.quotes.is_empty()
+                && !lang.char_literals,
+        }
+    }
+
+    /// Classifies the next line of the file and adds it to `counts`.
+    ///
+    /// A line that contains any code at all counts as code, even if it also has a comment
+    /// on it. A line that is only whitespace is blank, unless it is in the middle of a
+    /// string (in which case it is part of that string, so code).
+    ///
+    /// # Parameters
+    /// - `line`: The line, without its line terminator.
+    /// - `counts`: The counts to update.
+    pub fn push_line(&mut self, line: &[u8], counts: &mut LineCounts) {
+        self.push_line_into(line, counts, &mut ());
+    }
+
+    /// The same as `push_line`, but also passes the code on the line to `sink`.
+    pub fn push_line_into<S: CodeSink>(
+        &mut self,
+        line: &[u8],
+        counts: &mut LineCounts,
+        sink: &mut S,
+    ) {
+        self.classify(line, counts, sink);
+        sink.end_line();
+    }
+
+    fn classify<S: CodeSink>(&mut self, line: &[u8], counts: &mut LineCounts, sink: &mut S) {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            match self.state {
+                State::Str(_) => counts.code += 1,
+                _ => counts.blank += 1,
+            }
+            return;
+        }
+
+        // Without any comment or string syntax, there's nothing else to look for.
+        if self.plain {
+            counts.code += 1;
+            sink.code(line, 0);
+            return;
+        }
+
+        let mut has_code = false;
+        let mut has_comment = false;
+        let mut rest = line;
+
+        while !rest.is_empty() {
+            match self.state {
+                State::BlockComment(idx, depth) => {
+                    has_comment = true;
+                    let (start, end) = self.lang.block_comments[idx];
+                    let skip = if self.lang.nested_comments {
+                        memchr2(end.as_bytes()[0], start.as_bytes()[0], rest)
+                    } else {
+                        memchr(end.as_bytes()[0], rest)
+                    };
+                    let skip = skip.unwrap_or(rest.len());
+                    sink.comment(&rest[..skip]);
+                    rest = &rest[skip..];
+                    if rest.is_empty() {
+                        break;
+                    }
+
+                    if rest.starts_with(end.as_bytes()) {
+                        rest = &rest[end.len()..];
+                        self.state = if depth > 1 {
+                            State::BlockComment(idx, depth - 1)
+                        } else {
+                            State::Normal
+                        };
+                        continue;
+                    }
+
+                    if self.lang.nested_comments && rest.starts_with(start.as_bytes()) {
+                        rest = &rest[start.len()..];
+                        self.state = State::BlockComment(idx, depth + 1);
+                        continue;
+                    }
+
+                    sink.comment(&rest[..1]);
+                }
+                State::Str(end) => {
+                    has_code = true;
+                    rest = &rest[memchr2(b'\\', end.as_bytes()[0], rest).unwrap_or(rest.len())..];
+                    if rest.is_empty() {
+                        break;
+                    }
+
+                    if rest[0] == b'\\' {
+                        rest = &rest[rest.len().min(2)..];
+                        continue;
+                    }
+
+                    if rest.starts_with(end.as_bytes()) {
+                        rest = &rest[end.len()..];
+                        self.state = State::Normal;
+                        continue;
+                    }
+                }
+                State::Normal => {
+                    let run = rest
+                        .iter()
+                        .position(|&b| self.starts[b as usize])
+                        .unwrap_or(rest.len());
+                    if run > 0 {
+                        has_code |= rest[..run].iter().any(|b| !b.is_ascii_whitespace());
+                        sink.code(&rest[..run], line.len() - rest.len());
+                        rest = &rest[run..];
+                        continue;
+                    }
+
+                    // Block comments are checked first so that Lua's `--[[` isn't mistaken
+                    // for its `--` line comment.
+                    if let Some(idx) = self
+                        .lang
+                        .block_comments
+                        .iter()
+                        .position(|(start, _)| rest.starts_with(start.as_bytes()))
+                    {
+                        has_comment = true;
+                        rest = &rest[self.lang.block_comments[idx].0.len()..];
+                        self.state = State::BlockComment(idx, 1);
+                        continue;
+                    }
+
+                    let not_comment = self
+                        .lang
+                        .not_line_comments
+                        .iter()
+                        .any(|n| rest.starts_with(n.as_bytes()));
+                    if let Some(start) = self
+                        .lang
+                        .line_comments
+                        .iter()
+                        .find(|c| !not_comment && rest.starts_with(c.as_bytes()))
+                    {
+                        has_comment = true;
+                        sink.comment(&rest[start.len()..]);
+                        break;
+                    }
+
+                    if let Some((start, end)) = self
+                        .lang
+                        .quotes
+                        .iter()
+                        .find(|(s, _)| rest.starts_with(s.as_bytes()))
+                    {
+                        has_code = true;
+                        sink.code(&rest[..start.len()], line.len() - rest.len());
+                        rest = &rest[start.len()..];
+                        self.state = State::Str(end);
+                        continue;
+                    }
+
+                    // A character literal is skipped over whole, like a string on one line,
+                    // while the `'` of a lifetime is just code.
+                    if self.lang.char_literals && rest[0] == b'\'' {
+                        has_code = true;
+                        sink.code(&rest[..1], line.len() - rest.len());
+                        rest = &rest[char_literal_len(rest).unwrap_or(1)..];
+                        continue;
+                    }
+
+                    has_code = true;
+                    sink.code(&rest[..1], line.len() - rest.len());
+                }
+            }
+
+            rest = &rest[1..];
+        }
+
+        if has_code {
+            counts.code += 1;
+        } else if has_comment {
+            counts.comment += 1;
+        } else {
+            counts.blank += 1;
+        }
+    }
+}
+
+/// Finds the length of the character literal at the start of some text, such as `'x'`,
+/// `'\''`, or `'\u{1F600}'`.
+///
+/// # Returns
+/// The literal's length, with its quotes, or `None` if the `'` at the start doesn't start a
+/// character literal (such as the one in a lifetime, `'a`).
+fn char_literal_len(text: &[u8]) -> Option<usize> {
+    let end = match *text.get(1)? {
+        // An escape can be longer than one character, as in `'\x7f'` or `'\u{10FFFF}'`.
+        b'\\' => 3 + memchr(b'\'', text.get(3..text.len().min(13))?)?,
+        b'\'' => return None,
+        // The rest of a multibyte UTF-8 character.
+        lead if lead >= 0xf0 => 5,
+        lead if lead >= 0xe0 => 4,
+        lead if lead >= 0xc0 => 3,
+        _ => 2,
+    };
+    if text.get(end) == Some(&b'\'') {
+        Some(end + 1)
+    } else {
+        None
+    }
+}
+
+/// Strips the line terminator (`\n` or `\r\n`) off of a line.
+fn trim_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Splits text into lines the same way as `count_text`: on `\n` bytes, with a last line
+/// that has no line break still counting.
+///
+/// # Returns
+/// Each line, without its line terminator.
+pub fn lines(text: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+
+        let line = match memchr(b'\n', rest) {
+            Some(end) => {
+                let line = &rest[..end];
+                rest = &rest[end + 1..];
+                line
+            }
+            None => std::mem::take(&mut rest),
+        };
+        Some(trim_newline(line))
+    })
+}
+
+/// Reads everything from `reader` and classifies each line as the given language. Lines are
+/// split on `\n` bytes, and a last line without a line break still counts.
+///
+/// Lines are classified straight out of the reader's buffer. Only a line that runs past the
+/// end of the buffer is copied, so reading through a large buffer costs no allocations.
+///
+/// # Parameters
+/// - `reader`: Where to read the lines from.
+/// - `lang`: The language the lines are written in.
+///
+/// # Returns
+/// The line counts, or the first error from reading (other than being interrupted).
+pub fn count_lines<R: BufRead>(reader: R, lang: &Language) -> io::Result<LineCounts> {
+    count_lines_into(reader, lang, &mut ())
+}
+
+/// The same as `count_lines`, but also passes the code on each line to `sink`.
+pub fn count_lines_into<R: BufRead, S: CodeSink>(
+    mut reader: R,
+    lang: &Language,
+    sink: &mut S,
+) -> io::Result<LineCounts> {
+    let mut counts = LineCounts::default();
+    let mut classifier = Classifier::new(lang);
+    // The start of a line that was cut off at the end of the previous buffer.
+    let mut partial = vec![];
+    loop {
+        let buf = match reader.fill_buf() {
+            Ok([]) => break,
+            Ok(buf) => buf,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        let mut start = 0;
+        for end in memchr_iter(b'\n', buf) {
+            if partial.is_empty() {
+                classifier.push_line_into(trim_newline(&buf[start..end]), &mut counts, sink);
+            } else {
+                partial.extend_from_slice(&buf[start..end]);
+                classifier.push_line_into(trim_newline(&partial), &mut counts, sink);
+                partial.clear();
+            }
+
+            start = end + 1;
+        }
+
+        partial.extend_from_slice(&buf[start..]);
+        let len = buf.len();
+        reader.consume(len);
+    }
+
+    if !partial.is_empty() {
+        classifier.push_line_into(trim_newline(&partial), &mut counts, sink);
+    }
+
+    Ok(counts)
+}
+
+/// The same as `count_lines`, but for text that is already in memory (or memory-mapped).
+///
+/// # Parameters
+/// - `text`: The text.
+/// - `lang`: The language the text is written in.
+///
+/// # Returns
+/// The line counts.
+pub fn count_text(text: &[u8], lang: &Language) -> LineCounts {
+    count_text_into(text, lang, &mut ())
+}
+
+/// The same as `count_text`, but also passes the code on each line to `sink`.
+pub fn count_text_into<S: CodeSink>(text: &[u8], lang: &Language, sink: &mut S) -> LineCounts {
+    let mut counts = LineCounts::default();
+    let mut classifier = Classifier::new(lang);
+    let mut start = 0;
+    for end in memchr_iter(b'\n', text) {
+        classifier.push_line_into(trim_newline(&text[start..end]), &mut counts, sink);
+        start = end + 1;
+    }
+
+    if start < text.len() {
+        classifier.push_line_into(trim_newline(&text[start..]), &mut counts, sink);
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use std::io::BufReader;
+
+    fn counts(code: usize, comment: usize, blank: usize) -> LineCounts {
+        LineCounts {
+            code,
+            comment,
+            blank,
+        }
+    }
+
+    fn count(text: &str, lang: &str) -> LineCounts {
+        count_text(text.as_bytes(), language::from_name(lang).unwrap())
+    }
+
+    #[test]
+    fn code_with_a_trailing_comment_is_code() {
+        let text = "// comment\nlet x = 1; // comment\n\n   \n";
+        assert_eq!(count(text, "rust"), counts(1, 1, 2));
+    }
+
+    /// Collects the comment text on each line.
+    #[derive(Default)]
+    struct Comments {
+        lines: Vec<String>,
+        line: String,
+    }
+
+    impl CodeSink for Comments {
+        fn code(&mut self, _code: &[u8], _offset: usize) {}
+
+        fn comment(&mut self, text: &[u8]) {
+            self.line.push_str(std::str::from_utf8(text).unwrap());
+        }
+
+        fn end_line(&mut self) {
+            self.lines.push(std::mem::take(&mut self.line));
+        }
+    }
+
+    #[test]
+    fn comment_text_is_passed_without_delimiters() {
+        let text = "let x = \"// no\"; // trailing\n/* one * two\n three */ code\n";
+        let mut comments = Comments::default();
+        let rust = language::from_name("rust").unwrap();
+        count_text_into(text.as_bytes(), rust, &mut comments);
+        assert_eq!(comments.lines, [" trailing", " one * two", " three "]);
+    }
+
+    #[test]
+    fn block_comments_span_lines() {
+        let text = "/* start\n\n   end */\nint x; /* c */\n";
+        assert_eq!(count(text, "c"), counts(1, 2, 1));
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        let rust = "/* outer /* inner */\nstill a comment */\nfn main() {}\n";
+        assert_eq!(count(rust, "rust"), counts(1, 2, 0));
+
+        // C doesn't nest, so the first `*/` ends the comment.
+        let c = "/* outer /* inner */\nstill code */\n";
+        assert_eq!(count(c, "c"), counts(1, 1, 0));
+    }
+
+    #[test]
+    fn comment_markers_in_strings_are_code() {
+        let text = "let url = \"http://example.com\";\nlet s = \"/* not a comment\";\nx();\n";
+        assert_eq!(count(text, "rust"), counts(3, 0, 0));
+    }
+
+    #[test]
+    fn blank_lines_in_strings_are_code() {
+        let text = "s = \"\"\"\n\ndocs\n\"\"\"\n";
+        assert_eq!(count(text, "python"), counts(4, 0, 0));
+    }
+
+    #[test]
+    fn escaped_quotes_do_not_end_strings() {
+        let text = "let s = \"a \\\" // b\";\n// comment\n";
+        assert_eq!(count(text, "rust"), counts(1, 1, 0));
+    }
+
+    #[test]
+    fn char_literals_are_not_strings() {
+        let text = "let q = '\"';\n// comment\nlet e = '\\'';\n// comment\n\
+                    fn f<'a>(s: &'a str) -> char { 'é' } // comment\n/* comment */\n";
+        assert_eq!(count(text, "rust"), counts(3, 3, 0));
+        assert_eq!(char_literal_len(b"'\\u{1F600}' x"), Some(11));
+        assert_eq!(char_literal_len(b"'a>"), None);
+        assert_eq!(char_literal_len(b"'"), None);
+    }
+
+    #[test]
+    fn php_attributes_are_not_comments() {
+        let text = "<?php\n#[Attribute]\nclass A {}\n# comment\n";
+        assert_eq!(count(text, "php"), counts(3, 1, 0));
+    }
+
+    #[test]
+    fn lua_block_comments_win_over_line_comments() {
+        let text = "--[[ start\nend ]]\nprint(1) -- done\n";
+        assert_eq!(count(text, "lua"), counts(1, 2, 0));
+    }
+
+    #[test]
+    fn crlf_and_missing_final_newline() {
+        let text = "a = 1\r\n\r\n# comment";
+        assert_eq!(count(text, "python"), counts(1, 1, 1));
+        assert_eq!(
+            lines(text.as_bytes()).collect::<Vec<_>>(),
+            [&b"a = 1"[..], b"", b"# comment"]
+        );
+        assert_eq!(lines(b"\n").count(), 1);
+        assert_eq!(lines(b"").count(), 0);
+    }
+
+    #[test]
+    fn languages_without_comments_count_every_line_as_code() {
+        assert_eq!(
+            count("some words\n\n# not a comment\n", "text"),
+            counts(2, 0, 1)
+        );
+    }
+
+    #[test]
+    fn count_lines_matches_count_text_across_buffer_boundaries() {
+        let text = "fn main() {\n    /* a\n\n b */ let s = \"x\n\ny\";\n}\n// end";
+        let rust = language::from_name("rust").unwrap();
+        for capacity in 1..=text.len() {
+            let reader = BufReader::with_capacity(capacity, text.as_bytes());
+            assert_eq!(
+                count_lines(reader, rust).unwrap(),
+                count_text(text.as_bytes(), rust)
+            );
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::walk::{Counted, FileStats};
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, Read};
+use xxhash_rust::xxh3::Xxh3;
+
+/// How alike two files have to be to count as copies of each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Match {
+    /// The same, byte for byte (not counting a byte order mark).
+    Exact,
+    /// The same once all whitespace is taken out, so that copies that were reindented,
+    /// rewrapped, or given different line endings still match.
+    Whitespace,
+}
+
+/// A 128-bit hash of a file's contents. It's written out as 32 hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentHash(pub u128);
+
+impl Display for ContentHash {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl Serialize for ContentHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        u128::from_str_radix(&hex, 16)
+            .map(ContentHash)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Hashes text a piece at a time.
+pub struct ContentHasher {
+    mode: Match,
+    state: Xxh3,
+}
+
+impl ContentHasher {
+    pub fn new(mode: Match) -> Self {
+        ContentHasher {
+            mode,
+            state: Xxh3::new(),
+        }
+    }
+
+    /// Adds the next piece of text to the hash. The text can be split up anywhere, even
+    /// in the middle of a line.
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self.mode {
+            Match::Exact => self.state.update(bytes),
+            Match::Whitespace => {
+                for run in bytes.split(u8::is_ascii_whitespace) {
+                    self.state.update(run);
+                }
+            }
+        }
+    }
+
+    pub fn finish(&self) -> ContentHash {
+        ContentHash(self.state.digest128())
+    }
+}
+
+/// Hashes text that is all in memory at once.
+pub fn hash(mode: Match, bytes: &[u8]) -> ContentHash {
+    let mut hasher = ContentHasher::new(mode);
+    hasher.update(bytes);
+    hasher.finish()
+}
+
+/// Wraps a reader, and hashes everything that is read through it (if a hash is wanted at
+/// all).
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Option<ContentHasher>,
+}
+
+impl<R: BufRead> HashingReader<R> {
+    pub fn new(inner: R, mode: Option<Match>) -> Self {
+        HashingReader {
+            inner,
+            hasher: mode.map(ContentHasher::new),
+        }
+    }
+
+    /// Gets the hash of everything that has been read so far.
+    pub fn finish(&self) -> Option<ContentHash> {
+        self.hasher.as_ref().map(ContentHasher::finish)
+    }
+}
+
+impl<R: BufRead> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..n]);
+        }
+
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for HashingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if let Some(hasher) = self.hasher.as_mut().filter(|_| amt > 0) {
+            // The bytes being consumed are still at the start of the buffer, so this
+            // doesn't read anything.
+            if let Ok(buf) = self.inner.fill_buf() {
+                hasher.update(&buf[..amt.min(buf.len())]);
+            }
+        }
+
+        self.inner.consume(amt);
+    }
+}
+
+/// Gets the hash that a file is grouped by. Files without a single line of code or
+/// comments are left out of every group, since otherwise every empty file would be a copy
+/// of every other one.
+fn group_hash(file: &FileStats) -> Option<ContentHash> {
+    file.hash
+        .filter(|_| file.counts.code + file.counts.comment > 0)
+}
+
+/// Files that all have the same contents.
+pub struct Group<'a> {
+    pub hash: ContentHash,
+    pub files: Vec<&'a FileStats>,
+}
+
+/// Finds every group of files with the same contents, across all of the roots.
+///
+/// # Parameters
+/// - `roots`: Everything that was counted under each root, with hashes.
+///
+/// # Returns
+/// The groups of two or more files, in the order that their first files were counted.
+/// Each group's files are in the order they were counted: by root, then by path.
+pub fn find_groups(roots: &[(String, Counted)]) -> Vec<Group<'_>> {
+    let mut groups: Vec<Group> = vec![];
+    let mut by_hash = HashMap::new();
+    for file in roots.iter().flat_map(|(_, counted)| &counted.files) {
+        let hash = match group_hash(file) {
+            Some(hash) => hash,
+            None => continue,
+        };
+
+        let idx = *by_hash.entry(hash).or_insert_with(|| {
+            groups.push(Group {
+                hash,
+                files: vec![],
+            });
+            groups.len() - 1
+        });
+        groups[idx].files.push(file);
+    }
+
+    groups.retain(|group| group.files.len() > 1);
+    groups
+}
+
+/// Takes out every copy of a file but the first, so that each group of files with the
+/// same contents (as found by `find_groups`) is only counted once.
+///
+/// # Parameters
+/// - `roots`: Everything that was counted under each root, with hashes.
+///
+/// # Returns
+/// The same roots, without the extra copies.
+pub fn remove_copies(roots: &[(String, Counted)]) -> Vec<(String, Counted)> {
+    let mut seen = HashSet::new();
+    roots
+        .iter()
+        .map(|(name, counted)| {
+            let files = counted
+                .files
+                .iter()
+                .filter(|file| group_hash(file).is_none_or(|hash| seen.insert(hash)))
+                .cloned()
+                .collect();
+            let deduped = Counted {
+                files,
+                binary: counted.binary.clone(),
+                skipped: counted.skipped.clone(),
+                errors: counted.errors.clone(),
+            };
+            (name.clone(), deduped)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn whitespace_mode_ignores_layout() {
+        let a = hash(Match::Whitespace, b"fn f(a: u8) {\n    a\n}\n");
+        let b = hash(Match::Whitespace, b"fn f(a:u8){\r\n\ta\r\n}");
+        assert_eq!(a, b);
+        assert_ne!(
+            hash(Match::Exact, b"fn f(a: u8) {\n    a\n}\n"),
+            hash(Match::Exact, b"fn f(a:u8){\r\n\ta\r\n}")
+        );
+    }
+
+    #[test]
+    fn hashes_do_not_depend_on_how_text_is_split() {
+        let text = b"let a = 1;\n  let b = 2;\n\nlet c = 3;\n";
+        for mode in [Match::Exact, Match::Whitespace] {
+            for capacity in 1..text.len() {
+                let inner = BufReader::with_capacity(capacity, &text[..]);
+                let mut reader = HashingReader::new(inner, Some(mode));
+                io::copy(&mut reader, &mut io::sink()).unwrap();
+                assert_eq!(reader.finish(), Some(hash(mode, text)));
+
+                let inner = BufReader::with_capacity(capacity, &text[..]);
+                let mut reader = HashingReader::new(inner, Some(mode));
+                while let Ok(n) = reader.fill_buf().map(|buf| buf.len().min(3)) {
+                    if n == 0 {
+                        break;
+                    }
+
+                    reader.consume(n);
+                }
+                assert_eq!(reader.finish(), Some(hash(mode, text)));
+            }
+        }
+    }
+
+    #[test]
+    fn hashes_round_trip_as_hex() {
+        let hash = ContentHash(0xABC);
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, "\"00000000000000000000000000000abc\"");
+        assert_eq!(serde_json::from_str::<ContentHash>(&json).unwrap(), hash);
+    }
+}
@@ -0,0 +1,281 @@
+use crate::report::{FileEntry, Totals, SCHEMA_VERSION};
+use crate::snapshot::Snapshot;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+/// The signed difference between two sets of totals.
+#[derive(Serialize, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Delta {
+    pub files: i64,
+    pub lines: i64,
+    pub code: i64,
+    pub comment: i64,
+    pub blank: i64,
+    pub bytes: i64,
+}
+
+impl Delta {
+    /// Gets how much `new` grew (or shrank) compared to `old`.
+    pub fn between(old: &Totals, new: &Totals) -> Self {
+        let diff = |old: u64, new: u64| new as i64 - old as i64;
+        Delta {
+            files: diff(old.files as u64, new.files as u64),
+            lines: diff(old.lines as u64, new.lines as u64),
+            code: diff(old.code as u64, new.code as u64),
+            comment: diff(old.comment as u64, new.comment as u64),
+            blank: diff(old.blank as u64, new.blank as u64),
+            bytes: diff(old.bytes, new.bytes),
+        }
+    }
+
+    /// Whether nothing changed.
+    pub fn is_zero(&self) -> bool {
+        *self == Delta::default()
+    }
+}
+
+/// What happened to a file between two snapshots.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl Status {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Added => "added",
+            Status::Removed => "removed",
+            Status::Changed => "changed",
+        }
+    }
+}
+
+/// Which snapshot one side of a diff is.
+#[derive(Serialize)]
+pub struct SnapshotInfo {
+    pub label: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A file that was added, removed, or changed.
+#[derive(Serialize)]
+pub struct FileChange {
+    pub path: String,
+    pub status: Status,
+    /// The file's language in the newer snapshot, or in the older one if it was removed.
+    pub language: String,
+    pub delta: Delta,
+}
+
+/// How the totals of one language changed.
+#[derive(Serialize)]
+pub struct LanguageChange {
+    pub language: String,
+    pub old: Totals,
+    pub new: Totals,
+    pub delta: Delta,
+}
+
+/// How the rolled-up totals of one directory changed.
+#[derive(Serialize)]
+pub struct DirectoryChange {
+    pub root: String,
+    /// The directory's path relative to its root; empty for the root itself.
+    pub path: String,
+    pub depth: usize,
+    pub old: Totals,
+    pub new: Totals,
+    pub delta: Delta,
+}
+
+/// How the grand total changed.
+#[derive(Serialize)]
+pub struct TotalChange {
+    pub old: Totals,
+    pub new: Totals,
+    pub delta: Delta,
+}
+
+/// Everything that changed between two snapshots. Only things that actually changed are
+/// listed.
+#[derive(Serialize)]
+pub struct Diff {
+    pub version: u32,
+    pub old: SnapshotInfo,
+    pub new: SnapshotInfo,
+    pub files: Vec<FileChange>,
+    pub languages: Vec<LanguageChange>,
+    pub directories: Vec<DirectoryChange>,
+    pub total: TotalChange,
+}
+
+/// The totals of a single file.
+fn file_totals(file: &FileEntry) -> Totals {
+    Totals {
+        files: 1,
+        lines: file.lines,
+        code: file.code,
+        comment: file.comment,
+        blank: file.blank,
+        bytes: file.bytes,
+    }
+}
+
+impl Diff {
+    /// Compares two snapshots.
+    ///
+    /// # Parameters
+    /// - `old`: The older snapshot.
+    /// - `new`: The newer snapshot.
+    /// - `max_depth`: How many levels below each root to list directories for, if limited.
+    ///
+    /// # Returns
+    /// The changes. Files are in path order; languages and directories are ordered from
+    /// the biggest change in lines to the smallest (directories keep tree order among
+    /// equal changes).
+    pub fn new(old: &Snapshot, new: &Snapshot, max_depth: Option<usize>) -> Self {
+        Diff {
+            version: SCHEMA_VERSION,
+            old: SnapshotInfo {
+                label: old.label.clone(),
+                timestamp: old.timestamp,
+            },
+            new: SnapshotInfo {
+                label: new.label.clone(),
+                timestamp: new.timestamp,
+            },
+            files: diff_files(&old.report.files, &new.report.files),
+            languages: diff_languages(old, new),
+            directories: diff_directories(old, new, max_depth),
+            total: TotalChange {
+                old: old.report.total,
+                new: new.report.total,
+                delta: Delta::between(&old.report.total, &new.report.total),
+            },
+        }
+    }
+}
+
+fn diff_files(old: &[FileEntry], new: &[FileEntry]) -> Vec<FileChange> {
+    let old_by_path = old
+        .iter()
+        .map(|file| (file.path.as_str(), file))
+        .collect::<HashMap<_, _>>();
+    let new_paths = new
+        .iter()
+        .map(|file| file.path.as_str())
+        .collect::<HashSet<_>>();
+
+    let mut changes = vec![];
+    for file in new {
+        let change = match old_by_path.get(file.path.as_str()) {
+            None => (Status::Added, Totals::default()),
+            Some(old) if file_totals(old) != file_totals(file) || old.language != file.language => {
+                (Status::Changed, file_totals(old))
+            }
+            Some(_) => continue,
+        };
+        changes.push(FileChange {
+            path: file.path.clone(),
+            status: change.0,
+            language: file.language.clone(),
+            delta: Delta::between(&change.1, &file_totals(file)),
+        });
+    }
+
+    for file in old {
+        if !new_paths.contains(file.path.as_str()) {
+            changes.push(FileChange {
+                path: file.path.clone(),
+                status: Status::Removed,
+                language: file.language.clone(),
+                delta: Delta::between(&file_totals(file), &Totals::default()),
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+fn diff_languages(old: &Snapshot, new: &Snapshot) -> Vec<LanguageChange> {
+    let mut both: HashMap<&str, (Totals, Totals)> = HashMap::new();
+    for lang in &old.report.languages {
+        both.entry(&lang.language).or_default().0 = lang.totals;
+    }
+
+    for lang in &new.report.languages {
+        both.entry(&lang.language).or_default().1 = lang.totals;
+    }
+
+    let mut changes = both
+        .into_iter()
+        .map(|(language, (old, new))| LanguageChange {
+            language: language.to_string(),
+            old,
+            new,
+            delta: Delta::between(&old, &new),
+        })
+        .filter(|change| !change.delta.is_zero())
+        .collect::<Vec<_>>();
+    changes.sort_by(|a, b| {
+        b.delta
+            .lines
+            .abs()
+            .cmp(&a.delta.lines.abs())
+            .then_with(|| a.language.cmp(&b.language))
+    });
+    changes
+}
+
+/// Compares the directory trees of two snapshots. Directories that only exist in the
+/// older one are listed after the rest.
+fn diff_directories(
+    old: &Snapshot,
+    new: &Snapshot,
+    max_depth: Option<usize>,
+) -> Vec<DirectoryChange> {
+    let old_tree = old.report.tree.as_deref().unwrap_or_default();
+    let new_tree = new.report.tree.as_deref().unwrap_or_default();
+    let old_by_path = old_tree
+        .iter()
+        .map(|dir| ((dir.root.as_str(), dir.path.as_str()), dir))
+        .collect::<HashMap<_, _>>();
+    let new_paths = new_tree
+        .iter()
+        .map(|dir| (dir.root.as_str(), dir.path.as_str()))
+        .collect::<HashSet<_>>();
+
+    let old_only = old_tree
+        .iter()
+        .filter(|dir| !new_paths.contains(&(dir.root.as_str(), dir.path.as_str())))
+        .map(|dir| (dir, dir.totals, Totals::default()));
+    let mut changes = new_tree
+        .iter()
+        .map(|dir| {
+            let old = old_by_path
+                .get(&(dir.root.as_str(), dir.path.as_str()))
+                .map_or_else(Totals::default, |old| old.totals);
+            (dir, old, dir.totals)
+        })
+        .chain(old_only)
+        .filter(|(dir, _, _)| max_depth.is_none_or(|max| dir.depth <= max))
+        .map(|(dir, old, new)| DirectoryChange {
+            root: dir.root.clone(),
+            path: dir.path.clone(),
+            depth: dir.depth,
+            old,
+            new,
+            delta: Delta::between(&old, &new),
+        })
+        .filter(|change| !change.delta.is_zero())
+        .collect::<Vec<_>>();
+    // The sort is stable, so directories with the same change stay in tree order.
+    changes.sort_by_key(|change| Reverse(change.delta.lines.abs()));
+    changes
+}
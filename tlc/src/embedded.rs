@@ -0,0 +1,479 @@
+use crate::count::{self, Classifier, CodeSink, LineCounts};
+use crate::language::{self, Embedding, Language};
+use serde_json::Value;
+
+/// The lines of a file that are written in another language than the file's own, such as
+/// a code block in Markdown.
+#[derive(Debug, Clone, Copy)]
+pub struct Embedded {
+    pub language: &'static Language,
+    pub counts: LineCounts,
+}
+
+/// The counts of a file that can have code in other languages embedded in it.
+#[derive(Debug, Default)]
+pub struct Split {
+    /// The counts of the whole file, embedded lines included.
+    pub counts: LineCounts,
+    /// The embedded lines, summed up by language, in the order each language first shows
+    /// up in the file.
+    pub embedded: Vec<Embedded>,
+}
+
+impl Split {
+    /// Adds lines in an embedded language.
+    fn add(&mut self, language: &'static Language, counts: LineCounts) {
+        if counts.lines() == 0 {
+            return;
+        }
+
+        self.counts += counts;
+        match self
+            .embedded
+            .iter_mut()
+            .find(|e| e.language.name == language.name)
+        {
+            Some(embedded) => embedded.counts += counts,
+            None => self.embedded.push(Embedded { language, counts }),
+        }
+    }
+}
+
+/// Counts the lines in a file, attributing any code embedded in it (as described by
+/// `Language::embedding`) to the embedded code's language. Lines that open or close a block
+/// of embedded code, such as a Markdown fence or a `<script>` tag, belong to the file's own
+/// language, as do blocks in a language we don't know.
+///
+/// # Parameters
+/// - `text`: The file's text.
+/// - `lang`: The file's language.
+/// - `sink`: Where to pass the code on each line, in the file's own language and the
+///   embedded ones alike. Notebooks aren't passed on at all, since their line numbers are
+///   those of the JSON rather than of their cells.
+///
+/// # Returns
+/// The counts of the whole file, and of the lines in each embedded language.
+pub fn count_into<S: CodeSink>(text: &[u8], lang: &'static Language, sink: &mut S) -> Split {
+    match lang.embedding {
+        Some(Embedding::Cells) => count_cells(text, lang).unwrap_or_else(|| Split {
+            // A notebook that isn't valid JSON is counted as it is.
+            counts: count::count_text(text, lang),
+            embedded: vec![],
+        }),
+        Some(embedding) => {
+            let mut split = Split::default();
+            count_blocks(text, lang, embedding, sink, &mut split, |_| {});
+            split
+        }
+        None => Split {
+            counts: count::count_text_into(text, lang, sink),
+            embedded: vec![],
+        },
+    }
+}
+
+/// Classifies each line of a file the same way as `count_into` counts them, for when lines
+/// have to be told apart one by one.
+///
+/// # Parameters
+/// - `text`: The file's text.
+/// - `lang`: The file's language.
+///
+/// # Returns
+/// The counts of each line (so one line of code, comments, or blank), or `None` for a
+/// notebook, whose lines are those of its JSON rather than of its cells.
+pub fn classify_lines(text: &[u8], lang: &'static Language) -> Option<Vec<LineCounts>> {
+    let mut lines = vec![];
+    match lang.embedding {
+        Some(Embedding::Cells) => return None,
+        Some(embedding) => {
+            let on_line = |counts| lines.push(counts);
+            count_blocks(
+                text,
+                lang,
+                embedding,
+                &mut (),
+                &mut Split::default(),
+                on_line,
+            );
+        }
+        None => {
+            let mut classifier = Classifier::new(lang);
+            for line in count::lines(text) {
+                let mut counts = LineCounts::default();
+                classifier.push_line(line, &mut counts);
+                lines.push(counts);
+            }
+        }
+    }
+
+    Some(lines)
+}
+
+/// A block of embedded code that has been opened, but not closed yet.
+struct Block {
+    end: End,
+    /// The language the block is in, its classifier, and its counts so far, or `None` if
+    /// its language isn't one we know, so its lines are left to the file's own language.
+    code: Option<(&'static Language, Classifier<'static>, LineCounts)>,
+}
+
+/// What closes a block of embedded code.
+enum End {
+    /// A fence of at least `len` of the same `byte` as the opening one.
+    Fence { byte: u8, len: usize },
+    /// A closing tag, such as `</script`.
+    Tag(&'static [u8]),
+}
+
+impl Block {
+    /// Checks whether a line opens a block of embedded code.
+    fn open(embedding: Embedding, line: &[u8]) -> Option<Block> {
+        let (end, lang) = match embedding {
+            Embedding::Fences => opening_fence(line)?,
+            Embedding::Tags => opening_tag(line)?,
+            Embedding::Cells => return None,
+        };
+        Some(Block {
+            end,
+            code: lang.map(|lang| (lang, Classifier::new(lang), LineCounts::default())),
+        })
+    }
+
+    /// Whether a line closes this block.
+    fn closes(&self, line: &[u8]) -> bool {
+        match self.end {
+            End::Fence { byte, len } => {
+                let indent = line.iter().take_while(|&&b| b == b' ').count();
+                let line = &line[indent..];
+                let run = line.iter().take_while(|&&b| b == byte).count();
+                indent <= 3 && run >= len && line[run..].iter().all(u8::is_ascii_whitespace)
+            }
+            End::Tag(tag) => find_ignoring_case(line, tag).is_some(),
+        }
+    }
+}
+
+/// Counts a file whose embedded code is in blocks that start and end on lines of their own,
+/// passing the counts of each line to `on_line` as well.
+fn count_blocks<S: CodeSink>(
+    text: &[u8],
+    lang: &'static Language,
+    embedding: Embedding,
+    sink: &mut S,
+    split: &mut Split,
+    mut on_line: impl FnMut(LineCounts),
+) {
+    let mut host = Classifier::new(lang);
+    let mut block: Option<Block> = None;
+    for line in count::lines(text) {
+        let mut line_counts = LineCounts::default();
+        let closed = match &mut block {
+            Some(open) if !open.closes(line) => {
+                match &mut open.code {
+                    Some((_, classifier, counts)) => {
+                        classifier.push_line_into(line, &mut line_counts, sink);
+                        *counts += line_counts;
+                    }
+                    None => {
+                        host.push_line_into(line, &mut line_counts, sink);
+                        split.counts += line_counts;
+                    }
+                }
+                on_line(line_counts);
+                continue;
+            }
+            Some(_) => block.take(),
+            None => None,
+        };
+
+        host.push_line_into(line, &mut line_counts, sink);
+        split.counts += line_counts;
+        on_line(line_counts);
+        match closed {
+            Some(Block {
+                code: Some((lang, _, counts)),
+                ..
+            }) => split.add(lang, counts),
+            Some(_) => {}
+            None => block = Block::open(embedding, line),
+        }
+    }
+
+    // A block that's never closed runs to the end of the file.
+    if let Some(Block {
+        code: Some((lang, _, counts)),
+        ..
+    }) = block
+    {
+        split.add(lang, counts);
+    }
+}
+
+/// Checks whether a line opens a fenced code block: three or more backticks or tildes,
+/// indented by no more than three spaces, followed by the name of the block's language.
+fn opening_fence(line: &[u8]) -> Option<(End, Option<&'static Language>)> {
+    let indent = line.iter().take_while(|&&b| b == b' ').count();
+    let line = &line[indent..];
+    let byte = *line.first().filter(|&&b| b == b'`' || b == b'~')?;
+    let len = line.iter().take_while(|&&b| b == byte).count();
+    let info = &line[len..];
+    if indent > 3 || len < 3 || (byte == b'`' && info.contains(&b'`')) {
+        return None;
+    }
+
+    // The language is the first word of the info string, as in "rust,ignore", or "{.python}"
+    // and "{r}" for Pandoc and R Markdown.
+    let info = String::from_utf8_lossy(info);
+    let name = info
+        .trim()
+        .trim_start_matches(['{', '.'])
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '}')
+        .next()
+        .unwrap_or_default();
+    let lang = Some(name)
+        .filter(|name| !name.is_empty())
+        .and_then(language::from_name);
+    Some((End::Fence { byte, len }, lang))
+}
+
+/// The types of `<script>` that hold JavaScript.
+const JAVASCRIPT_TYPES: &[&str] = &[
+    "module",
+    "text/javascript",
+    "application/javascript",
+    "text/babel",
+    "text/jsx",
+];
+
+/// Checks whether a line opens a `<script>` or `<style>` block that goes on past the line.
+/// The language comes from the tag's `lang` attribute (as in Vue and Svelte) or its `type`,
+/// and is JavaScript or CSS without either.
+fn opening_tag(line: &[u8]) -> Option<(End, Option<&'static Language>)> {
+    if !line.contains(&b'<') {
+        return None;
+    }
+
+    let tags: [(&[u8], &'static [u8], &str); 2] = [
+        (b"<script", b"</script", "javascript"),
+        (b"<style", b"</style", "css"),
+    ];
+    let (start, &(open, close, default)) = tags
+        .iter()
+        .filter_map(|tag| Some((find_ignoring_case(line, tag.0)?, tag)))
+        .min_by_key(|&(start, _)| start)?;
+
+    // The tag has to end on this line, and not be closed on it too.
+    let rest = &line[start + open.len()..];
+    if !rest
+        .first()
+        .is_some_and(|&b| b == b'>' || b.is_ascii_whitespace())
+    {
+        return None;
+    }
+
+    let end = rest.iter().position(|&b| b == b'>')?;
+    let attributes = String::from_utf8_lossy(&rest[..end]).to_ascii_lowercase();
+    if attributes.ends_with('/') || find_ignoring_case(&rest[end..], close).is_some() {
+        return None;
+    }
+
+    let lang = match (
+        attribute(&attributes, "lang"),
+        attribute(&attributes, "type"),
+    ) {
+        (Some(lang), _) => language::from_name(lang),
+        (None, Some(kind)) if default == "javascript" && !JAVASCRIPT_TYPES.contains(&kind) => {
+            match kind {
+                "text/typescript" | "application/typescript" => language::from_name("typescript"),
+                kind if kind.ends_with("json") => language::from_name("json"),
+                // Such as templates, which are markup.
+                _ => None,
+            }
+        }
+        _ => language::from_name(default),
+    };
+    Some((End::Tag(close), lang))
+}
+
+/// Gets the value of an attribute in a tag, quoted or not.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    attributes.match_indices(name).find_map(|(i, _)| {
+        let before = attributes[..i].chars().next_back();
+        let value = attributes[i + name.len()..]
+            .trim_start()
+            .strip_prefix('=')?;
+        if !before.is_some_and(char::is_whitespace) {
+            return None;
+        }
+
+        let value = value.trim_start();
+        match value.chars().next()? {
+            quote @ ('"' | '\'') => value[1..].split(quote).next(),
+            _ => value.split(char::is_whitespace).next(),
+        }
+    })
+}
+
+/// Finds where `needle` (which is lowercase) first shows up in `haystack`, in any case.
+fn find_ignoring_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+/// Counts the cells of a Jupyter notebook. Code cells are in the notebook's language (or
+/// Python, if it doesn't say), and Markdown cells are counted like Markdown files, with
+/// their prose in the notebook's own language. Raw cells are counted as prose too.
+///
+/// # Returns
+/// The counts, or `None` if the notebook isn't valid JSON.
+fn count_cells(text: &[u8], lang: &'static Language) -> Option<Split> {
+    let notebook: Value = serde_json::from_slice(text).ok()?;
+    let metadata = &notebook["metadata"];
+    let code_lang = [
+        &metadata["kernelspec"]["language"],
+        &metadata["language_info"]["name"],
+    ]
+    .iter()
+    .find_map(|name| name.as_str())
+    .map_or(language::from_name("python"), language::from_name)
+    .unwrap_or(&language::PLAIN_TEXT);
+
+    // Notebooks before version 4 keep their cells in worksheets, and code in `input`.
+    let cells = match notebook.get("worksheets").and_then(Value::as_array) {
+        Some(sheets) => sheets.iter().map(|sheet| &sheet["cells"]).collect(),
+        None => vec![&notebook["cells"]],
+    };
+    let mut split = Split::default();
+    for cell in cells.into_iter().filter_map(Value::as_array).flatten() {
+        let source = match cell.get("source").or_else(|| cell.get("input")) {
+            Some(Value::String(source)) => source.clone(),
+            Some(Value::Array(parts)) => parts.iter().filter_map(Value::as_str).collect(),
+            _ => continue,
+        };
+
+        match cell["cell_type"].as_str() {
+            Some("code") => split.add(code_lang, count::count_text(source.as_bytes(), code_lang)),
+            Some("markdown") => count_blocks(
+                source.as_bytes(),
+                lang,
+                Embedding::Fences,
+                &mut (),
+                &mut split,
+                |_| {},
+            ),
+            _ => split.counts += count::count_text(source.as_bytes(), lang),
+        }
+    }
+
+    Some(split)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(text: &str, lang: &str) -> (LineCounts, Vec<(&'static str, LineCounts)>) {
+        let split = count_into(text.as_bytes(), language::from_name(lang).unwrap(), &mut ());
+        let embedded = split
+            .embedded
+            .iter()
+            .map(|e| (e.language.name, e.counts))
+            .collect();
+        (split.counts, embedded)
+    }
+
+    fn counts(code: usize, comment: usize, blank: usize) -> LineCounts {
+        LineCounts {
+            code,
+            comment,
+            blank,
+        }
+    }
+
+    #[test]
+    fn markdown_fences() {
+        let text = "\
+# Title
+
+```rust,ignore
+// a comment
+fn main() {}
+```
+
+~~~~ {.python}
+```
+x = 1
+~~~~
+
+```
+unknown
+```
+```py
+# never closed
+
+";
+        assert_eq!(
+            count(text, "markdown"),
+            (
+                counts(12, 2, 4),
+                vec![("Rust", counts(1, 1, 0)), ("Python", counts(2, 1, 1))]
+            )
+        );
+    }
+
+    #[test]
+    fn script_and_style_tags() {
+        let text = "\
+<template>
+  <div>{{ x }}</div>
+</template>
+<script setup lang=\"ts\">
+// TypeScript
+const x: number = 1;
+</script>
+<style scoped>
+a { color: red; }
+</style>
+<script type=\"text/x-template\">
+  <p>markup</p>
+</script>
+<script src=\"a.js\"></script>
+<STYLE lang='scss'>
+$c: red; </STYLE>
+";
+        assert_eq!(
+            count(text, "vue"),
+            (
+                counts(15, 1, 0),
+                vec![("TypeScript", counts(1, 1, 0)), ("CSS", counts(1, 0, 0))]
+            )
+        );
+    }
+
+    #[test]
+    fn notebook_cells() {
+        let notebook = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Title\n", "\n", "```sh\n", "ls\n", "```"]},
+  {"cell_type": "code", "metadata": {}, "outputs": [], "source": ["# comment\n", "x = 1\n", "\n", "print(x)"]},
+  {"cell_type": "code", "metadata": {}, "outputs": [], "source": "y = 2"},
+  {"cell_type": "raw", "metadata": {}, "source": []}
+ ],
+ "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+        assert_eq!(
+            count(notebook, "ipynb"),
+            (
+                counts(7, 1, 2),
+                vec![("Shell", counts(1, 0, 0)), ("Python", counts(3, 1, 1))]
+            )
+        );
+
+        let (broken, embedded) = count("{\"cells\": [", "jupyter");
+        assert_eq!((broken.lines(), embedded), (1, vec![]));
+    }
+}
@@ -0,0 +1,132 @@
+use std::borrow::Cow;
+
+/// How many bytes at the start of a file are looked at to decide whether it is binary.
+pub const SNIFF_LEN: usize = 8192;
+
+/// What we could tell about a file's encoding from its first few bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8 with a byte order mark, which should be skipped.
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// Anything else that looks like text: UTF-8, Latin-1, and so on. These are all
+    /// counted byte by byte.
+    Text,
+    /// Not text at all.
+    Binary,
+}
+
+impl Encoding {
+    /// The length of the byte order mark at the start of files in this encoding.
+    pub fn bom_len(self) -> usize {
+        match self {
+            Encoding::Utf8Bom => 3,
+            Encoding::Utf16Le | Encoding::Utf16Be => 2,
+            Encoding::Text | Encoding::Binary => 0,
+        }
+    }
+}
+
+/// Guesses a file's encoding from its start. A byte order mark decides the encoding;
+/// otherwise, a file with a NUL byte in its first `SNIFF_LEN` bytes is taken to be binary,
+/// since text files practically never contain one.
+///
+/// # Parameters
+/// - `head`: The first bytes of the file.
+///
+/// # Returns
+/// The encoding.
+pub fn detect(head: &[u8]) -> Encoding {
+    if head.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        Encoding::Utf8Bom
+    } else if head.starts_with(&[0xFF, 0xFE]) {
+        Encoding::Utf16Le
+    } else if head.starts_with(&[0xFE, 0xFF]) {
+        Encoding::Utf16Be
+    } else if head[..head.len().min(SNIFF_LEN)].contains(&0) {
+        Encoding::Binary
+    } else {
+        Encoding::Text
+    }
+}
+
+/// Decodes UTF-16 into UTF-8. Unpaired surrogates become U+FFFD, and a trailing odd byte
+/// is dropped.
+///
+/// # Parameters
+/// - `bytes`: The UTF-16 data, without its byte order mark.
+/// - `little_endian`: Whether the data is little endian.
+///
+/// # Returns
+/// The decoded text.
+pub fn decode_utf16(bytes: &[u8], little_endian: bool) -> String {
+    let units = bytes.chunks_exact(2).map(|pair| {
+        if little_endian {
+            u16::from_le_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_be_bytes([pair[0], pair[1]])
+        }
+    });
+
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Decodes a whole file the same way as it's counted: without its byte order mark, and with
+/// UTF-16 turned into UTF-8.
+///
+/// # Returns
+/// The text, or `None` if the file is binary.
+pub fn decode(data: &[u8]) -> Option<Cow<'_, [u8]>> {
+    let enc = detect(data);
+    let text = &data[enc.bom_len()..];
+    match enc {
+        Encoding::Binary => None,
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let text = decode_utf16(text, enc == Encoding::Utf16Le);
+            Some(Cow::Owned(text.into_bytes()))
+        }
+        Encoding::Utf8Bom | Encoding::Text => Some(Cow::Borrowed(text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_order_marks() {
+        assert_eq!(detect(b"\xEF\xBB\xBFfn main"), Encoding::Utf8Bom);
+        assert_eq!(detect(b"\xFF\xFEa\0"), Encoding::Utf16Le);
+        assert_eq!(detect(b"\xFE\xFF\0a"), Encoding::Utf16Be);
+    }
+
+    #[test]
+    fn nul_bytes_mean_binary() {
+        assert_eq!(detect(b"fn main() {}\n"), Encoding::Text);
+        assert_eq!(detect(b"\x7fELF\x02\x01\x01\0"), Encoding::Binary);
+        assert_eq!(detect(b""), Encoding::Text);
+
+        // A NUL past the sniffed part isn't seen.
+        let mut late = vec![b'a'; SNIFF_LEN];
+        late.push(0);
+        assert_eq!(detect(&late), Encoding::Text);
+    }
+
+    #[test]
+    fn utf16() {
+        assert_eq!(decode_utf16(b"h\0i\0\n\0", true), "hi\n");
+        assert_eq!(decode_utf16(b"\0h\0i\0", false), "hi");
+        // An unpaired surrogate.
+        assert_eq!(decode_utf16(b"\x00\xD8a\0", true), "\u{FFFD}a");
+    }
+
+    #[test]
+    fn whole_files() {
+        assert_eq!(decode(b"\xEF\xBB\xBFa\n").as_deref(), Some(&b"a\n"[..]));
+        assert_eq!(decode(b"\xFF\xFEa\0\n\0").as_deref(), Some(&b"a\n"[..]));
+        assert_eq!(decode(b"a\0"), None);
+    }
+}
@@ -0,0 +1,185 @@
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use std::ffi::OsString;
+use std::path::Path;
+
+/// Decides which paths are counted based on their names: the `--ignore` directory names, and
+/// the `--include` and `--exclude` globs. (Ignore files are handled by the walker itself.)
+///
+/// Globs are matched against paths relative to the root that they were found under. A glob
+/// without a `/` in it matches the file name anywhere in the tree (so `*.min.js` is the same
+/// as `**/*.min.js`), while a glob with a `/` is matched against the whole relative path. As
+/// with `.gitignore`, `*` doesn't match across `/` but `**` does, and a glob that ends in `/`
+/// only matches directories.
+pub struct PathFilter {
+    dirs_to_avoid: Vec<OsString>,
+    skip_git_dir: bool,
+    include: GlobSet,
+    include_patterns: Vec<String>,
+    exclude: GlobSet,
+    exclude_patterns: Vec<String>,
+}
+
+impl PathFilter {
+    /// Compiles the given globs.
+    ///
+    /// # Parameters
+    /// - `dirs_to_avoid`: Names of directories that should never be entered.
+    /// - `skip_git_dir`: Whether to skip `.git` directories.
+    /// - `include`: The globs that files must match at least one of. If empty, every file
+    ///   is included.
+    /// - `exclude`: The globs that files and directories must not match.
+    ///
+    /// # Returns
+    /// The filter, or an error if any of the globs are invalid.
+    pub fn new(
+        dirs_to_avoid: Vec<OsString>,
+        skip_git_dir: bool,
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<Self, globset::Error> {
+        Ok(PathFilter {
+            dirs_to_avoid,
+            skip_git_dir,
+            include: build_set(include)?,
+            include_patterns: include.to_vec(),
+            exclude: build_set(exclude)?,
+            exclude_patterns: exclude.to_vec(),
+        })
+    }
+
+    /// Checks whether the given path is excluded.
+    ///
+    /// # Parameters
+    /// - `rel_path`: The path, relative to its root.
+    /// - `is_dir`: Whether the path is a directory. Directories are never checked against
+    ///   `--include`, since files under them might still match.
+    ///
+    /// # Returns
+    /// `None` if the path should be counted, or a description of the rule that excluded it.
+    pub fn excluded_by(&self, rel_path: &Path, is_dir: bool) -> Option<String> {
+        if is_dir {
+            if let Some(name) = rel_path.file_name() {
+                if self.skip_git_dir && name == ".git" {
+                    return Some("git directory".to_string());
+                }
+
+                if self.dirs_to_avoid.iter().any(|x| x == name) {
+                    return Some(format!("--ignore '{}'", name.to_string_lossy()));
+                }
+            }
+        }
+
+        let excluded = self
+            .exclude
+            .matches(rel_path)
+            .into_iter()
+            .find(|&idx| is_dir || !self.exclude_patterns[idx].ends_with('/'));
+        if let Some(idx) = excluded {
+            return Some(format!("--exclude '{}'", self.exclude_patterns[idx]));
+        }
+
+        if !is_dir && !self.include.is_empty() && !self.include.is_match(rel_path) {
+            return Some(format!(
+                "not matched by any --include ('{}')",
+                self.include_patterns.join("', '")
+            ));
+        }
+
+        None
+    }
+}
+
+fn build_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(compile(pattern)?);
+    }
+
+    builder.build()
+}
+
+fn compile(pattern: &str) -> Result<Glob, globset::Error> {
+    let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+    let pattern = if pattern.contains('/') {
+        pattern.trim_start_matches('/').to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+
+    GlobBuilder::new(&pattern).literal_separator(true).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter_with(include: &[&str], exclude: &[&str]) -> PathFilter {
+        let strings = |globs: &[&str]| globs.iter().map(|g| g.to_string()).collect::<Vec<_>>();
+        PathFilter::new(
+            vec![OsString::from("node_modules")],
+            true,
+            &strings(include),
+            &strings(exclude),
+        )
+        .unwrap()
+    }
+
+    fn excluded(filter: &PathFilter, path: &str, is_dir: bool) -> bool {
+        filter.excluded_by(Path::new(path), is_dir).is_some()
+    }
+
+    #[test]
+    fn ignored_directory_names() {
+        let filter = filter_with(&[], &[]);
+        assert!(excluded(&filter, "node_modules", true));
+        assert!(excluded(&filter, "web/node_modules", true));
+        assert!(excluded(&filter, "web/.git", true));
+        assert!(!excluded(&filter, "node_modules", false));
+        assert!(!excluded(&filter, "src", true));
+    }
+
+    #[test]
+    fn globs_without_a_slash_match_at_any_depth() {
+        let filter = filter_with(&[], &["*.min.js"]);
+        assert!(excluded(&filter, "app.min.js", false));
+        assert!(excluded(&filter, "web/static/app.min.js", false));
+        assert!(!excluded(&filter, "web/static/app.js", false));
+
+        // A trailing slash doesn't anchor the glob, but it only matches directories.
+        let filter = filter_with(&[], &["vendor/"]);
+        assert!(excluded(&filter, "lib/vendor", true));
+        assert!(!excluded(&filter, "lib/vendor", false));
+    }
+
+    #[test]
+    fn globs_with_a_slash_are_anchored() {
+        let filter = filter_with(&[], &["src/vendor", "/build/*.rs"]);
+        assert!(excluded(&filter, "src/vendor", true));
+        assert!(!excluded(&filter, "lib/src/vendor", true));
+        assert!(excluded(&filter, "build/gen.rs", false));
+        assert!(!excluded(&filter, "build/sub/gen.rs", false));
+    }
+
+    #[test]
+    fn include_only_applies_to_files() {
+        let filter = filter_with(&["src/**/*.rs"], &[]);
+        assert!(!excluded(&filter, "src/a/b.rs", false));
+        assert!(excluded(&filter, "tests/b.rs", false));
+        assert!(!excluded(&filter, "tests", true));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let filter = filter_with(&["*.rs"], &["generated"]);
+        assert!(excluded(&filter, "generated", true));
+        assert_eq!(
+            filter.excluded_by(Path::new("src/generated"), false),
+            Some("--exclude 'generated'".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_globs_are_errors() {
+        assert!(PathFilter::new(vec![], true, &["a[".to_string()], &[]).is_err());
+    }
+}
@@ -0,0 +1,1019 @@
+/// Describes how a language marks up comments and strings, which is all we need to know
+/// to decide whether a line is code, a comment, or blank.
+#[derive(Debug)]
+pub struct Language {
+    /// The display name of the language.
+    pub name: &'static str,
+    /// The file extensions (without the leading dot, lowercase) used by this language.
+    pub extensions: &'static [&'static str],
+    /// Markers that start a comment running to the end of the line.
+    pub line_comments: &'static [&'static str],
+    /// Things that start with a line comment marker but aren't comments, such as PHP's `#[`
+    /// attributes.
+    pub not_line_comments: &'static [&'static str],
+    /// Start and end delimiters of block comments.
+    pub block_comments: &'static [(&'static str, &'static str)],
+    /// Whether block comments can be nested inside each other (e.g. Rust, Haskell).
+    pub nested_comments: bool,
+    /// Start and end delimiters of string literals. Comment markers inside a string are
+    /// ignored, and a backslash escapes the next character.
+    pub quotes: &'static [(&'static str, &'static str)],
+    /// Whether `'` starts a character literal, such as `'x'` or `'\''`. It only does if the
+    /// literal is closed right after its one character (or escape), since `'` can also start
+    /// a lifetime or a type variable, as in Rust's `'a`.
+    pub char_literals: bool,
+    /// Well-known names of files (e.g. `Makefile`) that are written in this language, no
+    /// matter their extension.
+    pub filenames: &'static [&'static str],
+    /// Names of the programs that run this language, as they would appear in a `#!` line.
+    /// Version numbers at the end of the program's name are ignored, so `python` also
+    /// covers `python3` and `python3.11`.
+    pub interpreters: &'static [&'static str],
+    /// Other names that this language goes by (lowercase), such as in editor modelines or
+    /// `--check`. The lowercased name and the extensions are always accepted too.
+    pub aliases: &'static [&'static str],
+    /// How to measure the complexity of code in this language, if we know how.
+    pub complexity: Option<&'static Complexity>,
+    /// How code in other languages is embedded in files of this language, if it can be.
+    pub embedding: Option<Embedding>,
+}
+
+/// The ways that code in one language can be embedded in a file of another. The embedded
+/// lines are counted as their own language, and the rest of the file as the file's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Embedding {
+    /// Fenced code blocks, as in Markdown, whose language is named after the opening fence.
+    Fences,
+    /// `<script>` and `<style>` blocks, as in HTML, Vue, and Svelte.
+    Tags,
+    /// The cells of a Jupyter notebook, which is JSON. Code cells are in the notebook's
+    /// language, and Markdown cells are the notebook's prose.
+    Cells,
+}
+
+/// Describes how a language marks functions, blocks, and branches, which is enough to
+/// estimate how complex its code is without parsing it.
+#[derive(Debug)]
+pub struct Complexity {
+    /// Keywords that start a named function, such as `fn` or `def`. The function's name is
+    /// the word after the keyword (skipping over a receiver in parentheses, as in Go).
+    pub function_keywords: &'static [&'static str],
+    /// Whether a block right after `name(...)` is the body of a function called `name`, as
+    /// in C and Java. Blocks after control keywords, such as `if (...) {`, don't count.
+    pub c_style_functions: bool,
+    /// Keywords and operators that each add a path through the code, such as `if` or `&&`.
+    pub branches: &'static [&'static str],
+    /// Whether blocks are marked by indenting the lines after a `:`, as in Python, rather
+    /// than by braces.
+    pub indented_blocks: bool,
+}
+
+/// Used for files whose extension was explicitly requested but that we have no definition
+/// for. Every non-blank line is counted as code.
+pub static PLAIN_TEXT: Language = Language {
+    name: "Plain Text",
+    extensions: &["txt"],
+    line_comments: &[],
+    not_line_comments: &[],
+    block_comments: &[],
+    nested_comments: false,
+    quotes: &[],
+    char_literals: false,
+    filenames: &[],
+    interpreters: &[],
+    aliases: &["text"],
+    complexity: None,
+    embedding: None,
+};
+
+const C_COMMENTS: &[(&str, &str)] = &[("/*", "*/")];
+const C_QUOTES: &[(&str, &str)] = &[("\"", "\""), ("'", "'")];
+const SCRIPT_QUOTES: &[(&str, &str)] = &[("\"", "\""), ("'", "'"), ("`", "`")];
+const PY_QUOTES: &[(&str, &str)] = &[
+    ("\"\"\"", "\"\"\""),
+    ("'''", "'''"),
+    ("\"", "\""),
+    ("'", "'"),
+];
+const XML_COMMENTS: &[(&str, &str)] = &[("<!--", "-->")];
+
+const C_COMPLEXITY: &Complexity = &Complexity {
+    function_keywords: &[],
+    c_style_functions: true,
+    branches: &["if", "for", "while", "case", "catch", "&&", "||", "?"],
+    indented_blocks: false,
+};
+const JAVA_COMPLEXITY: &Complexity = &Complexity {
+    function_keywords: &[],
+    c_style_functions: true,
+    branches: &["if", "for", "foreach", "while", "case", "catch", "&&", "||"],
+    indented_blocks: false,
+};
+const JS_COMPLEXITY: &Complexity = &Complexity {
+    function_keywords: &["function"],
+    c_style_functions: true,
+    branches: &["if", "for", "while", "case", "catch", "&&", "||", "??"],
+    indented_blocks: false,
+};
+
+/// All languages that we know about, other than plain text.
+pub static LANGUAGES: &[Language] = &[
+    Language {
+        name: "Batch",
+        extensions: &["bat", "cmd"],
+        line_comments: &["REM", "rem", "::"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &["dosbatch"],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "C",
+        extensions: &["c"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(C_COMPLEXITY),
+        embedding: None,
+    },
+    Language {
+        name: "C Header",
+        extensions: &["h"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(C_COMPLEXITY),
+        embedding: None,
+    },
+    Language {
+        name: "C#",
+        extensions: &["cs"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &["csharp"],
+        complexity: Some(JAVA_COMPLEXITY),
+        embedding: None,
+    },
+    Language {
+        name: "C++",
+        extensions: &["cc", "cpp", "cxx", "c++"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &["cpp", "cplusplus"],
+        complexity: Some(C_COMPLEXITY),
+        embedding: None,
+    },
+    Language {
+        name: "C++ Header",
+        extensions: &["hh", "hpp", "hxx", "h++"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(C_COMPLEXITY),
+        embedding: None,
+    },
+    Language {
+        name: "CSS",
+        extensions: &["css"],
+        line_comments: &[],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "CMake",
+        extensions: &["cmake"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[("\"", "\"")],
+        char_literals: false,
+        filenames: &["CMakeLists.txt"],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Dart",
+        extensions: &["dart"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: true,
+        quotes: PY_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(JAVA_COMPLEXITY),
+        embedding: None,
+    },
+    Language {
+        name: "Dockerfile",
+        extensions: &["dockerfile"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[],
+        char_literals: false,
+        filenames: &["Dockerfile", "Containerfile"],
+        interpreters: &[],
+        aliases: &["docker"],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Elixir",
+        extensions: &["ex", "exs"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: PY_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &["elixir"],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Go",
+        extensions: &["go"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: SCRIPT_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(&Complexity {
+            function_keywords: &["func"],
+            c_style_functions: false,
+            branches: &["if", "for", "case", "&&", "||"],
+            indented_blocks: false,
+        }),
+        embedding: None,
+    },
+    Language {
+        name: "Groovy",
+        extensions: &["groovy", "gradle"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: PY_QUOTES,
+        char_literals: false,
+        filenames: &["Jenkinsfile"],
+        interpreters: &["groovy"],
+        aliases: &["jenkinsfile"],
+        complexity: Some(JAVA_COMPLEXITY),
+        embedding: None,
+    },
+    Language {
+        name: "Haskell",
+        extensions: &["hs"],
+        line_comments: &["--"],
+        not_line_comments: &[],
+        block_comments: &[("{-", "-}")],
+        nested_comments: true,
+        quotes: &[("\"", "\"")],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &["runhaskell", "runghc"],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "HTML",
+        extensions: &["html", "htm", "xhtml"],
+        line_comments: &[],
+        not_line_comments: &[],
+        block_comments: XML_COMMENTS,
+        nested_comments: false,
+        quotes: &[],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: Some(Embedding::Tags),
+    },
+    Language {
+        name: "Java",
+        extensions: &["java"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(JAVA_COMPLEXITY),
+        embedding: None,
+    },
+    Language {
+        name: "JavaScript",
+        extensions: &["js", "mjs", "cjs", "jsx"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: SCRIPT_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &["node", "nodejs"],
+        aliases: &[],
+        complexity: Some(JS_COMPLEXITY),
+        embedding: None,
+    },
+    Language {
+        name: "JSON",
+        extensions: &["json"],
+        line_comments: &[],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[("\"", "\"")],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Jupyter Notebook",
+        extensions: &["ipynb"],
+        line_comments: &[],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &["jupyter"],
+        complexity: None,
+        embedding: Some(Embedding::Cells),
+    },
+    Language {
+        name: "Kotlin",
+        extensions: &["kt", "kts"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: true,
+        quotes: &[("\"\"\"", "\"\"\""), ("\"", "\""), ("'", "'")],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(&Complexity {
+            function_keywords: &["fun"],
+            c_style_functions: false,
+            branches: &["if", "for", "while", "catch", "&&", "||"],
+            indented_blocks: false,
+        }),
+        embedding: None,
+    },
+    Language {
+        name: "Lua",
+        extensions: &["lua"],
+        line_comments: &["--"],
+        not_line_comments: &[],
+        block_comments: &[("--[[", "]]")],
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &["lua", "luajit"],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Makefile",
+        extensions: &["mk", "mak"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[],
+        char_literals: false,
+        filenames: &["Makefile", "makefile", "GNUmakefile"],
+        interpreters: &["make"],
+        aliases: &["make"],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Markdown",
+        extensions: &["md", "markdown"],
+        line_comments: &[],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: Some(Embedding::Fences),
+    },
+    Language {
+        name: "OCaml",
+        extensions: &["ml", "mli"],
+        line_comments: &[],
+        not_line_comments: &[],
+        block_comments: &[("(*", "*)")],
+        nested_comments: true,
+        quotes: &[("\"", "\"")],
+        char_literals: true,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Perl",
+        extensions: &["pl", "pm"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[("=pod", "=cut")],
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &["perl"],
+        aliases: &[],
+        complexity: Some(&Complexity {
+            function_keywords: &["sub"],
+            c_style_functions: false,
+            branches: &[
+                "if", "elsif", "unless", "while", "until", "for", "&&", "||", "and", "or",
+            ],
+            indented_blocks: false,
+        }),
+        embedding: None,
+    },
+    Language {
+        name: "PHP",
+        extensions: &["php"],
+        line_comments: &["//", "#"],
+        not_line_comments: &["#["],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &["php"],
+        aliases: &[],
+        complexity: Some(&Complexity {
+            function_keywords: &["function"],
+            c_style_functions: false,
+            branches: &[
+                "if", "elseif", "for", "foreach", "while", "case", "catch", "&&", "||", "?",
+            ],
+            indented_blocks: false,
+        }),
+        embedding: None,
+    },
+    Language {
+        name: "PowerShell",
+        extensions: &["ps1", "psm1", "psd1"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[("<#", "#>")],
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &["pwsh", "powershell"],
+        aliases: &["posh"],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Python",
+        extensions: &["py", "pyw", "pyi"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: PY_QUOTES,
+        char_literals: false,
+        filenames: &["SConstruct", "SConscript"],
+        interpreters: &["python", "pypy"],
+        aliases: &[],
+        complexity: Some(&Complexity {
+            function_keywords: &["def"],
+            c_style_functions: false,
+            branches: &["if", "elif", "for", "while", "except", "and", "or"],
+            indented_blocks: true,
+        }),
+        embedding: None,
+    },
+    Language {
+        name: "R",
+        extensions: &["r"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &["Rscript"],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Ruby",
+        extensions: &["rb"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[("=begin", "=end")],
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &["Rakefile", "Gemfile", "Vagrantfile", "Podfile"],
+        interpreters: &["ruby"],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: true,
+        quotes: &[("\"", "\"")],
+        char_literals: true,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(&Complexity {
+            function_keywords: &["fn"],
+            c_style_functions: false,
+            branches: &["if", "for", "while", "=>", "&&", "||"],
+            indented_blocks: false,
+        }),
+        embedding: None,
+    },
+    Language {
+        name: "Scala",
+        extensions: &["scala", "sc"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: true,
+        quotes: &[("\"\"\"", "\"\"\""), ("\"", "\"")],
+        char_literals: true,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(&Complexity {
+            function_keywords: &["def"],
+            c_style_functions: false,
+            branches: &["if", "for", "while", "case", "catch", "&&", "||"],
+            indented_blocks: false,
+        }),
+        embedding: None,
+    },
+    Language {
+        name: "SCSS",
+        extensions: &["scss", "sass", "less"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Shell",
+        extensions: &["sh", "bash", "zsh", "fish"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[
+            ".bashrc",
+            ".bash_profile",
+            ".bash_logout",
+            ".bash_aliases",
+            ".profile",
+            ".zshrc",
+            ".zshenv",
+            ".zprofile",
+            ".zlogin",
+        ],
+        interpreters: &["sh", "bash", "zsh", "dash", "ksh", "fish"],
+        aliases: &["shell-script", "shellscript"],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "SQL",
+        extensions: &["sql"],
+        line_comments: &["--"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: &[("'", "'")],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Svelte",
+        extensions: &["svelte"],
+        line_comments: &[],
+        not_line_comments: &[],
+        block_comments: XML_COMMENTS,
+        nested_comments: false,
+        quotes: &[],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: Some(Embedding::Tags),
+    },
+    Language {
+        name: "Swift",
+        extensions: &["swift"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: true,
+        quotes: &[("\"\"\"", "\"\"\""), ("\"", "\"")],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(&Complexity {
+            function_keywords: &["func"],
+            c_style_functions: false,
+            branches: &["if", "guard", "for", "while", "case", "catch", "&&", "||"],
+            indented_blocks: false,
+        }),
+        embedding: None,
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: PY_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "TypeScript",
+        extensions: &["ts", "mts", "cts", "tsx"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: C_COMMENTS,
+        nested_comments: false,
+        quotes: SCRIPT_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &["ts-node", "deno"],
+        aliases: &[],
+        complexity: Some(JS_COMPLEXITY),
+        embedding: None,
+    },
+    Language {
+        name: "Vue",
+        extensions: &["vue"],
+        line_comments: &[],
+        not_line_comments: &[],
+        block_comments: XML_COMMENTS,
+        nested_comments: false,
+        quotes: &[],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: Some(Embedding::Tags),
+    },
+    Language {
+        name: "XML",
+        extensions: &["xml", "xsd", "xsl", "svg", "csproj", "props"],
+        line_comments: &[],
+        not_line_comments: &[],
+        block_comments: XML_COMMENTS,
+        nested_comments: false,
+        quotes: &[],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "YAML",
+        extensions: &["yml", "yaml"],
+        line_comments: &["#"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[],
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: None,
+        embedding: None,
+    },
+    Language {
+        name: "Zig",
+        extensions: &["zig"],
+        line_comments: &["//"],
+        not_line_comments: &[],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: C_QUOTES,
+        char_literals: false,
+        filenames: &[],
+        interpreters: &[],
+        aliases: &[],
+        complexity: Some(&Complexity {
+            function_keywords: &["fn"],
+            c_style_functions: false,
+            branches: &["if", "for", "while", "=>", "catch", "and", "or"],
+            indented_blocks: false,
+        }),
+        embedding: None,
+    },
+];
+
+/// Every language, plain text included.
+fn all() -> impl Iterator<Item = &'static Language> {
+    LANGUAGES.iter().chain(std::iter::once(&PLAIN_TEXT))
+}
+
+/// Finds the language associated with the given file extension, if any. The comparison
+/// ignores case, so `Foo.RS` is still Rust.
+///
+/// # Parameters
+/// - `ext`: The file extension, without the leading dot.
+///
+/// # Returns
+/// The language, or `None` if we don't know about this extension.
+pub fn from_extension(ext: &str) -> Option<&'static Language> {
+    let ext = ext.to_ascii_lowercase();
+    all().find(|lang| lang.extensions.contains(&ext.as_str()))
+}
+
+/// Finds the language of a file with a well-known name, such as `Makefile`.
+///
+/// # Parameters
+/// - `file_name`: The name of the file.
+///
+/// # Returns
+/// The language, or `None` if this isn't a well-known file name.
+pub fn from_file_name(file_name: &str) -> Option<&'static Language> {
+    all().find(|lang| lang.filenames.contains(&file_name))
+}
+
+/// Finds a language by any of its names: its display name, one of its aliases, or one of
+/// its extensions. The comparison ignores case.
+///
+/// # Parameters
+/// - `name`: The name, e.g. `python` or `C++`.
+///
+/// # Returns
+/// The language, or `None` if no language goes by that name.
+pub fn from_name(name: &str) -> Option<&'static Language> {
+    let name = name.to_ascii_lowercase();
+    all()
+        .find(|lang| {
+            lang.name.to_ascii_lowercase() == name || lang.aliases.contains(&name.as_str())
+        })
+        .or_else(|| from_extension(&name))
+}
+
+/// Figures out a file's language from an editor modeline in its first five lines, such as
+/// `# vim: set ft=python:` or `-*- mode: python -*-`.
+///
+/// # Parameters
+/// - `head`: The start of the file.
+///
+/// # Returns
+/// The language, or `None` if there's no modeline naming a language we know.
+pub fn from_modeline(head: &str) -> Option<&'static Language> {
+    head.lines()
+        .take(5)
+        .find_map(|line| modeline_language(line).and_then(from_name))
+}
+
+/// Figures out a file's language from its `#!` line, such as `#!/usr/bin/env python3`.
+///
+/// # Parameters
+/// - `head`: The start of the file.
+///
+/// # Returns
+/// The language, or `None` if there's no `#!` line naming a program we know.
+pub fn from_shebang(head: &str) -> Option<&'static Language> {
+    let interpreter = shebang_interpreter(head.lines().next()?)?;
+    let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    all().find(|lang| lang.interpreters.contains(&interpreter))
+}
+
+/// Gets the name of the program from a `#!` line, skipping over `env` and its options.
+fn shebang_interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let program = words.next()?.rsplit('/').next()?;
+    if program != "env" {
+        return Some(program);
+    }
+
+    // e.g. `#!/usr/bin/env -S node --harmony`
+    words.find(|word| !word.starts_with('-') && !word.contains('='))
+}
+
+/// Gets the language named by a Vim or Emacs modeline, if the line has one.
+fn modeline_language(line: &str) -> Option<&str> {
+    // Emacs: `-*- mode: python; coding: utf-8 -*-` or just `-*- python -*-`.
+    if let Some(start) = line.find("-*-") {
+        let rest = &line[start + 3..];
+        let vars = &rest[..rest.find("-*-")?];
+        if !vars.contains(':') {
+            return Some(vars.trim());
+        }
+
+        return vars.split(';').find_map(|var| {
+            let (key, value) = var.split_once(':')?;
+            if key.trim().eq_ignore_ascii_case("mode") {
+                Some(value.trim())
+            } else {
+                None
+            }
+        });
+    }
+
+    // Vim: `vim: set ft=python:`, `vi: filetype=python`, `ex: syntax=python`.
+    let start = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        let idx = line.find(marker)?;
+        let at_word_start = line[..idx].ends_with(char::is_whitespace) || idx == 0;
+        if at_word_start {
+            Some(idx + marker.len())
+        } else {
+            None
+        }
+    })?;
+
+    line[start..]
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .find_map(|option| {
+            let (key, value) = option.split_once('=')?;
+            if matches!(key, "ft" | "filetype" | "syntax" | "syn") && !value.is_empty() {
+                Some(value)
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(lang: Option<&Language>) -> Option<&str> {
+        lang.map(|lang| lang.name)
+    }
+
+    #[test]
+    fn extensions_ignore_case() {
+        assert_eq!(name(from_extension("rs")), Some("Rust"));
+        assert_eq!(name(from_extension("RS")), Some("Rust"));
+        assert_eq!(name(from_extension("nope")), None);
+    }
+
+    #[test]
+    fn well_known_file_names() {
+        assert_eq!(name(from_file_name("SConstruct")), Some("Python"));
+        assert_eq!(name(from_file_name("sconstruct")), None);
+    }
+
+    #[test]
+    fn names_aliases_and_extensions() {
+        assert_eq!(name(from_name("Python")), Some("Python"));
+        assert_eq!(name(from_name("text")), Some("Plain Text"));
+        assert_eq!(name(from_name("py")), Some("Python"));
+        assert_eq!(name(from_name("nope")), None);
+    }
+
+    #[test]
+    fn shebangs() {
+        assert_eq!(
+            name(from_shebang("#!/usr/bin/python3.11\n")),
+            Some("Python")
+        );
+        assert_eq!(
+            name(from_shebang("#!/usr/bin/env python\n")),
+            Some("Python")
+        );
+        assert_eq!(
+            name(from_shebang("#!/usr/bin/env -S FOO=1 python3 -u\n")),
+            Some("Python")
+        );
+        assert_eq!(name(from_shebang("# not a shebang\n")), None);
+        assert_eq!(name(from_shebang("#!/usr/bin/nope\n")), None);
+    }
+
+    #[test]
+    fn modelines() {
+        assert_eq!(
+            name(from_modeline("# vim: set ft=python:\n")),
+            Some("Python")
+        );
+        assert_eq!(name(from_modeline("x\n/* vi: filetype=c */\n")), Some("C"));
+        assert_eq!(
+            name(from_modeline("# -*- mode: python; coding: utf-8 -*-\n")),
+            Some("Python")
+        );
+        assert_eq!(name(from_modeline("# -*- python -*-\n")), Some("Python"));
+        // Only a whole word starts a Vim modeline.
+        assert_eq!(name(from_modeline("# nvim: ft=python\n")), None);
+        // Only the first five lines are looked at.
+        assert_eq!(name(from_modeline("\n\n\n\n\n# vim: ft=python\n")), None);
+    }
+}
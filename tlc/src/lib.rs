@@ -0,0 +1,343 @@
+//! Counts the lines of code, comments, and blank lines in source trees.
+//!
+//! The simplest way in is [`count()`], which walks some paths and returns a [`Report`]:
+//!
+//! ```no_run
+//! let report = tlc::count(&["src".into()], &tlc::Options::default()).unwrap();
+//! println!("{} lines of code", report.total.code);
+//! ```
+//!
+//! The modules underneath are what the `tlc` binary is built out of, for when more control
+//! is needed: walking roots one at a time ([`walk`]), classifying lines
+//! ([`count`](mod@count) and [`language`]), splitting out embedded code ([`embedded`]),
+//! measuring complexity ([`complexity`]), finding marker comments ([`markers`]),
+//! attributing lines to their authors ([`blame`]), owners ([`owners`]), and projects
+//! ([`projects`]), printing reports ([`output`]), snapshots ([`snapshot`] and [`diff`]), the
+//! on-disk cache ([`cache`]), looking inside archives ([`archive`]), and watching for changes
+//! ([`watch`]).
+
+pub mod archive;
+pub mod blame;
+pub mod cache;
+pub mod complexity;
+pub mod count;
+pub mod dedupe;
+pub mod diff;
+pub mod embedded;
+mod encoding;
+pub mod filter;
+pub mod language;
+pub mod markers;
+pub mod output;
+pub mod owners;
+pub mod projects;
+pub mod report;
+pub mod snapshot;
+mod tree;
+pub mod walk;
+pub mod watch;
+
+pub use crate::dedupe::Match;
+pub use crate::report::{Report, SortKey};
+
+use crate::filter::PathFilter;
+use crate::report::{
+    AuthorsReport, ComplexityReport, Duplicates, MarkersReport, OwnersReport, ProjectsReport,
+};
+use crate::walk::{Counted, WalkOptions};
+use std::ffi::OsString;
+use std::fmt::{self, Display, Formatter};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+
+/// Everything that controls what gets counted and how the report is laid out. The defaults
+/// are the same as the `tlc` binary's, except that no cache is used.
+pub struct Options {
+    /// Names of directories that should never be entered.
+    pub ignore_dirs: Vec<OsString>,
+    /// The extensions and language names to count. If empty, every file in a known language
+    /// is counted.
+    pub check: Vec<String>,
+    /// The globs that files must match at least one of. If empty, every file is included.
+    pub include: Vec<String>,
+    /// The globs that files and directories must not match.
+    pub exclude: Vec<String>,
+    /// Whether to skip files matched by `.gitignore`, `.ignore`, and friends, and the `.git`
+    /// directory.
+    pub respect_ignore: bool,
+    /// Whether to follow symbolic links. If not, symbolic links are skipped.
+    pub follow_symlinks: bool,
+    /// The number of threads to walk and count with.
+    pub threads: usize,
+    /// Whether to print every skipped path, and why it was skipped, to standard error.
+    pub explain: bool,
+    /// The directory to keep cached results in, or `None` to not use a cache.
+    pub cache_dir: Option<PathBuf>,
+    /// Whether to ignore previously cached results (but still save new ones).
+    pub rebuild_cache: bool,
+    /// What to sort the file listing by.
+    pub sort: SortKey,
+    /// Whether to flip the order of the file listing.
+    pub reverse: bool,
+    /// How many files to list, if limited. Totals still include every file.
+    pub top: Option<usize>,
+    /// Whether to include the tree of directories in the report.
+    pub tree: bool,
+    /// How many levels below each root the tree goes, if limited.
+    pub depth: Option<usize>,
+    /// How alike files have to be to be listed as copies of each other, or `None` to not
+    /// look for copies (unless `dedupe` is set).
+    pub duplicates: Option<Match>,
+    /// Whether to count each group of copies as a single file, leaving the rest out of the
+    /// listing and the totals. Without `duplicates`, only exact copies count.
+    pub dedupe: bool,
+    /// Whether to measure how complex each file and function is.
+    pub complexity: bool,
+    /// How many of the most complex files and functions to list.
+    pub complexity_top: usize,
+    /// Whether to count the files inside archives and compressed files.
+    pub archives: bool,
+    /// Whether to look for marker comments, such as `TODO`s.
+    pub markers: bool,
+    /// More marker tags to look for, besides `markers::DEFAULT_TAGS`.
+    pub marker_tags: Vec<String>,
+    /// Whether to attribute lines to their authors with `git blame`.
+    pub by_author: bool,
+    /// Whether to break each author's lines down by language.
+    pub author_languages: bool,
+    /// Whether to break each author's lines down by directory.
+    pub author_dirs: bool,
+    /// Whether to attribute files to their owners in `CODEOWNERS`.
+    pub by_owner: bool,
+    /// Whether to total up the files in each project.
+    pub by_project: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            ignore_dirs: vec![],
+            check: vec![],
+            include: vec![],
+            exclude: vec![],
+            respect_ignore: true,
+            follow_symlinks: false,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            explain: false,
+            cache_dir: None,
+            rebuild_cache: false,
+            sort: SortKey::Path,
+            reverse: false,
+            top: None,
+            tree: false,
+            depth: None,
+            duplicates: None,
+            dedupe: false,
+            complexity: false,
+            complexity_top: 10,
+            archives: false,
+            markers: false,
+            marker_tags: vec![],
+            by_author: false,
+            author_languages: false,
+            author_dirs: false,
+            by_owner: false,
+            by_project: false,
+        }
+    }
+}
+
+impl Options {
+    /// Gets the options for walking roots with `walk::count_files`.
+    ///
+    /// # Returns
+    /// The walk options, or an error if any of the globs are invalid.
+    pub fn walk_options(&self) -> Result<WalkOptions, Error> {
+        let filter = PathFilter::new(
+            self.ignore_dirs.clone(),
+            self.respect_ignore,
+            &self.include,
+            &self.exclude,
+        )?;
+        Ok(WalkOptions {
+            filter: Arc::new(filter),
+            to_check: self.check.clone(),
+            respect_ignore: self.respect_ignore,
+            follow_symlinks: self.follow_symlinks,
+            threads: self.threads,
+            explain: self.explain,
+            cache_dir: self.cache_dir.clone(),
+            rebuild_cache: self.rebuild_cache,
+            hash: self.match_mode(),
+            complexity: self.complexity,
+            archives: self.archives,
+            markers: self.marker_tags(),
+        })
+    }
+
+    /// The marker tags to look for, if they're being looked for: the default ones, then any
+    /// others that were given.
+    fn marker_tags(&self) -> Option<Vec<String>> {
+        if !self.markers {
+            return None;
+        }
+
+        let mut tags = markers::DEFAULT_TAGS
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect::<Vec<_>>();
+        for tag in &self.marker_tags {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+
+        Some(tags)
+    }
+
+    /// How alike files have to be to count as copies, if copies are being looked for.
+    fn match_mode(&self) -> Option<Match> {
+        match self.duplicates {
+            None if self.dedupe => Some(Match::Exact),
+            mode => mode,
+        }
+    }
+
+    /// Builds a report out of what was counted under each root, deduplicated, sorted, and
+    /// trimmed as these options say.
+    pub fn report(&self, counted: &[(String, Counted)]) -> Report {
+        let duplicates = self.match_mode().map(|mode| {
+            Duplicates::new(&dedupe::find_groups(counted), mode, self.dedupe)
+        });
+        let deduped;
+        let counted = if self.dedupe {
+            deduped = dedupe::remove_copies(counted);
+            &deduped
+        } else {
+            counted
+        };
+
+        let mut report = if self.tree {
+            Report::with_tree(counted, self.depth)
+        } else {
+            Report::new(counted)
+        };
+        report.sort_files(self.sort, self.reverse, self.top);
+        report.duplicates = duplicates;
+        if self.complexity {
+            report.complexity = Some(ComplexityReport::new(counted, self.complexity_top));
+        }
+
+        if let Some(tags) = self.marker_tags() {
+            report.markers = Some(MarkersReport::new(counted, &tags));
+        }
+
+        let files = counted
+            .iter()
+            .flat_map(|(_, counted)| &counted.files)
+            .collect::<Vec<_>>();
+        if self.by_author {
+            let authors = blame::blame_files(&files, self.threads, self.explain);
+            report.authors = Some(AuthorsReport::new(
+                &files,
+                &authors,
+                self.author_languages,
+                self.author_dirs,
+            ));
+        }
+
+        if self.by_owner {
+            report.owners = Some(OwnersReport::new(&files, &owners::find_owners(&files)));
+        }
+
+        if self.by_project {
+            let projects = projects::find_projects(&files);
+            report.projects = Some(ProjectsReport::new(&files, &projects));
+        }
+
+        report
+    }
+}
+
+/// A group of paths that are counted together and get their own subtotal.
+pub struct Root {
+    /// What to call the root in the report.
+    pub name: String,
+    pub paths: Vec<PathBuf>,
+}
+
+impl Root {
+    /// Makes a root out of a single path, named after it.
+    pub fn from_path(path: PathBuf) -> Self {
+        Root {
+            name: path.display().to_string(),
+            paths: vec![path],
+        }
+    }
+}
+
+/// Everything that can keep a report from being made.
+#[derive(Debug)]
+pub enum Error {
+    /// One of the `include` or `exclude` globs is invalid.
+    Glob(globset::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Glob(e) => write!(f, "invalid glob: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<globset::Error> for Error {
+    fn from(e: globset::Error) -> Self {
+        Error::Glob(e)
+    }
+}
+
+/// Counts every file under the given paths, each of which is its own root.
+///
+/// # Parameters
+/// - `paths`: The directories and files to count.
+/// - `options`: What to count, and how to lay out the report.
+///
+/// # Returns
+/// The report, or an error if the options are invalid.
+pub fn count(paths: &[PathBuf], options: &Options) -> Result<Report, Error> {
+    let roots = paths
+        .iter()
+        .cloned()
+        .map(Root::from_path)
+        .collect::<Vec<_>>();
+    count_roots(&roots, options)
+}
+
+/// Counts every file under the given roots.
+///
+/// # Parameters
+/// - `roots`: The roots, in the order they should be listed.
+/// - `options`: What to count, and how to lay out the report.
+///
+/// # Returns
+/// The report, or an error if the options are invalid.
+pub fn count_roots(roots: &[Root], options: &Options) -> Result<Report, Error> {
+    let counted = walk_roots(roots, &options.walk_options()?);
+    Ok(options.report(&counted))
+}
+
+/// Walks each root in turn.
+///
+/// # Returns
+/// The name of each root, along with what was counted under it.
+pub fn walk_roots(roots: &[Root], opts: &WalkOptions) -> Vec<(String, Counted)> {
+    roots
+        .iter()
+        .map(|root| (root.name.clone(), walk::count_files(&root.paths, opts)))
+        .collect()
+}
@@ -1,131 +1,466 @@
+use chrono::{DateTime, Utc};
 use clap::{AppSettings, Clap};
-use std::fs::{File};
 use std::error::Error;
-use std::path::{Path, PathBuf};
-use std::io::{BufReader, BufRead};
-use std::ffi::OsStr;
-use std::collections::VecDeque;
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::SystemTime;
+use tlc::diff::Diff;
+use tlc::output::{self, Format};
+use tlc::snapshot::{self, History, Snapshot};
+use tlc::{cache, watch, Match, Options, Report, Root, SortKey};
 
 fn main() -> Result<(), Box<dyn Error>> {
     let opts: Opts = Opts::parse();
+    if let Some(command) = &opts.command {
+        return run_command(command);
+    }
 
-    let dir = Path::new(&opts.dir);
-    let dirs_to_avoid = opts.dirs_to_ignore
-        .split_ascii_whitespace()
-        .map(|x| OsStr::new(x))
-        .collect::<Vec<_>>();
-    let ext_to_check = opts.extensions_to_check
-        .split_ascii_whitespace()
-        .map(|x| OsStr::new(x))
+    let mut options = Options {
+        ignore_dirs: opts
+            .dirs_to_ignore
+            .split_ascii_whitespace()
+            .map(OsString::from)
+            .collect(),
+        check: opts
+            .extensions_to_check
+            .split_ascii_whitespace()
+            .map(String::from)
+            .collect(),
+        include: opts.include.clone(),
+        exclude: opts.exclude.clone(),
+        respect_ignore: !opts.no_ignore,
+        follow_symlinks: opts.follow_symlinks,
+        explain: opts.explain,
+        cache_dir: if opts.no_cache {
+            None
+        } else {
+            opts.cache_dir.clone().or_else(cache::default_dir)
+        },
+        rebuild_cache: opts.rebuild_cache,
+        sort: opts.sort,
+        reverse: opts.reverse,
+        top: opts.top,
+        tree: opts.tree,
+        depth: opts.depth,
+        duplicates: if opts.near_duplicates {
+            Some(Match::Whitespace)
+        } else if opts.duplicates {
+            Some(Match::Exact)
+        } else {
+            None
+        },
+        dedupe: opts.dedupe,
+        complexity: opts.complexity || opts.sort == SortKey::Complexity,
+        archives: opts.archives,
+        markers: opts.markers,
+        marker_tags: opts.marker_tags.clone(),
+        by_author: opts.by_author,
+        author_languages: opts.author_languages,
+        author_dirs: opts.author_dirs,
+        by_owner: opts.by_owner,
+        by_project: opts.by_project,
+        ..Options::default()
+    };
+    if let Some(threads) = opts.threads {
+        options.threads = threads;
+    }
+
+    if let Some(top) = opts.complexity_top {
+        options.complexity_top = top;
+    }
+
+    let mut roots = opts
+        .dirs
+        .iter()
+        .chain(opts.paths.iter())
+        .cloned()
+        .map(Root::from_path)
         .collect::<Vec<_>>();
+    if let Some(list) = &opts.files_from {
+        let label = if list == "-" { "<stdin>" } else { list };
+        roots.push(Root {
+            name: label.to_string(),
+            paths: read_path_list(list)?,
+        });
+    }
+
+    if roots.is_empty() {
+        roots.push(Root::from_path(PathBuf::from(".")));
+    }
+
+    let walk_opts = options.walk_options()?;
+    if opts.watch {
+        return watch::run(&roots, &walk_opts, |counted, changed| {
+            show_update(&options.report(counted), changed, roots.len(), opts.format)
+        });
+    }
+
+    let counted = tlc::walk_roots(&roots, &walk_opts);
+    if let Some(label) = &opts.snapshot {
+        // Snapshots always hold every file and the whole tree, no matter what is shown.
+        let full = Report::with_tree(&counted, None);
+        let path = Snapshot::new(label, full).save(&opts.snapshot_dir)?;
+        eprintln!("Saved snapshot to {}", path.display());
+    }
+
+    let report = options.report(&counted);
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    output::write_report(&mut out, &report, opts.format)?;
+    out.flush()?;
+    if opts.strict && !report.errors.is_empty() {
+        eprintln!(
+            "tlc: {} path(s) couldn't be read, so the counts are incomplete",
+            report.errors.len()
+        );
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Prints one update in watch mode. In a terminal, the text format redraws the summary in
+/// place; otherwise, each update is written after the one before.
+///
+/// # Parameters
+/// - `report`: The report.
+/// - `changed`: How many paths changed since the last update; 0 for the first scan.
+/// - `roots`: How many roots are being watched.
+/// - `format`: The output format.
+///
+/// # Returns
+/// Any error from writing to standard output.
+fn show_update(report: &Report, changed: usize, roots: usize, format: Format) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if format != Format::Text {
+        output::write_report(&mut out, report, format)?;
+        return out.flush();
+    }
+
+    if io::stdout().is_terminal() {
+        // Clear the screen and move to the top left.
+        write!(out, "\x1b[2J\x1b[H")?;
+    } else if changed > 0 {
+        writeln!(out)?;
+    }
+
+    let now: DateTime<Utc> = SystemTime::now().into();
+    let what = if changed == 0 {
+        "Counted".to_string()
+    } else {
+        format!("{} path(s) changed", changed)
+    };
+    writeln!(
+        out,
+        "{} at {}; watching {} root(s). Press Ctrl-C to stop.",
+        what,
+        now.format("%H:%M:%S UTC"),
+        roots
+    )?;
+    writeln!(out)?;
+    output::write_summary(&mut out, report)?;
+    out.flush()
+}
 
-    let mut line_ct: usize = 0;
-    let mut dir_queue: VecDeque<PathBuf> = VecDeque::new();
-    dir_queue.push_back(PathBuf::from(dir));
-
-    while !dir_queue.is_empty() {
-        let this_dir = dir_queue.pop_front().unwrap();
-        let directory = match this_dir.read_dir() {
-            Ok(d) => d,
-            Err(_) => continue
-        };
-
-        for f in directory {
-            let file = f.unwrap();
-            let entry = file.path();
-            let name = file.file_name();
-
-            if entry.is_dir() {
-                if dirs_to_avoid.iter().any(|x| (*x) == name) {
-                    continue;
-                }
-
-                dir_queue.push_back(entry);
-                continue;
-            }
-
-            match entry.extension() {
-                None => continue,
-                Some(extension) => {
-                    if !ext_to_check.contains(&extension) {
-                        continue;
-                    }
-                }
-            }
-
-
-            let file = match File::open(entry.as_path()) {
-                Ok(f) => f,
-                Err(_) => continue
-            };
-
-            let ct = BufReader::new(file).lines().count();
-            println!("{0:<10} {1}", ct, entry.as_path().display());
-            line_ct += ct;
+/// Runs one of the subcommands that work with saved snapshots.
+fn run_command(command: &Command) -> Result<(), Box<dyn Error>> {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    match command {
+        Command::Diff(opts) => {
+            let old = snapshot::find(&opts.old, &opts.snapshot_dir)?;
+            let new = snapshot::find(&opts.new, &opts.snapshot_dir)?;
+            let diff = Diff::new(&old, &new, opts.depth);
+            output::write_diff(&mut out, &diff, opts.format)?;
         }
+        Command::History(opts) => {
+            let history = History::new(&snapshot::load_all(&opts.snapshot_dir)?);
+            output::write_history(&mut out, &history, opts.format)?;
+        }
+    }
+
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads a list of paths from a file, or from standard input if `source` is `-`. If the
+/// list contains any NUL bytes, paths are separated by NULs (as with `git ls-files -z`);
+/// otherwise, they are separated by line breaks. Empty entries are skipped.
+///
+/// # Parameters
+/// - `source`: The file to read from, or `-` for standard input.
+///
+/// # Returns
+/// The paths, or the error from reading the list.
+fn read_path_list(source: &str) -> io::Result<Vec<PathBuf>> {
+    let mut data = vec![];
+    if source == "-" {
+        io::stdin().lock().read_to_end(&mut data)?;
+    } else {
+        File::open(source)?.read_to_end(&mut data)?;
     }
 
-    println!("Total Lines: {}", line_ct);
-    return Ok(());
+    let separator = if data.contains(&0) { b'\0' } else { b'\n' };
+    Ok(data
+        .split(|&b| b == separator)
+        .map(|entry| entry.strip_suffix(b"\r").unwrap_or(entry))
+        .filter(|entry| !entry.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
 }
 
 #[derive(Clap)]
-#[clap(version = "0.1.0", about = "Recursively gets the total line count of all files in one or more directories.")]
+#[clap(
+    version = "0.1.0",
+    about = "Recursively gets the total line count of all files in one or more directories."
+)]
 #[clap(setting = AppSettings::ColoredHelp)]
 struct Opts {
-    #[clap(short('d'), long("dir"), default_value = ".")]
-    dir: String,
+    /// A directory or file to count. Can be given more than once; each one gets its own
+    /// subtotal. Defaults to the current directory if no paths are given at all.
+    #[clap(
+        short('d'),
+        long("dir"),
+        parse(from_os_str),
+        multiple_occurrences(true),
+        number_of_values(1)
+    )]
+    dirs: Vec<PathBuf>,
+
+    /// More directories or files to count, the same as passing them to `--dir`.
+    #[clap(parse(from_os_str))]
+    paths: Vec<PathBuf>,
+
+    /// Also count the paths listed in this file (`-` for standard input), separated by line
+    /// breaks or NUL bytes. Together, these get a single subtotal.
+    #[clap(long("files-from"))]
+    files_from: Option<String>,
 
     #[clap(short('i'), long("ignore"), default_value = "")]
     dirs_to_ignore: String,
 
+    /// Only count files whose path (relative to its root) matches this glob. Can be given
+    /// more than once; a file only has to match one of them.
+    #[clap(long("include"), multiple_occurrences(true), number_of_values(1))]
+    include: Vec<String>,
+
+    /// Skip files and directories whose path (relative to its root) matches this glob. Can
+    /// be given more than once.
+    #[clap(long("exclude"), multiple_occurrences(true), number_of_values(1))]
+    exclude: Vec<String>,
+
+    /// Print every path that was skipped, along with the rule that skipped it, to standard
+    /// error.
+    #[clap(long("explain"))]
+    explain: bool,
+
+    /// Exit with a non-zero status if any path couldn't be read (after writing the report,
+    /// which lists them), instead of only listing them.
+    #[clap(long("strict"), conflicts_with("watch"))]
+    strict: bool,
+
+    /// The file extensions or language names (e.g. `python`) to count, separated by spaces.
+    /// If none are given, every file written in a language tlc knows about is counted.
     #[clap(short('c'), long("check"), default_value = "")]
     extensions_to_check: String,
+
+    /// Don't skip files matched by `.gitignore`, `.ignore`, `.git/info/exclude`, or the
+    /// global git excludes file.
+    #[clap(long("no-ignore"))]
+    no_ignore: bool,
+
+    /// Follow symbolic links. Without this, they are skipped.
+    #[clap(short('L'), long("follow-symlinks"))]
+    follow_symlinks: bool,
+
+    /// The number of threads to use. Defaults to the number of CPUs.
+    #[clap(short('j'), long("threads"))]
+    threads: Option<usize>,
+
+    /// Don't use the cache: read every file, and don't save anything.
+    #[clap(long("no-cache"), conflicts_with("rebuild-cache"))]
+    no_cache: bool,
+
+    /// Ignore what's in the cache and count every file again, then save the fresh results.
+    #[clap(long("rebuild-cache"))]
+    rebuild_cache: bool,
+
+    /// Where to keep the cache. Defaults to `tlc` in the user's cache directory.
+    #[clap(long("cache-dir"), parse(from_os_str))]
+    cache_dir: Option<PathBuf>,
+
+    /// What to sort the file listing by. Paths are sorted A to Z; everything else is sorted
+    /// from largest to smallest.
+    #[clap(short('s'), long("sort"), arg_enum, default_value = "path")]
+    sort: SortKey,
+
+    /// Flip the order of the file listing.
+    #[clap(short('r'), long("reverse"))]
+    reverse: bool,
+
+    /// Only list this many files (after sorting). The totals still include every file.
+    #[clap(short('n'), long("top"))]
+    top: Option<usize>,
+
+    /// Instead of listing every file, show a tree of directories with the rolled-up totals
+    /// of everything under each one.
+    #[clap(long("tree"))]
+    tree: bool,
+
+    /// With `--tree`, how many levels below each root to show. Deeper directories are still
+    /// counted in the directories above them.
+    #[clap(long("depth"), requires("tree"))]
+    depth: Option<usize>,
+
+    /// List the groups of files that have the same contents.
+    #[clap(long("duplicates"))]
+    duplicates: bool,
+
+    /// Also treat files that only differ in whitespace as copies. Implies `--duplicates`.
+    #[clap(long("near-duplicates"))]
+    near_duplicates: bool,
+
+    /// Count each group of copies as a single file: only the first copy (by root, then by
+    /// path) is listed and counted in the totals. Also lists the groups.
+    #[clap(long("dedupe"))]
+    dedupe: bool,
+
+    /// Measure how complex the code is, in the languages that `tlc` knows how to: the
+    /// number of functions, how deeply blocks are nested, and the approximate cyclomatic
+    /// complexity of each file and function. The most complex ones are listed after the
+    /// totals. Implied by `--sort complexity`.
+    #[clap(long("complexity"))]
+    complexity: bool,
+
+    /// How many of the most complex files, and of the most complex functions, to list.
+    /// Defaults to 10.
+    #[clap(long("complexity-top"), requires("complexity"))]
+    complexity_top: Option<usize>,
+
+    /// List the marker comments in the code, such as `// TODO(alice): handle errors`: how
+    /// many there are of each tag, in total and in each directory, and every one of them
+    /// with its file and line. Looks for `TODO`, `FIXME`, `HACK`, and `XXX`.
+    #[clap(long("markers"))]
+    markers: bool,
+
+    /// Another tag to look for with `--markers`, such as `NOTE`. Can be given more than once.
+    #[clap(
+        long("marker-tag"),
+        requires("markers"),
+        multiple_occurrences(true),
+        number_of_values(1)
+    )]
+    marker_tags: Vec<String>,
+
+    /// Attribute the lines of every file in a git repository to whoever last changed them,
+    /// as `git blame` would (applying the repository's `.mailmap`), and list each author's
+    /// totals. Lines that haven't been committed go to "Not Committed Yet".
+    #[clap(long("by-author"), conflicts_with("watch"))]
+    by_author: bool,
+
+    /// Break each author's lines down by language, with `--by-author`.
+    #[clap(long("author-languages"), requires("by-author"))]
+    author_languages: bool,
+
+    /// Break each author's lines down by directory, with `--by-author`.
+    #[clap(long("author-dirs"), requires("by-author"))]
+    author_dirs: bool,
+
+    /// Attribute every file to its owners in the repository's `CODEOWNERS` file (at the top
+    /// of the repository, or in `.github/` or `docs/`), where the last matching rule wins,
+    /// and list each owner's totals. A file with more than one owner counts toward each of
+    /// them, and files that no one owns are totalled on their own.
+    #[clap(long("by-owner"))]
+    by_owner: bool,
+
+    /// Total up the files in each project: the closest directory above a file with a
+    /// `Cargo.toml`, `package.json`, `pyproject.toml`, `go.mod`, or `pom.xml` in it. Each
+    /// project is listed by the name its manifest gives it and its kind, with its totals in
+    /// each language.
+    #[clap(long("by-project"))]
+    by_project: bool,
+
+    /// Count the files inside `.zip`, `.tar`, `.tar.gz`, `.tar.xz`, and `.gz` files, as if
+    /// they were unpacked. Each one is listed as the archive's path, then `!/`, then its path
+    /// inside the archive (e.g. `foo.tar.gz!/src/lib.rs`).
+    #[clap(long("archives"))]
+    archives: bool,
+
+    /// Keep running after counting, and count again whenever files under the roots change.
+    /// Only the changed files are read again. With the text format, the per-language
+    /// summary is redrawn after each change.
+    #[clap(short('w'), long("watch"), conflicts_with("snapshot"))]
+    watch: bool,
+
+    /// Also save the full report as a snapshot with this label, to compare against later
+    /// with `tlc diff` and `tlc history`.
+    #[clap(long("snapshot"))]
+    snapshot: Option<String>,
+
+    /// The directory that snapshots are saved in.
+    #[clap(long("snapshot-dir"), parse(from_os_str), default_value = snapshot::DEFAULT_DIR)]
+    snapshot_dir: PathBuf,
+
+    /// The output format.
+    #[clap(short('f'), long("format"), arg_enum, default_value = "text")]
+    format: Format,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
+#[derive(Clap)]
+enum Command {
+    /// Show what changed between two snapshots.
+    Diff(DiffOpts),
+    /// Show how the grand total changed over every saved snapshot.
+    History(HistoryOpts),
+}
 
-// Recursive solution:
-// fn get_line_ct(path: &Path, dirs_avoid: &Vec<&OsStr>, ext_check: &Vec<&OsStr>) -> usize {
-//     let mut line_ct: usize = 0;
-//     let directory = match path.read_dir() {
-//         Ok(d) => d,
-//         Err(_) => return line_ct
-//     };
-//
-//     for f in directory {
-//         let file = f.unwrap();
-//         let entry = file.path();
-//         let name = file.file_name();
-//
-//         if entry.is_dir() {
-//             if dirs_avoid.iter().any(|x| (*x) == name) {
-//                 continue;
-//             }
-//
-//             line_ct += get_line_ct(entry.as_path(), dirs_avoid, ext_check);
-//             continue;
-//         }
-//
-//
-//         match entry.extension() {
-//             None => continue,
-//             Some(extension) => {
-//                 if !ext_check.contains(&extension) {
-//                     continue;
-//                 }
-//             }
-//         }
-//
-//
-//         let file = match File::open(entry.as_path()) {
-//             Ok(f) => f,
-//             Err(_) => continue
-//         };
-//
-//         let ct = BufReader::new(file).lines().count();
-//         println!("{0:<10} {1}", ct, entry.as_path().display());
-//         line_ct += ct;
-//     }
-//
-//     return line_ct;
-// }
\ No newline at end of file
+#[derive(Clap)]
+struct DiffOpts {
+    /// The older snapshot: either a snapshot file, or the label of a saved snapshot (the
+    /// newest one, if several have the label).
+    old: String,
+
+    /// The newer snapshot, given the same way.
+    new: String,
+
+    /// How many levels below each root to show directory changes for.
+    #[clap(long("depth"))]
+    depth: Option<usize>,
+
+    /// The directory that snapshots are saved in.
+    #[clap(long("snapshot-dir"), parse(from_os_str), default_value = snapshot::DEFAULT_DIR)]
+    snapshot_dir: PathBuf,
+
+    /// The output format.
+    #[clap(short('f'), long("format"), arg_enum, default_value = "text")]
+    format: Format,
+}
+
+#[derive(Clap)]
+struct HistoryOpts {
+    /// The directory that snapshots are saved in.
+    #[clap(long("snapshot-dir"), parse(from_os_str), default_value = snapshot::DEFAULT_DIR)]
+    snapshot_dir: PathBuf,
+
+    /// The output format.
+    #[clap(short('f'), long("format"), arg_enum, default_value = "text")]
+    format: Format,
+}
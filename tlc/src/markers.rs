@@ -0,0 +1,180 @@
+use crate::count::CodeSink;
+use memchr::memmem;
+use serde::{Deserialize, Serialize};
+
+/// The tags that are looked for unless others are added.
+pub const DEFAULT_TAGS: &[&str] = &["TODO", "FIXME", "HACK", "XXX"];
+
+/// A marker comment, such as `// TODO(alice): handle errors`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Marker {
+    /// The line the marker is on, counting from 1.
+    pub line: usize,
+    pub tag: String,
+    /// Who the marker is for, if it names someone in brackets after the tag.
+    pub owner: Option<String>,
+    /// The rest of the comment on that line.
+    pub text: String,
+}
+
+/// Looks for marker comments in the comments that it's given, one line at a time.
+///
+/// A tag only counts as a whole word, spelled exactly as given, and only if it starts the
+/// comment or is followed by `:` or an owner in brackets, so that comments that just
+/// mention a tag (as in "see the TODO list") aren't taken for markers. Only the first
+/// marker on each line is kept.
+pub struct Scanner<'a> {
+    tags: &'a [String],
+    /// The line being scanned, counting from 1.
+    line: usize,
+    /// The comment text on the line so far.
+    comment: Vec<u8>,
+    markers: Vec<Marker>,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(tags: &'a [String]) -> Self {
+        Scanner {
+            tags,
+            line: 1,
+            comment: vec![],
+            markers: vec![],
+        }
+    }
+
+    /// Stops scanning.
+    ///
+    /// # Returns
+    /// Every marker that was found, in the order they appear.
+    pub fn finish(self) -> Vec<Marker> {
+        self.markers
+    }
+}
+
+impl CodeSink for Scanner<'_> {
+    fn code(&mut self, _code: &[u8], _offset: usize) {}
+
+    fn comment(&mut self, text: &[u8]) {
+        self.comment.extend_from_slice(text);
+    }
+
+    fn end_line(&mut self) {
+        // Most comments don't mention any tag, so they're ruled out before being scanned.
+        let mentioned = |tag: &String| memmem::find(&self.comment, tag.as_bytes()).is_some();
+        if self.tags.iter().any(mentioned) {
+            let comment = String::from_utf8_lossy(&self.comment);
+            if let Some(marker) = find(self.tags, &comment, self.line) {
+                self.markers.push(marker);
+            }
+        }
+
+        self.comment.clear();
+        self.line += 1;
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Finds the first marker in the comment text on a line.
+fn find(tags: &[String], comment: &str, line: usize) -> Option<Marker> {
+    let starts = comment
+        .char_indices()
+        .filter(|&(i, _)| !comment[..i].chars().next_back().is_some_and(is_word));
+    for (i, _) in starts {
+        let rest = &comment[i..];
+        let tag = match tags.iter().find(|tag| {
+            rest.starts_with(tag.as_str()) && !rest[tag.len()..].chars().next().is_some_and(is_word)
+        }) {
+            Some(tag) => tag,
+            None => continue,
+        };
+
+        let mut after = &rest[tag.len()..];
+        let mut owner = None;
+        if let Some((name, tail)) = after.strip_prefix('(').and_then(|a| a.split_once(')')) {
+            owner = Some(name.trim().trim_start_matches('@').to_string()).filter(|o| !o.is_empty());
+            after = tail;
+        }
+
+        let first = !comment[..i].chars().any(is_word);
+        if !first && owner.is_none() && !after.starts_with(':') {
+            continue;
+        }
+
+        return Some(Marker {
+            line,
+            tag: tag.clone(),
+            owner,
+            text: after
+                .trim_start_matches(|c: char| c == ':' || c == '-' || c.is_whitespace())
+                .trim_end()
+                .to_string(),
+        });
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::count;
+    use crate::language;
+
+    fn tags() -> Vec<String> {
+        DEFAULT_TAGS.iter().map(|t| t.to_string()).collect()
+    }
+
+    fn markers(text: &str, lang: &str) -> Vec<(usize, String, Option<String>, String)> {
+        let tags = tags();
+        let mut scanner = Scanner::new(&tags);
+        let lang = language::from_name(lang).unwrap();
+        count::count_text_into(text.as_bytes(), lang, &mut scanner);
+        scanner
+            .finish()
+            .into_iter()
+            .map(|m| (m.line, m.tag, m.owner, m.text))
+            .collect()
+    }
+
+    #[test]
+    fn tags_owners_and_text() {
+        let text = "\
+// TODO: first
+let s = \"// FIXME: in a string\";
+/* HACK(@bob) - work around the parser
+ * XXX(carol): nested in a block */
+x = 1; // FIXME(): no owner
+";
+        assert_eq!(
+            markers(text, "rust"),
+            [
+                (1, "TODO".to_string(), None, "first".to_string()),
+                (
+                    3,
+                    "HACK".to_string(),
+                    Some("bob".to_string()),
+                    "work around the parser".to_string()
+                ),
+                (
+                    4,
+                    "XXX".to_string(),
+                    Some("carol".to_string()),
+                    "nested in a block".to_string()
+                ),
+                (5, "FIXME".to_string(), None, "no owner".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn mentions_are_not_markers() {
+        let text = "# see the TODO list\n# TODOS: plural\n# todo: lower case\n# TODO\n";
+        assert_eq!(
+            markers(text, "python"),
+            [(4, "TODO".to_string(), None, String::new())]
+        );
+    }
+}
@@ -0,0 +1,1735 @@
+use crate::diff::{Delta, Diff, DirectoryChange};
+use crate::dedupe::Match;
+use crate::report::{
+    AuthorTotals, AuthorsReport, Bucket, ComplexityEntry, ComplexityReport, Distribution,
+    Duplicates, MarkersReport, OwnersReport, ProjectsReport, Report, Totals, TreeEntry,
+};
+use crate::snapshot::History;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ArgEnum;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The ways that a report can be printed.
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+    Tsv,
+    Markdown,
+}
+
+/// Writes the report in the given format.
+///
+/// # Parameters
+/// - `out`: Where to write the report.
+/// - `report`: The report.
+/// - `format`: The format to write it in.
+///
+/// # Returns
+/// Any error from writing to `out`.
+pub fn write_report<W: Write>(out: &mut W, report: &Report, format: Format) -> io::Result<()> {
+    match format {
+        Format::Text => write_text(out, report),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, report)?;
+            writeln!(out)
+        }
+        Format::Csv => write_delimited(out, report, ',', escape_csv),
+        Format::Tsv => write_delimited(out, report, '\t', escape_tsv),
+        Format::Markdown => write_markdown(out, report),
+    }
+}
+
+fn write_text<W: Write>(out: &mut W, report: &Report) -> io::Result<()> {
+    match &report.tree {
+        Some(tree) => write_text_tree(out, tree)?,
+        None => write_text_files(out, report)?,
+    }
+
+    writeln!(out)?;
+    write_text_languages(out, report)?;
+    write_text_distribution(out, &report.distribution)?;
+    write_text_roots(out, report)?;
+    if let Some(complexity) = &report.complexity {
+        write_text_complexity(out, complexity)?;
+    }
+
+    if let Some(duplicates) = &report.duplicates {
+        write_text_duplicates(out, duplicates)?;
+    }
+
+    if let Some(markers) = &report.markers {
+        write_text_markers(out, markers)?;
+    }
+
+    if let Some(authors) = &report.authors {
+        write_text_authors(out, authors)?;
+    }
+
+    if let Some(owners) = &report.owners {
+        write_text_owners(out, owners)?;
+    }
+
+    if let Some(projects) = &report.projects {
+        write_text_projects(out, projects)?;
+    }
+
+    if !report.binary.is_empty() {
+        writeln!(out)?;
+        for file in &report.binary {
+            writeln!(out, "Skipped binary file: {}", file.path)?;
+        }
+    }
+
+    write_text_errors(out, report)
+}
+
+/// Writes just the per-language and per-root totals as text, for a summary that is
+/// redrawn over and over (as in watch mode).
+///
+/// # Parameters
+/// - `out`: Where to write the summary.
+/// - `report`: The report.
+///
+/// # Returns
+/// Any error from writing to `out`.
+pub fn write_summary<W: Write>(out: &mut W, report: &Report) -> io::Result<()> {
+    write_text_languages(out, report)?;
+    write_text_roots(out, report)?;
+    write_text_errors(out, report)
+}
+
+fn write_text_errors<W: Write>(out: &mut W, report: &Report) -> io::Result<()> {
+    if report.errors.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Couldn't read {} path(s):", report.errors.len())?;
+    for error in &report.errors {
+        writeln!(out, "  {}: {}", error.path, error.reason)?;
+    }
+
+    Ok(())
+}
+
+fn write_text_languages<W: Write>(out: &mut W, report: &Report) -> io::Result<()> {
+    writeln!(
+        out,
+        "{0:<16} {1:>8} {2:>10} {3:>10} {4:>10} {5:>10} {6:>12}",
+        "Language", "Files", "Lines", "Code", "Comment", "Blank", "Bytes"
+    )?;
+    for lang in &report.languages {
+        write_text_row(out, &lang.language, &lang.totals)?;
+    }
+
+    write_text_row(out, "Total", &report.total)
+}
+
+fn write_text_roots<W: Write>(out: &mut W, report: &Report) -> io::Result<()> {
+    // With only one root, its subtotal is just the grand total.
+    if report.roots.len() <= 1 {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{0:>8} {1:>10} {2:>10} {3:>10} {4:>10} {5:>12} Root",
+        "Files", "Lines", "Code", "Comment", "Blank", "Bytes"
+    )?;
+    for root in &report.roots {
+        let t = &root.totals;
+        writeln!(
+            out,
+            "{0:>8} {1:>10} {2:>10} {3:>10} {4:>10} {5:>12} {6}",
+            t.files, t.lines, t.code, t.comment, t.blank, t.bytes, root.root
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes the percentiles, then a histogram with one `#` per file (scaled down to fit if
+/// the biggest bucket has too many files).
+fn write_text_distribution<W: Write>(out: &mut W, dist: &Distribution) -> io::Result<()> {
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Lines per file: median {}, p90 {}, p95 {}, p99 {}, max {}",
+        dist.median, dist.p90, dist.p95, dist.p99, dist.max
+    )?;
+
+    let most = dist.histogram.iter().map(|b| b.files).max().unwrap_or(0);
+    let width = 50;
+    for bucket in &dist.histogram {
+        let bar = if most <= width {
+            bucket.files
+        } else {
+            (bucket.files * width).div_ceil(most)
+        };
+        let line = format!(
+            "{0:>10} {1:>8}  {2}",
+            bucket_label(bucket),
+            bucket.files,
+            "#".repeat(bar)
+        );
+        writeln!(out, "{}", line.trim_end())?;
+    }
+
+    Ok(())
+}
+
+/// Sums up the duplicates in a sentence, like `Duplicates: 2 group(s), with 3 extra
+/// file(s) and 120 extra lines`.
+fn duplicates_summary(dups: &Duplicates) -> String {
+    format!(
+        "Duplicates{}: {} group(s), with {} extra file(s) and {} extra lines{}",
+        match dups.mode {
+            Match::Exact => "",
+            Match::Whitespace => " (ignoring whitespace)",
+        },
+        dups.groups.len(),
+        dups.copies.files,
+        dups.copies.lines,
+        if dups.deduplicated { " (not counted in the totals)" } else { "" }
+    )
+}
+
+/// Writes each group of copies as a block of files, with a blank line between groups.
+fn write_text_duplicates<W: Write>(out: &mut W, dups: &Duplicates) -> io::Result<()> {
+    writeln!(out)?;
+    writeln!(out, "{}", duplicates_summary(dups))?;
+    if dups.groups.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out, "{0:>10} {1:>12}  File", "Lines", "Bytes")?;
+    for (i, group) in dups.groups.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+
+        for file in &group.files {
+            writeln!(out, "{0:>10} {1:>12}  {2}", file.lines, file.bytes, file.path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes how many markers there are with each tag, then by directory, then each marker.
+fn write_text_markers<W: Write>(out: &mut W, markers: &MarkersReport) -> io::Result<()> {
+    writeln!(out)?;
+    let tags = markers
+        .tags
+        .iter()
+        .map(|t| format!("{} {}", t.tag, t.count))
+        .collect::<Vec<_>>();
+    writeln!(out, "Markers: {}", tags.join(", "))?;
+    if markers.markers.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "{0:>10}  {1:<10} Directory", "Markers", "Tag")?;
+    for dir in &markers.directories {
+        writeln!(out, "{0:>10}  {1:<10} {2}", dir.count, dir.tag, dir.path)?;
+    }
+
+    writeln!(out)?;
+    for marker in &markers.markers {
+        let owner = marker
+            .owner
+            .as_ref()
+            .map(|owner| format!("({})", owner))
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "{}:{}: {}{}: {}",
+            marker.path, marker.line, marker.tag, owner, marker.text
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes each author's totals, then the breakdowns by language and directory if there are
+/// any, with the lines that couldn't be attributed at the end of the authors.
+fn write_text_authors<W: Write>(out: &mut W, authors: &AuthorsReport) -> io::Result<()> {
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{0:>8} {1:>10} {2:>10} {3:>10} {4:>10}  Author",
+        "Files", "Lines", "Code", "Comment", "Blank"
+    )?;
+    for author in &authors.authors {
+        let name = format!("{} <{}>", author.name, author.email);
+        write_text_author_row(out, &author.totals, &name)?;
+    }
+
+    if authors.unattributed.files > 0 {
+        write_text_author_row(out, &authors.unattributed, "(not in git)")?;
+    }
+
+    if let Some(languages) = &authors.languages {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{0:>8} {1:>10} {2:>10} {3:>10} {4:>10}  {5:<16} Author",
+            "Files", "Lines", "Code", "Comment", "Blank", "Language"
+        )?;
+        for lang in languages {
+            let name = format!("{:<16} {}", lang.language, lang.email);
+            write_text_author_row(out, &lang.totals, &name)?;
+        }
+    }
+
+    if let Some(directories) = &authors.directories {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{0:>8} {1:>10} {2:>10} {3:>10} {4:>10}  Author / Directory",
+            "Files", "Lines", "Code", "Comment", "Blank"
+        )?;
+        for dir in directories {
+            let name = format!("{} {}", dir.email, dir.path);
+            write_text_author_row(out, &dir.totals, &name)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_text_author_row<W: Write>(
+    out: &mut W,
+    totals: &AuthorTotals,
+    name: &str,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "{0:>8} {1:>10} {2:>10} {3:>10} {4:>10}  {5}",
+        totals.files, totals.lines, totals.code, totals.comment, totals.blank, name
+    )
+}
+
+/// Writes each owner's totals, with the files that no one owns at the end.
+fn write_text_owners<W: Write>(out: &mut W, owners: &OwnersReport) -> io::Result<()> {
+    let write_row = |out: &mut W, totals: &Totals, name: &str| {
+        writeln!(
+            out,
+            "{0:>8} {1:>10} {2:>10} {3:>10} {4:>10}  {5}",
+            totals.files, totals.lines, totals.code, totals.comment, totals.blank, name
+        )
+    };
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{0:>8} {1:>10} {2:>10} {3:>10} {4:>10}  Owner",
+        "Files", "Lines", "Code", "Comment", "Blank"
+    )?;
+    for owner in &owners.owners {
+        write_row(out, &owner.totals, &owner.owner)?;
+    }
+
+    if owners.unowned.files > 0 {
+        write_row(out, &owners.unowned, "(no owner)")?;
+    }
+
+    Ok(())
+}
+
+/// Writes each project's totals, each followed by its totals in each language, with the
+/// files that aren't in a project at the end.
+fn write_text_projects<W: Write>(out: &mut W, projects: &ProjectsReport) -> io::Result<()> {
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{0:<16} {1:>8} {2:>10} {3:>10} {4:>10} {5:>10} {6:>12}",
+        "Project", "Files", "Lines", "Code", "Comment", "Blank", "Bytes"
+    )?;
+    for project in &projects.projects {
+        let kind = project.kind.name();
+        writeln!(out, "{} ({}, {})", project.name, kind, project.path)?;
+        for lang in &project.languages {
+            write_text_row(out, &format!("  {}", lang.language), &lang.totals)?;
+        }
+
+        write_text_row(out, "  Total", &project.totals)?;
+    }
+
+    if projects.outside.files > 0 {
+        writeln!(out, "(not in a project)")?;
+        write_text_row(out, "  Total", &projects.outside)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the most complex files, then the most complex functions (each with the file and
+/// line it starts on).
+fn write_text_complexity<W: Write>(out: &mut W, complexity: &ComplexityReport) -> io::Result<()> {
+    writeln!(out)?;
+    if complexity.files.is_empty() {
+        return writeln!(out, "Complexity: no files in a language it can be measured for");
+    }
+
+    writeln!(out, "Most complex files:")?;
+    writeln!(
+        out,
+        "{0:>10} {1:>10} {2:>10} {3:>10}  File",
+        "Complexity", "Functions", "Depth", "Code"
+    )?;
+    for file in &complexity.files {
+        let c = file.complexity.unwrap_or_default();
+        writeln!(
+            out,
+            "{0:>10} {1:>10} {2:>10} {3:>10}  {4}",
+            c.complexity, c.functions, c.max_depth, file.code, file.path
+        )?;
+    }
+
+    if complexity.functions.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "Most complex functions:")?;
+    writeln!(
+        out,
+        "{0:>10} {1:>10} {2:>10}  Function",
+        "Complexity", "Depth", "Lines"
+    )?;
+    for function in &complexity.functions {
+        writeln!(
+            out,
+            "{0:>10} {1:>10} {2:>10}  {3}:{4} {5}",
+            function.complexity,
+            function.max_depth,
+            function.lines,
+            function.path,
+            function.line,
+            function.name
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The range of line counts in a histogram bucket, like `500-999` or `5000+`.
+fn bucket_label(bucket: &Bucket) -> String {
+    match bucket.max {
+        Some(max) => format!("{}-{}", bucket.min, max),
+        None => format!("{}+", bucket.min),
+    }
+}
+
+/// Writes one line per file. If complexity was measured, each file's complexity is shown
+/// too (or `-`, for languages it can't be measured for).
+fn write_text_files<W: Write>(out: &mut W, report: &Report) -> io::Result<()> {
+    let measured = report.complexity.is_some();
+    write!(
+        out,
+        "{0:<10} {1:<10} {2:<10} {3:<10} ",
+        "Lines", "Code", "Comment", "Blank"
+    )?;
+    if measured {
+        write!(out, "{0:<10} {1:<10} {2:<10} ", "Functions", "Depth", "Complexity")?;
+    }
+    writeln!(out, "File")?;
+
+    for file in &report.files {
+        write!(
+            out,
+            "{0:<10} {1:<10} {2:<10} {3:<10} ",
+            file.lines, file.code, file.comment, file.blank
+        )?;
+        match file.complexity.filter(|_| measured) {
+            Some(c) => write!(
+                out,
+                "{0:<10} {1:<10} {2:<10} ",
+                c.functions, c.max_depth, c.complexity
+            )?,
+            None if measured => write!(out, "{0:<10} {0:<10} {0:<10} ", "-")?,
+            None => {}
+        }
+        writeln!(out, "{}", file.path)?;
+    }
+
+    Ok(())
+}
+
+/// Writes each directory indented under its parent, followed by its languages.
+fn write_text_tree<W: Write>(out: &mut W, tree: &[TreeEntry]) -> io::Result<()> {
+    writeln!(
+        out,
+        "{0:>10} {1:>10} {2:>10} {3:>10} {4:>8}  Directory",
+        "Lines", "Code", "Comment", "Blank", "Files"
+    )?;
+    for dir in tree {
+        let t = &dir.totals;
+        let languages = dir
+            .languages
+            .iter()
+            .map(|lang| format!("{} {}", lang.language, lang.totals.lines))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            out,
+            "{0:>10} {1:>10} {2:>10} {3:>10} {4:>8}  {5:6$}{7}  ({8})",
+            t.lines,
+            t.code,
+            t.comment,
+            t.blank,
+            t.files,
+            "",
+            dir.depth * 2,
+            tree_name(dir),
+            languages
+        )?;
+    }
+
+    Ok(())
+}
+
+/// The name to show for a directory in the tree: the root as given, or the directory's own
+/// name.
+fn tree_name(dir: &TreeEntry) -> String {
+    if dir.depth == 0 {
+        return dir.root.clone();
+    }
+
+    let name = Path::new(&dir.path)
+        .file_name()
+        .map_or_else(|| dir.path.clone(), |n| n.to_string_lossy().into_owned());
+    format!("{}/", name)
+}
+
+/// The path of a directory in the tree, including its root.
+fn tree_path(dir: &TreeEntry) -> String {
+    if dir.depth == 0 {
+        return dir.root.clone();
+    }
+
+    Path::new(&dir.root).join(&dir.path).display().to_string()
+}
+
+fn write_text_row<W: Write>(out: &mut W, name: &str, totals: &Totals) -> io::Result<()> {
+    writeln!(
+        out,
+        "{0:<16} {1:>8} {2:>10} {3:>10} {4:>10} {5:>10} {6:>12}",
+        name,
+        totals.files,
+        totals.lines,
+        totals.code,
+        totals.comment,
+        totals.blank,
+        totals.bytes
+    )
+}
+
+/// Writes the report as a single table, where the `kind` column says whether the row is a
+/// `file`, a `language`, a `root`, the `total`, or a skipped `binary` file. With a tree, there
+/// are also `dir` rows, each followed by one `dir_language` row per language in it. The
+/// distribution of file sizes is written as `percentile` rows (named `median`, `p90`, `p95`,
+/// `p99`, and `max` in the `path` column, with the value in `lines`) and `bucket` rows (the
+/// range of line counts in `path`, and the number of files in it in `files`). Each file in a
+/// group of copies gets a `duplicate` row, with the number of its group in `files`. A file
+/// with code in other languages embedded in it is followed by an `embedded` row for each of
+/// those languages, with the counts of its lines. Columns that don't apply to a kind are left
+/// empty.
+///
+/// If complexity was measured, there are five more columns: `function`, `line`, `functions`,
+/// `depth`, and `complexity`. File rows fill in the last three, and each of the most complex
+/// functions gets a `function` row, with its file in `path`, its length in `lines`, and its
+/// name and the line it starts on in `function` and `line`.
+///
+/// If marker comments were looked for, there are also `line` (unless it's already there),
+/// `tag`, `owner`, `count`, and `text` columns. Each tag gets a `tag` row with its `count`,
+/// each directory gets a `dir_tag` row for each tag found in it, and each marker gets a
+/// `marker` row with its file in `path`.
+///
+/// If lines were attributed to their authors, there are also `author_name` and `author`
+/// columns, with an author's email in `author`. Each author gets an
+/// `author` row with their totals, and, if asked for, an `author_language` row for each
+/// language and an `author_dir` row (with the directory in `path`) for each directory they
+/// wrote lines in. The files that couldn't be attributed are summed up in an `unattributed`
+/// row.
+///
+/// If files were attributed to their owners, there is also a `code_owner` column. Each owner
+/// gets an `owner` row with the totals of their files, and the files that no one owns are
+/// summed up in an `unowned` row.
+///
+/// If files were totalled up by project, there are also `project` and `project_kind`
+/// columns. Each project gets a `project` row with its directory in `path`, its name in
+/// `project`, its kind, and its totals, followed by a `project_language` row for each
+/// language in it. The files that aren't in a project are summed up in a `no_project` row.
+///
+/// The last column is always `reason`, and each path that couldn't be read gets an `error`
+/// row with what went wrong in it.
+fn write_delimited<W: Write>(
+    out: &mut W,
+    report: &Report,
+    sep: char,
+    escape: fn(&str) -> String,
+) -> io::Result<()> {
+    let mut header = vec![
+        "kind", "path", "language", "files", "lines", "code", "comment", "blank", "bytes",
+    ];
+    if report.complexity.is_some() {
+        header.extend(["function", "line", "functions", "depth", "complexity"]);
+    }
+    if report.markers.is_some() {
+        if report.complexity.is_none() {
+            header.push("line");
+        }
+        header.extend(["tag", "owner", "count", "text"]);
+    }
+    if report.authors.is_some() {
+        header.extend(["author_name", "author"]);
+    }
+    if report.owners.is_some() {
+        header.push("code_owner");
+    }
+    if report.projects.is_some() {
+        header.extend(["project", "project_kind"]);
+    }
+    header.push("reason");
+    writeln!(out, "{}", header.join(&sep.to_string()))?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| *h == name)
+            .expect("column is in the header")
+    };
+
+    // Rows can leave off the columns at the end that don't apply to them.
+    let width = header.len();
+    let mut write_row = |fields: &[String]| -> io::Result<()> {
+        let mut row = fields.iter().map(|f| escape(f)).collect::<Vec<_>>();
+        row.resize(width, String::new());
+        writeln!(out, "{}", row.join(&sep.to_string()))
+    };
+
+    for file in &report.files {
+        let mut row = vec![
+            "file".to_string(),
+            file.path.clone(),
+            file.language.clone(),
+            "1".to_string(),
+            file.lines.to_string(),
+            file.code.to_string(),
+            file.comment.to_string(),
+            file.blank.to_string(),
+            file.bytes.to_string(),
+        ];
+        if let Some(c) = file.complexity.filter(|_| report.complexity.is_some()) {
+            row.extend(complexity_fields(&c));
+        }
+        write_row(&row)?;
+
+        for embedded in &file.embedded {
+            write_row(&[
+                "embedded".to_string(),
+                file.path.clone(),
+                embedded.language.clone(),
+                String::new(),
+                embedded.lines.to_string(),
+                embedded.code.to_string(),
+                embedded.comment.to_string(),
+                embedded.blank.to_string(),
+            ])?;
+        }
+    }
+
+    let totals_row = |kind: &str, path: &str, language: &str, t: &Totals| {
+        [
+            kind.to_string(),
+            path.to_string(),
+            language.to_string(),
+            t.files.to_string(),
+            t.lines.to_string(),
+            t.code.to_string(),
+            t.comment.to_string(),
+            t.blank.to_string(),
+            t.bytes.to_string(),
+        ]
+    };
+
+    for lang in &report.languages {
+        write_row(&totals_row("language", "", &lang.language, &lang.totals))?;
+    }
+
+    for root in &report.roots {
+        write_row(&totals_row("root", &root.root, "", &root.totals))?;
+    }
+
+    write_row(&totals_row("total", "", "", &report.total))?;
+
+    let dist = &report.distribution;
+    let percentiles = [
+        ("median", dist.median),
+        ("p90", dist.p90),
+        ("p95", dist.p95),
+        ("p99", dist.p99),
+        ("max", dist.max),
+    ];
+    for (name, lines) in percentiles.iter() {
+        let mut row: [String; 9] = Default::default();
+        row[0] = "percentile".to_string();
+        row[1] = name.to_string();
+        row[4] = lines.to_string();
+        write_row(&row)?;
+    }
+
+    for bucket in &dist.histogram {
+        let mut row: [String; 9] = Default::default();
+        row[0] = "bucket".to_string();
+        row[1] = bucket_label(bucket);
+        row[3] = bucket.files.to_string();
+        write_row(&row)?;
+    }
+
+    for dir in report.tree.iter().flatten() {
+        let path = tree_path(dir);
+        write_row(&totals_row("dir", &path, "", &dir.totals))?;
+        for lang in &dir.languages {
+            write_row(&totals_row("dir_language", &path, &lang.language, &lang.totals))?;
+        }
+    }
+
+    let groups = report.duplicates.iter().flat_map(|dups| &dups.groups);
+    for (i, group) in groups.enumerate() {
+        for file in &group.files {
+            write_row(&[
+                "duplicate".to_string(),
+                file.path.clone(),
+                file.language.clone(),
+                (i + 1).to_string(),
+                file.lines.to_string(),
+                file.code.to_string(),
+                file.comment.to_string(),
+                file.blank.to_string(),
+                file.bytes.to_string(),
+            ])?;
+        }
+    }
+
+    let functions = report.complexity.iter().flat_map(|c| &c.functions);
+    for function in functions {
+        write_row(&[
+            "function".to_string(),
+            function.path.clone(),
+            function.language.clone(),
+            String::new(),
+            function.lines.to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            function.name.clone(),
+            function.line.to_string(),
+            String::new(),
+            function.max_depth.to_string(),
+            function.complexity.to_string(),
+        ])?;
+    }
+
+    if let Some(markers) = &report.markers {
+        let (line, tag, owner, count, text) = (
+            column("line"),
+            column("tag"),
+            column("owner"),
+            column("count"),
+            column("text"),
+        );
+        for t in &markers.tags {
+            let mut row = vec![String::new(); width];
+            row[0] = "tag".to_string();
+            row[tag] = t.tag.clone();
+            row[count] = t.count.to_string();
+            write_row(&row)?;
+        }
+
+        for dir in &markers.directories {
+            let mut row = vec![String::new(); width];
+            row[0] = "dir_tag".to_string();
+            row[1] = dir.path.clone();
+            row[tag] = dir.tag.clone();
+            row[count] = dir.count.to_string();
+            write_row(&row)?;
+        }
+
+        for marker in &markers.markers {
+            let mut row = vec![String::new(); width];
+            row[0] = "marker".to_string();
+            row[1] = marker.path.clone();
+            row[line] = marker.line.to_string();
+            row[tag] = marker.tag.clone();
+            row[owner] = marker.owner.clone().unwrap_or_default();
+            row[text] = marker.text.clone();
+            write_row(&row)?;
+        }
+    }
+
+    if let Some(authors) = &report.authors {
+        let (name, author) = (column("author_name"), column("author"));
+        let totals_row = |kind: &str, t: &AuthorTotals| {
+            let mut row = vec![String::new(); width];
+            row[0] = kind.to_string();
+            row[3] = t.files.to_string();
+            row[4] = t.lines.to_string();
+            row[5] = t.code.to_string();
+            row[6] = t.comment.to_string();
+            row[7] = t.blank.to_string();
+            row
+        };
+        for a in &authors.authors {
+            let mut row = totals_row("author", &a.totals);
+            row[name] = a.name.clone();
+            row[author] = a.email.clone();
+            write_row(&row)?;
+        }
+
+        for lang in authors.languages.iter().flatten() {
+            let mut row = totals_row("author_language", &lang.totals);
+            row[2] = lang.language.clone();
+            row[author] = lang.email.clone();
+            write_row(&row)?;
+        }
+
+        for dir in authors.directories.iter().flatten() {
+            let mut row = totals_row("author_dir", &dir.totals);
+            row[1] = dir.path.clone();
+            row[author] = dir.email.clone();
+            write_row(&row)?;
+        }
+
+        write_row(&totals_row("unattributed", &authors.unattributed))?;
+    }
+
+    if let Some(owners) = &report.owners {
+        let owner = column("code_owner");
+        let totals_row = |kind: &str, t: &Totals| {
+            let mut row = vec![String::new(); width];
+            row[0] = kind.to_string();
+            row[3] = t.files.to_string();
+            row[4] = t.lines.to_string();
+            row[5] = t.code.to_string();
+            row[6] = t.comment.to_string();
+            row[7] = t.blank.to_string();
+            row[8] = t.bytes.to_string();
+            row
+        };
+        for o in &owners.owners {
+            let mut row = totals_row("owner", &o.totals);
+            row[owner] = o.owner.clone();
+            write_row(&row)?;
+        }
+
+        write_row(&totals_row("unowned", &owners.unowned))?;
+    }
+
+    if let Some(projects) = &report.projects {
+        let (name, kind) = (column("project"), column("project_kind"));
+        let totals_row = |kind: &str, path: &str, language: &str, t: &Totals| {
+            let mut row = vec![String::new(); width];
+            row[0] = kind.to_string();
+            row[1] = path.to_string();
+            row[2] = language.to_string();
+            row[3] = t.files.to_string();
+            row[4] = t.lines.to_string();
+            row[5] = t.code.to_string();
+            row[6] = t.comment.to_string();
+            row[7] = t.blank.to_string();
+            row[8] = t.bytes.to_string();
+            row
+        };
+        for project in &projects.projects {
+            let mut row = totals_row("project", &project.path, "", &project.totals);
+            row[name] = project.name.clone();
+            row[kind] = project.kind.name().to_string();
+            write_row(&row)?;
+            for lang in &project.languages {
+                let (path, language) = (&project.path, &lang.language);
+                write_row(&totals_row("project_language", path, language, &lang.totals))?;
+            }
+        }
+
+        write_row(&totals_row("no_project", "", "", &projects.outside))?;
+    }
+
+    for file in &report.binary {
+        write_row(&[
+            "binary".to_string(),
+            file.path.clone(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            file.bytes.to_string(),
+        ])?;
+    }
+
+    for error in &report.errors {
+        let mut row = vec![String::new(); width];
+        row[0] = "error".to_string();
+        row[1] = error.path.clone();
+        row[column("reason")] = error.reason.clone();
+        write_row(&row)?;
+    }
+
+    Ok(())
+}
+
+/// The complexity columns of a file row: no `function` or `line`, then `functions`, `depth`,
+/// and `complexity`.
+fn complexity_fields(c: &ComplexityEntry) -> [String; 5] {
+    [
+        String::new(),
+        String::new(),
+        c.functions.to_string(),
+        c.max_depth.to_string(),
+        c.complexity.to_string(),
+    ]
+}
+
+/// Quotes a CSV field if it contains a comma, quote, or line break (RFC 4180).
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// TSV has no quoting, so tabs, line breaks, and backslashes are written as `\t`, `\n`,
+/// `\r`, and `\\`.
+fn escape_tsv(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Escapes characters that would break a Markdown table cell.
+fn escape_markdown(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn write_markdown<W: Write>(out: &mut W, report: &Report) -> io::Result<()> {
+    writeln!(out, "| Language | Files | Lines | Code | Comment | Blank | Bytes |")?;
+    writeln!(out, "|---|---:|---:|---:|---:|---:|---:|")?;
+    for lang in &report.languages {
+        let t = &lang.totals;
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} | {} | {} |",
+            escape_markdown(&lang.language),
+            t.files,
+            t.lines,
+            t.code,
+            t.comment,
+            t.blank,
+            t.bytes
+        )?;
+    }
+
+    let t = &report.total;
+    writeln!(
+        out,
+        "| **Total** | **{}** | **{}** | **{}** | **{}** | **{}** | **{}** |",
+        t.files, t.lines, t.code, t.comment, t.blank, t.bytes
+    )?;
+
+    let dist = &report.distribution;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Lines per file: median {}, p90 {}, p95 {}, p99 {}, max {}",
+        dist.median, dist.p90, dist.p95, dist.p99, dist.max
+    )?;
+    writeln!(out)?;
+    writeln!(out, "| Lines per file | Files |")?;
+    writeln!(out, "|---|---:|")?;
+    for bucket in &dist.histogram {
+        writeln!(out, "| {} | {} |", bucket_label(bucket), bucket.files)?;
+    }
+
+    if report.roots.len() > 1 {
+        writeln!(out)?;
+        writeln!(out, "| Root | Files | Lines | Code | Comment | Blank | Bytes |")?;
+        writeln!(out, "|---|---:|---:|---:|---:|---:|---:|")?;
+        for root in &report.roots {
+            let t = &root.totals;
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} | {} |",
+                escape_markdown(&root.root),
+                t.files,
+                t.lines,
+                t.code,
+                t.comment,
+                t.blank,
+                t.bytes
+            )?;
+        }
+    }
+
+    writeln!(out)?;
+    if let Some(tree) = &report.tree {
+        writeln!(
+            out,
+            "| Directory | Files | Lines | Code | Comment | Blank | Bytes | Languages |"
+        )?;
+        writeln!(out, "|---|---:|---:|---:|---:|---:|---:|---|")?;
+        for dir in tree {
+            let t = &dir.totals;
+            let languages = dir
+                .languages
+                .iter()
+                .map(|lang| format!("{} {}", escape_markdown(&lang.language), lang.totals.lines))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} | {} | {} |",
+                escape_markdown(&tree_path(dir)),
+                t.files,
+                t.lines,
+                t.code,
+                t.comment,
+                t.blank,
+                t.bytes,
+                languages
+            )?;
+        }
+    } else {
+        write_markdown_files(out, report)?;
+    }
+
+    if let Some(complexity) = &report.complexity {
+        write_markdown_complexity(out, complexity)?;
+    }
+
+    if let Some(markers) = &report.markers {
+        write_markdown_markers(out, markers)?;
+    }
+
+    if let Some(authors) = &report.authors {
+        write_markdown_authors(out, authors)?;
+    }
+
+    if let Some(owners) = &report.owners {
+        write_markdown_owners(out, owners)?;
+    }
+
+    if let Some(projects) = &report.projects {
+        write_markdown_projects(out, projects)?;
+    }
+
+    if let Some(dups) = &report.duplicates {
+        writeln!(out)?;
+        writeln!(out, "{}", duplicates_summary(dups))?;
+        if !dups.groups.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "| Group | File | Lines | Bytes |")?;
+            writeln!(out, "|---:|---|---:|---:|")?;
+            for (i, group) in dups.groups.iter().enumerate() {
+                for file in &group.files {
+                    writeln!(
+                        out,
+                        "| {} | {} | {} | {} |",
+                        i + 1,
+                        escape_markdown(&file.path),
+                        file.lines,
+                        file.bytes
+                    )?;
+                }
+            }
+        }
+    }
+
+    if !report.binary.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "Skipped {} binary file(s):", report.binary.len())?;
+        for file in &report.binary {
+            writeln!(out, "- {}", escape_markdown(&file.path))?;
+        }
+    }
+
+    if !report.errors.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "Couldn't read {} path(s):", report.errors.len())?;
+        for error in &report.errors {
+            writeln!(
+                out,
+                "- {}: {}",
+                escape_markdown(&error.path),
+                escape_markdown(&error.reason)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_markdown_files<W: Write>(out: &mut W, report: &Report) -> io::Result<()> {
+    let measured = report.complexity.is_some();
+    if measured {
+        writeln!(
+            out,
+            "| File | Language | Lines | Code | Comment | Blank | Bytes | Functions | Depth | Complexity |"
+        )?;
+        writeln!(out, "|---|---|---:|---:|---:|---:|---:|---:|---:|---:|")?;
+    } else {
+        writeln!(out, "| File | Language | Lines | Code | Comment | Blank | Bytes |")?;
+        writeln!(out, "|---|---|---:|---:|---:|---:|---:|")?;
+    }
+
+    for file in &report.files {
+        write!(
+            out,
+            "| {} | {} | {} | {} | {} | {} | {} |",
+            escape_markdown(&file.path),
+            escape_markdown(&file.language),
+            file.lines,
+            file.code,
+            file.comment,
+            file.blank,
+            file.bytes
+        )?;
+        match file.complexity.filter(|_| measured) {
+            Some(c) => write!(out, " {} | {} | {} |", c.functions, c.max_depth, c.complexity)?,
+            None if measured => write!(out, " - | - | - |")?,
+            None => {}
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+fn write_markdown_markers<W: Write>(out: &mut W, markers: &MarkersReport) -> io::Result<()> {
+    writeln!(out)?;
+    writeln!(out, "| Marker | Count |")?;
+    writeln!(out, "|---|---:|")?;
+    for t in &markers.tags {
+        writeln!(out, "| {} | {} |", escape_markdown(&t.tag), t.count)?;
+    }
+
+    if markers.markers.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(out, "| Directory | Marker | Count |")?;
+    writeln!(out, "|---|---|---:|")?;
+    for dir in &markers.directories {
+        writeln!(
+            out,
+            "| {} | {} | {} |",
+            escape_markdown(&dir.path),
+            escape_markdown(&dir.tag),
+            dir.count
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "| File | Line | Marker | Owner | Text |")?;
+    writeln!(out, "|---|---:|---|---|---|")?;
+    for marker in &markers.markers {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            escape_markdown(&marker.path),
+            marker.line,
+            escape_markdown(&marker.tag),
+            escape_markdown(marker.owner.as_deref().unwrap_or_default()),
+            escape_markdown(&marker.text)
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_markdown_authors<W: Write>(out: &mut W, authors: &AuthorsReport) -> io::Result<()> {
+    let row = |t: &AuthorTotals| {
+        format!(
+            "{} | {} | {} | {} | {} |",
+            t.files, t.lines, t.code, t.comment, t.blank
+        )
+    };
+
+    writeln!(out)?;
+    writeln!(out, "| Author | Email | Files | Lines | Code | Comment | Blank |")?;
+    writeln!(out, "|---|---|---:|---:|---:|---:|---:|")?;
+    for author in &authors.authors {
+        writeln!(
+            out,
+            "| {} | {} | {}",
+            escape_markdown(&author.name),
+            escape_markdown(&author.email),
+            row(&author.totals)
+        )?;
+    }
+
+    if authors.unattributed.files > 0 {
+        writeln!(out, "| *Not in git* | | {}", row(&authors.unattributed))?;
+    }
+
+    if let Some(languages) = &authors.languages {
+        writeln!(out)?;
+        writeln!(out, "| Email | Language | Files | Lines | Code | Comment | Blank |")?;
+        writeln!(out, "|---|---|---:|---:|---:|---:|---:|")?;
+        for lang in languages {
+            writeln!(
+                out,
+                "| {} | {} | {}",
+                escape_markdown(&lang.email),
+                escape_markdown(&lang.language),
+                row(&lang.totals)
+            )?;
+        }
+    }
+
+    if let Some(directories) = &authors.directories {
+        writeln!(out)?;
+        writeln!(out, "| Email | Directory | Files | Lines | Code | Comment | Blank |")?;
+        writeln!(out, "|---|---|---:|---:|---:|---:|---:|")?;
+        for dir in directories {
+            writeln!(
+                out,
+                "| {} | {} | {}",
+                escape_markdown(&dir.email),
+                escape_markdown(&dir.path),
+                row(&dir.totals)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_markdown_owners<W: Write>(out: &mut W, owners: &OwnersReport) -> io::Result<()> {
+    let row = |t: &Totals| {
+        format!(
+            "{} | {} | {} | {} | {} |",
+            t.files, t.lines, t.code, t.comment, t.blank
+        )
+    };
+
+    writeln!(out)?;
+    writeln!(out, "| Owner | Files | Lines | Code | Comment | Blank |")?;
+    writeln!(out, "|---|---:|---:|---:|---:|---:|")?;
+    for owner in &owners.owners {
+        writeln!(out, "| {} | {}", escape_markdown(&owner.owner), row(&owner.totals))?;
+    }
+
+    if owners.unowned.files > 0 {
+        writeln!(out, "| *No owner* | {}", row(&owners.unowned))?;
+    }
+
+    Ok(())
+}
+
+fn write_markdown_projects<W: Write>(out: &mut W, projects: &ProjectsReport) -> io::Result<()> {
+    let row = |t: &Totals| {
+        format!(
+            "{} | {} | {} | {} | {} | {} |",
+            t.files, t.lines, t.code, t.comment, t.blank, t.bytes
+        )
+    };
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "| Project | Kind | Directory | Files | Lines | Code | Comment | Blank | Bytes |"
+    )?;
+    writeln!(out, "|---|---|---|---:|---:|---:|---:|---:|---:|")?;
+    for project in &projects.projects {
+        writeln!(
+            out,
+            "| {} | {} | {} | {}",
+            escape_markdown(&project.name),
+            project.kind.name(),
+            escape_markdown(&project.path),
+            row(&project.totals)
+        )?;
+    }
+
+    if projects.outside.files > 0 {
+        writeln!(out, "| *Not in a project* | | | {}", row(&projects.outside))?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "| Project | Language | Files | Lines | Code | Comment | Blank | Bytes |")?;
+    writeln!(out, "|---|---|---:|---:|---:|---:|---:|---:|")?;
+    for project in &projects.projects {
+        for lang in &project.languages {
+            writeln!(
+                out,
+                "| {} | {} | {}",
+                escape_markdown(&project.name),
+                escape_markdown(&lang.language),
+                row(&lang.totals)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_markdown_complexity<W: Write>(
+    out: &mut W,
+    complexity: &ComplexityReport,
+) -> io::Result<()> {
+    writeln!(out)?;
+    if complexity.files.is_empty() {
+        return writeln!(out, "Complexity: no files in a language it can be measured for");
+    }
+
+    writeln!(out, "| Most complex files | Complexity | Functions | Depth | Code |")?;
+    writeln!(out, "|---|---:|---:|---:|---:|")?;
+    for file in &complexity.files {
+        let c = file.complexity.unwrap_or_default();
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            escape_markdown(&file.path),
+            c.complexity,
+            c.functions,
+            c.max_depth,
+            file.code
+        )?;
+    }
+
+    if complexity.functions.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "| Most complex functions | File | Line | Complexity | Depth | Lines |"
+    )?;
+    writeln!(out, "|---|---|---:|---:|---:|---:|")?;
+    for function in &complexity.functions {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} | {} |",
+            escape_markdown(&function.name),
+            escape_markdown(&function.path),
+            function.line,
+            function.complexity,
+            function.max_depth,
+            function.lines
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Formats a change with its sign, like `+12`, `-3`, or `0`.
+fn signed(n: i64) -> String {
+    if n > 0 {
+        format!("+{}", n)
+    } else {
+        n.to_string()
+    }
+}
+
+/// The name to show for a directory in a diff, including its root.
+fn change_path(dir: &DirectoryChange) -> String {
+    if dir.depth == 0 {
+        return dir.root.clone();
+    }
+
+    Path::new(&dir.root).join(&dir.path).display().to_string()
+}
+
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Writes the changes between two snapshots in the given format.
+///
+/// # Parameters
+/// - `out`: Where to write the changes.
+/// - `diff`: The changes.
+/// - `format`: The format to write them in.
+///
+/// # Returns
+/// Any error from writing to `out`.
+pub fn write_diff<W: Write>(out: &mut W, diff: &Diff, format: Format) -> io::Result<()> {
+    match format {
+        Format::Text => write_text_diff(out, diff),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, diff)?;
+            writeln!(out)
+        }
+        Format::Csv => write_delimited_diff(out, diff, ',', escape_csv),
+        Format::Tsv => write_delimited_diff(out, diff, '\t', escape_tsv),
+        Format::Markdown => write_markdown_diff(out, diff),
+    }
+}
+
+fn write_text_diff<W: Write>(out: &mut W, diff: &Diff) -> io::Result<()> {
+    writeln!(
+        out,
+        "Comparing {} ({}) to {} ({})",
+        diff.old.label,
+        timestamp(&diff.old.timestamp),
+        diff.new.label,
+        timestamp(&diff.new.timestamp)
+    )?;
+    writeln!(out)?;
+    if diff.files.is_empty() {
+        writeln!(out, "No files changed.")?;
+    } else {
+        writeln!(
+            out,
+            "{0:<8} {1:>10} {2:>10} {3:>10} {4:>10}  File",
+            "Status", "Lines", "Code", "Comment", "Blank"
+        )?;
+        for file in &diff.files {
+            let d = &file.delta;
+            writeln!(
+                out,
+                "{0:<8} {1:>10} {2:>10} {3:>10} {4:>10}  {5}",
+                file.status.name(),
+                signed(d.lines),
+                signed(d.code),
+                signed(d.comment),
+                signed(d.blank),
+                file.path
+            )?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{0:<16} {1:>8} {2:>10} {3:>10} {4:>10} {5:>10} {6:>12}",
+        "Language", "Files", "Lines", "Code", "Comment", "Blank", "Bytes"
+    )?;
+    for lang in &diff.languages {
+        write_text_delta_row(out, &lang.language, &lang.delta)?;
+    }
+
+    write_text_delta_row(out, "Total", &diff.total.delta)?;
+
+    if !diff.directories.is_empty() {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{0:>8} {1:>10} {2:>10} {3:>10} {4:>10} {5:>12} Directory",
+            "Files", "Lines", "Code", "Comment", "Blank", "Bytes"
+        )?;
+        for dir in &diff.directories {
+            let d = &dir.delta;
+            writeln!(
+                out,
+                "{0:>8} {1:>10} {2:>10} {3:>10} {4:>10} {5:>12} {6}",
+                signed(d.files),
+                signed(d.lines),
+                signed(d.code),
+                signed(d.comment),
+                signed(d.blank),
+                signed(d.bytes),
+                change_path(dir)
+            )?;
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Lines: {} -> {} ({})",
+        diff.total.old.lines,
+        diff.total.new.lines,
+        signed(diff.total.delta.lines)
+    )
+}
+
+fn write_text_delta_row<W: Write>(out: &mut W, name: &str, d: &Delta) -> io::Result<()> {
+    writeln!(
+        out,
+        "{0:<16} {1:>8} {2:>10} {3:>10} {4:>10} {5:>10} {6:>12}",
+        name,
+        signed(d.files),
+        signed(d.lines),
+        signed(d.code),
+        signed(d.comment),
+        signed(d.blank),
+        signed(d.bytes)
+    )
+}
+
+/// Writes the changes as a single table, where the `kind` column says whether the row is a
+/// `file`, a `language`, a `dir`, or the `total`. Every number is a change. `status` is only
+/// filled in for files, and `language` for files and languages.
+fn write_delimited_diff<W: Write>(
+    out: &mut W,
+    diff: &Diff,
+    sep: char,
+    escape: fn(&str) -> String,
+) -> io::Result<()> {
+    let header = [
+        "kind", "status", "path", "language", "files", "lines", "code", "comment", "blank",
+        "bytes",
+    ];
+    writeln!(out, "{}", header.join(&sep.to_string()))?;
+
+    let mut write_row = |kind: &str, status: &str, path: &str, language: &str, d: &Delta| {
+        let fields = [
+            kind.to_string(),
+            status.to_string(),
+            path.to_string(),
+            language.to_string(),
+            d.files.to_string(),
+            d.lines.to_string(),
+            d.code.to_string(),
+            d.comment.to_string(),
+            d.blank.to_string(),
+            d.bytes.to_string(),
+        ];
+        let row = fields.iter().map(|f| escape(f)).collect::<Vec<_>>();
+        writeln!(out, "{}", row.join(&sep.to_string()))
+    };
+
+    for file in &diff.files {
+        write_row(
+            "file",
+            file.status.name(),
+            &file.path,
+            &file.language,
+            &file.delta,
+        )?;
+    }
+
+    for lang in &diff.languages {
+        write_row("language", "", "", &lang.language, &lang.delta)?;
+    }
+
+    for dir in &diff.directories {
+        write_row("dir", "", &change_path(dir), "", &dir.delta)?;
+    }
+
+    write_row("total", "", "", "", &diff.total.delta)
+}
+
+fn write_markdown_diff<W: Write>(out: &mut W, diff: &Diff) -> io::Result<()> {
+    writeln!(
+        out,
+        "Comparing **{}** ({}) to **{}** ({}): {} -> {} lines ({})",
+        escape_markdown(&diff.old.label),
+        timestamp(&diff.old.timestamp),
+        escape_markdown(&diff.new.label),
+        timestamp(&diff.new.timestamp),
+        diff.total.old.lines,
+        diff.total.new.lines,
+        signed(diff.total.delta.lines)
+    )?;
+
+    writeln!(out)?;
+    writeln!(out, "| Language | Files | Lines | Code | Comment | Blank | Bytes |")?;
+    writeln!(out, "|---|---:|---:|---:|---:|---:|---:|")?;
+    for lang in &diff.languages {
+        write_markdown_delta_row(out, &escape_markdown(&lang.language), &lang.delta)?;
+    }
+
+    write_markdown_delta_row(out, "**Total**", &diff.total.delta)?;
+
+    if !diff.directories.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "| Directory | Files | Lines | Code | Comment | Blank | Bytes |")?;
+        writeln!(out, "|---|---:|---:|---:|---:|---:|---:|")?;
+        for dir in &diff.directories {
+            write_markdown_delta_row(out, &escape_markdown(&change_path(dir)), &dir.delta)?;
+        }
+    }
+
+    if !diff.files.is_empty() {
+        writeln!(out)?;
+        writeln!(out, "| File | Status | Lines | Code | Comment | Blank |")?;
+        writeln!(out, "|---|---|---:|---:|---:|---:|")?;
+        for file in &diff.files {
+            let d = &file.delta;
+            writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} |",
+                escape_markdown(&file.path),
+                file.status.name(),
+                signed(d.lines),
+                signed(d.code),
+                signed(d.comment),
+                signed(d.blank)
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_markdown_delta_row<W: Write>(out: &mut W, name: &str, d: &Delta) -> io::Result<()> {
+    writeln!(
+        out,
+        "| {} | {} | {} | {} | {} | {} | {} |",
+        name,
+        signed(d.files),
+        signed(d.lines),
+        signed(d.code),
+        signed(d.comment),
+        signed(d.blank),
+        signed(d.bytes)
+    )
+}
+
+/// Writes the trend over every snapshot in the given format.
+///
+/// # Parameters
+/// - `out`: Where to write the trend.
+/// - `history`: The trend.
+/// - `format`: The format to write it in.
+///
+/// # Returns
+/// Any error from writing to `out`.
+pub fn write_history<W: Write>(out: &mut W, history: &History, format: Format) -> io::Result<()> {
+    match format {
+        Format::Text => write_text_history(out, history),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, history)?;
+            writeln!(out)
+        }
+        Format::Csv => write_delimited_history(out, history, ',', escape_csv),
+        Format::Tsv => write_delimited_history(out, history, '\t', escape_tsv),
+        Format::Markdown => write_markdown_history(out, history),
+    }
+}
+
+fn write_text_history<W: Write>(out: &mut W, history: &History) -> io::Result<()> {
+    if history.snapshots.is_empty() {
+        return writeln!(out, "No snapshots yet.");
+    }
+
+    writeln!(
+        out,
+        "{0:<20} {1:>8} {2:>10} {3:>10} {4:>10} {5:>10} {6:>12} {7:>10}  Label",
+        "Timestamp", "Files", "Lines", "Code", "Comment", "Blank", "Bytes", "Change"
+    )?;
+    for entry in &history.snapshots {
+        let t = &entry.totals;
+        writeln!(
+            out,
+            "{0:<20} {1:>8} {2:>10} {3:>10} {4:>10} {5:>10} {6:>12} {7:>10}  {8}",
+            timestamp(&entry.timestamp),
+            t.files,
+            t.lines,
+            t.code,
+            t.comment,
+            t.blank,
+            t.bytes,
+            signed(entry.change.lines),
+            entry.label
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes one row per snapshot. `change` is the change in lines since the snapshot before.
+fn write_delimited_history<W: Write>(
+    out: &mut W,
+    history: &History,
+    sep: char,
+    escape: fn(&str) -> String,
+) -> io::Result<()> {
+    let header = [
+        "timestamp",
+        "label",
+        "files",
+        "lines",
+        "code",
+        "comment",
+        "blank",
+        "bytes",
+        "change",
+    ];
+    writeln!(out, "{}", header.join(&sep.to_string()))?;
+    for entry in &history.snapshots {
+        let t = &entry.totals;
+        let fields = [
+            timestamp(&entry.timestamp),
+            entry.label.clone(),
+            t.files.to_string(),
+            t.lines.to_string(),
+            t.code.to_string(),
+            t.comment.to_string(),
+            t.blank.to_string(),
+            t.bytes.to_string(),
+            entry.change.lines.to_string(),
+        ];
+        let row = fields.iter().map(|f| escape(f)).collect::<Vec<_>>();
+        writeln!(out, "{}", row.join(&sep.to_string()))?;
+    }
+
+    Ok(())
+}
+
+fn write_markdown_history<W: Write>(out: &mut W, history: &History) -> io::Result<()> {
+    writeln!(
+        out,
+        "| Timestamp | Label | Files | Lines | Code | Comment | Blank | Bytes | Change |"
+    )?;
+    writeln!(out, "|---|---|---:|---:|---:|---:|---:|---:|---:|")?;
+    for entry in &history.snapshots {
+        let t = &entry.totals;
+        writeln!(
+            out,
+            "| {} | {} | {} | {} | {} | {} | {} | {} | {} |",
+            timestamp(&entry.timestamp),
+            escape_markdown(&entry.label),
+            t.files,
+            t.lines,
+            t.code,
+            t.comment,
+            t.blank,
+            t.bytes,
+            signed(entry.change.lines)
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(escape_csv("src/main.rs"), "src/main.rs");
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn tsv_fields_are_escaped() {
+        assert_eq!(escape_tsv("a\tb\\c\nd"), "a\\tb\\\\c\\nd");
+    }
+
+    #[test]
+    fn signed_numbers() {
+        assert_eq!(signed(3), "+3");
+        assert_eq!(signed(0), "0");
+        assert_eq!(signed(-3), "-3");
+    }
+}
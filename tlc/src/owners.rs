@@ -0,0 +1,223 @@
+use crate::walk::FileStats;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where a `CODEOWNERS` file can be, relative to the top of the repository, in the order
+/// that GitHub looks for it. Only the first one found is used.
+pub const LOCATIONS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// The rules in a `CODEOWNERS` file.
+///
+/// Each rule is a pattern followed by the owners of the paths it matches, and the last rule
+/// that matches a path wins. Patterns work as they do in `.gitignore`: a pattern with a `/`
+/// at the start or in the middle is matched against the whole path, and any other pattern
+/// matches a name anywhere in the tree. `*` doesn't match across `/` but `**` does, and a
+/// pattern that matches a directory matches everything under it, except that `dir/*` only
+/// matches the files directly in `dir`.
+pub struct Codeowners {
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    /// The pattern, and the pattern for everything under the directories it matches.
+    globs: GlobSet,
+    /// Who owns the paths that match, which is no one for a rule without any owners.
+    owners: Vec<String>,
+}
+
+impl Codeowners {
+    /// Parses a `CODEOWNERS` file. As on GitHub, lines with invalid patterns are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut rules = vec![];
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            let pattern = match fields.next() {
+                Some(pattern) if !pattern.starts_with('#') => pattern,
+                _ => continue,
+            };
+            let owners = fields
+                .take_while(|field| !field.starts_with('#'))
+                .map(|owner| owner.to_string())
+                .collect();
+            if let Some(globs) = compile(pattern) {
+                rules.push(Rule { globs, owners });
+            }
+        }
+
+        Codeowners { rules }
+    }
+
+    /// Finds who owns a path.
+    ///
+    /// # Parameters
+    /// - `rel_path`: The path, relative to the top of the repository.
+    ///
+    /// # Returns
+    /// The owners given by the last rule that matches the path, or nothing if no rule
+    /// matches it or that rule doesn't name any owners.
+    pub fn owners_of(&self, rel_path: &Path) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.globs.is_match(rel_path))
+            .map_or(&[], |rule| &rule.owners)
+    }
+}
+
+/// Compiles a pattern into the globs for what it matches, or `None` if it's invalid.
+fn compile(pattern: &str) -> Option<GlobSet> {
+    // Patterns that start with `#` have to be escaped to not be comments.
+    let pattern = pattern.strip_prefix('\\').unwrap_or(pattern);
+    let anchored = pattern.trim_end_matches('/').contains('/');
+    let pattern = pattern.trim_start_matches('/').trim_end_matches('/');
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let pattern = if anchored {
+        pattern.to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+    let mut builder = GlobSetBuilder::new();
+    let mut add = |glob: &str| {
+        let glob = GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()
+            .ok()?;
+        builder.add(glob);
+        Some(())
+    };
+    add(&pattern)?;
+    if !pattern.ends_with("/*") {
+        add(&format!("{}/**", pattern))?;
+    }
+
+    builder.build().ok()
+}
+
+/// Finds the owners of files, keeping each `CODEOWNERS` file once it's been read.
+#[derive(Default)]
+pub struct OwnerFinder {
+    /// The `CODEOWNERS` file that applies to each directory, as an index into `files`, or
+    /// `None` if there isn't one.
+    dirs: HashMap<PathBuf, Option<usize>>,
+    /// Each `CODEOWNERS` file that has been read, with the directory its rules are relative
+    /// to.
+    files: Vec<(PathBuf, Codeowners)>,
+}
+
+impl OwnerFinder {
+    /// Finds who owns a file, going by the `CODEOWNERS` file of the repository it's in: the
+    /// first one of the `LOCATIONS` under the top of the repository, which is the closest
+    /// directory above the file that has a `.git` in it.
+    ///
+    /// # Returns
+    /// The file's owners, which are none if it doesn't have any, or there's no `CODEOWNERS`
+    /// file for it (such as for a file inside an archive).
+    pub fn owners_of(&mut self, path: &Path) -> &[String] {
+        let path = match fs::canonicalize(path) {
+            Ok(path) => path,
+            Err(_) => return &[],
+        };
+        let index = match path.parent().and_then(|dir| self.lookup(dir)) {
+            Some(index) => index,
+            None => return &[],
+        };
+
+        let (top, codeowners) = &self.files[index];
+        match path.strip_prefix(top) {
+            Ok(rel_path) => codeowners.owners_of(rel_path),
+            Err(_) => &[],
+        }
+    }
+
+    /// Finds the `CODEOWNERS` file that applies to a directory, reading it if it hasn't been
+    /// read yet.
+    fn lookup(&mut self, dir: &Path) -> Option<usize> {
+        if let Some(index) = self.dirs.get(dir) {
+            return *index;
+        }
+
+        // Only the top of the repository has a `CODEOWNERS` file, even if there are ones
+        // further down (such as in `.github/` itself, or in a `docs/` of a package).
+        let index = if dir.join(".git").exists() {
+            let found = LOCATIONS
+                .iter()
+                .find_map(|location| fs::read_to_string(dir.join(location)).ok());
+            found.map(|text| {
+                self.files
+                    .push((dir.to_path_buf(), Codeowners::parse(&text)));
+                self.files.len() - 1
+            })
+        } else {
+            dir.parent().and_then(|parent| self.lookup(parent))
+        };
+        self.dirs.insert(dir.to_path_buf(), index);
+        index
+    }
+}
+
+/// Finds the owners of many files.
+///
+/// # Returns
+/// The owners of each file, in the same order as `files`.
+pub fn find_owners(files: &[&FileStats]) -> Vec<Vec<String>> {
+    let mut finder = OwnerFinder::default();
+    files
+        .iter()
+        .map(|file| finder.owners_of(&file.path).to_vec())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owners(codeowners: &Codeowners, path: &str) -> Vec<String> {
+        codeowners.owners_of(Path::new(path)).to_vec()
+    }
+
+    #[test]
+    fn the_last_matching_rule_wins() {
+        let codeowners = Codeowners::parse(
+            "# Everything, unless a later rule says otherwise.\n\
+             *       @org/core\n\
+             \n\
+             *.py    @org/data @dana  # Scripts too.\n\
+             /build/logs/\n",
+        );
+        assert_eq!(owners(&codeowners, "src/lib.rs"), ["@org/core"]);
+        assert_eq!(owners(&codeowners, "tools/gen.py"), ["@org/data", "@dana"]);
+        // A rule without owners takes them away.
+        assert!(owners(&codeowners, "build/logs/gen.py").is_empty());
+        assert_eq!(owners(&codeowners, "build/gen.py"), ["@org/data", "@dana"]);
+    }
+
+    #[test]
+    fn patterns_are_anchored_like_gitignore() {
+        let codeowners = Codeowners::parse(
+            "apps/        @apps\n\
+             /docs/       @docs\n\
+             src/*        @src\n\
+             **/logs      @logs\n\
+             \\#notes     @notes\n",
+        );
+        assert_eq!(owners(&codeowners, "web/apps/main.js"), ["@apps"]);
+        assert_eq!(owners(&codeowners, "docs/guide.md"), ["@docs"]);
+        assert!(owners(&codeowners, "web/docs/guide.md").is_empty());
+        assert_eq!(owners(&codeowners, "src/lib.rs"), ["@src"]);
+        assert!(owners(&codeowners, "src/bin/main.rs").is_empty());
+        assert!(owners(&codeowners, "lib/src/lib.rs").is_empty());
+        assert_eq!(owners(&codeowners, "a/b/logs/today.log"), ["@logs"]);
+        assert_eq!(owners(&codeowners, "#notes"), ["@notes"]);
+    }
+
+    #[test]
+    fn invalid_patterns_are_skipped() {
+        let codeowners = Codeowners::parse("*.rs @rust\nsrc/[ @broken\n");
+        assert_eq!(owners(&codeowners, "src/lib.rs"), ["@rust"]);
+    }
+}
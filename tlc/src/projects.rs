@@ -0,0 +1,249 @@
+use crate::walk::FileStats;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// The kinds of project that can be told apart by their manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Cargo,
+    Npm,
+    Python,
+    Go,
+    Maven,
+}
+
+impl Kind {
+    /// The name that the kind is written out as.
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Cargo => "cargo",
+            Kind::Npm => "npm",
+            Kind::Python => "python",
+            Kind::Go => "go",
+            Kind::Maven => "maven",
+        }
+    }
+}
+
+/// The manifest file of each kind of project. A directory with more than one of them is the
+/// kind of the first one.
+pub const MANIFESTS: [(&str, Kind); 5] = [
+    ("Cargo.toml", Kind::Cargo),
+    ("package.json", Kind::Npm),
+    ("pyproject.toml", Kind::Python),
+    ("go.mod", Kind::Go),
+    ("pom.xml", Kind::Maven),
+];
+
+/// A directory with a project manifest in it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Project {
+    /// The name that the manifest gives the project, or the directory's name if it doesn't
+    /// give one (such as a Cargo workspace without a package of its own).
+    pub name: String,
+    pub kind: Kind,
+    /// The directory, as reached from the file that it was found for.
+    pub path: PathBuf,
+}
+
+impl Project {
+    /// Reads the project manifest in a directory.
+    ///
+    /// # Returns
+    /// The project, or `None` if there isn't a manifest in the directory.
+    pub fn read(dir: &Path) -> Option<Self> {
+        let (text, kind) = MANIFESTS.iter().find_map(|(manifest, kind)| {
+            fs::read_to_string(dir.join(manifest))
+                .ok()
+                .map(|text| (text, *kind))
+        })?;
+        let name = manifest_name(&text, kind).unwrap_or_else(|| {
+            fs::canonicalize(dir)
+                .ok()
+                .and_then(|dir| {
+                    dir.file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                })
+                .unwrap_or_else(|| dir.display().to_string())
+        });
+        Some(Project {
+            name,
+            kind,
+            path: dir.to_path_buf(),
+        })
+    }
+}
+
+/// Finds the name that a manifest gives its project.
+fn manifest_name(text: &str, kind: Kind) -> Option<String> {
+    let name = match kind {
+        Kind::Cargo => {
+            let manifest = text.parse::<toml::Table>().ok()?;
+            manifest.get("package")?.get("name")?.as_str()?.to_string()
+        }
+        Kind::Npm => {
+            let manifest = serde_json::from_str::<serde_json::Value>(text).ok()?;
+            manifest.get("name")?.as_str()?.to_string()
+        }
+        Kind::Python => {
+            let manifest = text.parse::<toml::Table>().ok()?;
+            let poetry = || manifest.get("tool")?.get("poetry")?.get("name");
+            manifest
+                .get("project")
+                .and_then(|project| project.get("name"))
+                .or_else(poetry)?
+                .as_str()?
+                .to_string()
+        }
+        Kind::Go => text
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("module "))?
+            .trim()
+            .trim_matches('"')
+            .to_string(),
+        Kind::Maven => maven_artifact_id(text)?,
+    };
+
+    Some(name).filter(|name| !name.is_empty())
+}
+
+/// Finds the project's own `artifactId` in a `pom.xml`, skipping the one in `<parent>`.
+fn maven_artifact_id(text: &str) -> Option<String> {
+    let mut text = text;
+    loop {
+        let artifact = text.find("<artifactId>")?;
+        match text.find("<parent>") {
+            Some(parent) if parent < artifact => {
+                let end = text[parent..].find("</parent>")?;
+                text = &text[parent + end..];
+            }
+            _ => {
+                let start = artifact + "<artifactId>".len();
+                let end = text[start..].find("</artifactId>")?;
+                return Some(text[start..start + end].trim().to_string());
+            }
+        }
+    }
+}
+
+/// Finds the projects that files are in, keeping each manifest once it's been read.
+#[derive(Default)]
+pub struct ProjectFinder {
+    /// The project that each directory is in, as an index into `projects`, or `None` if it
+    /// isn't in one.
+    dirs: HashMap<PathBuf, Option<usize>>,
+    projects: Vec<Project>,
+}
+
+impl ProjectFinder {
+    /// Finds the project that a file is in: the closest directory above it with a manifest
+    /// in it. Directories above a repository (one with a `.git` in it) aren't looked in.
+    ///
+    /// # Returns
+    /// The project, or `None` if the file isn't in one.
+    pub fn project_of(&mut self, path: &Path) -> Option<&Project> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let index = self.lookup(dir)?;
+        Some(&self.projects[index])
+    }
+
+    /// Finds the project that a directory is in, reading its manifest if it hasn't been read
+    /// yet.
+    fn lookup(&mut self, dir: &Path) -> Option<usize> {
+        if let Some(index) = self.dirs.get(dir) {
+            return *index;
+        }
+
+        let index = match Project::read(dir) {
+            Some(project) => {
+                self.projects.push(project);
+                Some(self.projects.len() - 1)
+            }
+            None if dir.join(".git").exists() => None,
+            None => parent(dir).and_then(|parent| self.lookup(&parent)),
+        };
+        self.dirs.insert(dir.to_path_buf(), index);
+        index
+    }
+}
+
+/// The directory above another one. Relative paths are followed up to the current directory
+/// as they are, and past it to wherever it really is.
+fn parent(dir: &Path) -> Option<PathBuf> {
+    match dir.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => Some(parent.to_path_buf()),
+        Some(_) if matches!(dir.components().next_back(), Some(Component::Normal(_))) => {
+            Some(PathBuf::from("."))
+        }
+        _ if dir.is_relative() => Some(fs::canonicalize(dir).ok()?.parent()?.to_path_buf()),
+        _ => None,
+    }
+}
+
+/// Finds the projects that many files are in.
+///
+/// # Returns
+/// The project that each file is in, in the same order as `files`, or `None` for the files
+/// that aren't in one.
+pub fn find_projects(files: &[&FileStats]) -> Vec<Option<Project>> {
+    let mut finder = ProjectFinder::default();
+    files
+        .iter()
+        .map(|file| finder.project_of(&file.path).cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_come_from_the_manifest() {
+        let cargo = "[workspace]\nmembers = []\n\n[package]\nname = \"tlc\"\n";
+        assert_eq!(manifest_name(cargo, Kind::Cargo), Some("tlc".to_string()));
+        assert_eq!(manifest_name("[workspace]\n", Kind::Cargo), None);
+
+        let npm = r#"{ "name": "@acme/web", "version": "1.0.0" }"#;
+        assert_eq!(manifest_name(npm, Kind::Npm), Some("@acme/web".to_string()));
+
+        let python = "[tool.poetry]\nname = \"etl\"\n";
+        assert_eq!(manifest_name(python, Kind::Python), Some("etl".to_string()));
+        let python = "[project]\nname = \"etl\"\n[tool.poetry]\nname = \"old\"\n";
+        assert_eq!(manifest_name(python, Kind::Python), Some("etl".to_string()));
+
+        let go = "// The API.\nmodule example.com/acme/api\n\ngo 1.22\n";
+        assert_eq!(
+            manifest_name(go, Kind::Go),
+            Some("example.com/acme/api".to_string())
+        );
+    }
+
+    #[test]
+    fn maven_skips_the_parent_artifact() {
+        let pom = "<project>\n\
+                   <parent>\n<artifactId>acme-parent</artifactId>\n</parent>\n\
+                   <artifactId>billing</artifactId>\n\
+                   <dependencies><dependency><artifactId>junit</artifactId></dependency>\n\
+                   </dependencies>\n\
+                   </project>\n";
+        assert_eq!(manifest_name(pom, Kind::Maven), Some("billing".to_string()));
+    }
+
+    #[test]
+    fn relative_paths_go_up_through_the_current_directory() {
+        assert_eq!(parent(Path::new("a/b")), Some(PathBuf::from("a")));
+        assert_eq!(parent(Path::new("a")), Some(PathBuf::from(".")));
+        let above = std::env::current_dir()
+            .unwrap()
+            .parent()
+            .map(Path::to_path_buf);
+        assert_eq!(parent(Path::new(".")), above);
+        assert_eq!(parent(Path::new("/")), None);
+    }
+}
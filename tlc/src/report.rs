@@ -0,0 +1,1124 @@
+use crate::blame::Authors;
+use crate::count::LineCounts;
+use crate::dedupe::{ContentHash, Group, Match};
+use crate::projects::{Kind, Project};
+use crate::walk::{Counted, FileStats};
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// The version of the machine-readable report schema. Bump this whenever a field is
+/// removed or changes meaning; adding a field doesn't need a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Everything that `tlc` found, in the shape that all of the output formats share.
+#[derive(Serialize, Deserialize)]
+pub struct Report {
+    pub version: u32,
+    pub files: Vec<FileEntry>,
+    pub languages: Vec<LanguageEntry>,
+    pub roots: Vec<RootEntry>,
+    pub total: Totals,
+    /// How file sizes (in lines) are spread out.
+    #[serde(default)]
+    pub distribution: Distribution,
+    /// Files that would have been counted, but were skipped for being binary.
+    pub binary: Vec<BinaryEntry>,
+    /// The rolled-up totals of each directory, if a tree was asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree: Option<Vec<TreeEntry>>,
+    /// The groups of files with the same contents, if they were looked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicates: Option<Duplicates>,
+    /// The most complex files and functions, if complexity was measured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub complexity: Option<ComplexityReport>,
+    /// The marker comments (such as `TODO`s), if they were looked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markers: Option<MarkersReport>,
+    /// Who wrote how much of the code, if it was asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authors: Option<AuthorsReport>,
+    /// How much of the code each `CODEOWNERS` owner owns, if it was asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owners: Option<OwnersReport>,
+    /// The totals of each project, if they were asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projects: Option<ProjectsReport>,
+    /// The paths that couldn't be read, so anything in them is missing from the counts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ErrorEntry>,
+}
+
+/// One counted file.
+#[derive(Serialize, Deserialize)]
+pub struct FileEntry {
+    pub path: String,
+    pub language: String,
+    pub lines: usize,
+    pub code: usize,
+    pub comment: usize,
+    pub blank: usize,
+    pub bytes: u64,
+    /// How complex the file's code is, if that was measured and the language has rules
+    /// for it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub complexity: Option<ComplexityEntry>,
+    /// The lines in other languages embedded in the file, such as code blocks in Markdown.
+    /// These are included in the file's own counts too.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub embedded: Vec<EmbeddedEntry>,
+}
+
+/// The lines of one language embedded in a file.
+#[derive(Serialize, Deserialize)]
+pub struct EmbeddedEntry {
+    pub language: String,
+    pub lines: usize,
+    pub code: usize,
+    pub comment: usize,
+    pub blank: usize,
+}
+
+/// The complexity of one file, without its functions.
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+pub struct ComplexityEntry {
+    pub functions: usize,
+    pub max_depth: usize,
+    pub complexity: usize,
+}
+
+impl FileEntry {
+    pub fn new(file: &FileStats) -> Self {
+        FileEntry {
+            path: file.path.display().to_string(),
+            language: file.language.name.to_string(),
+            lines: file.counts.lines(),
+            code: file.counts.code,
+            comment: file.counts.comment,
+            blank: file.counts.blank,
+            bytes: file.bytes,
+            complexity: file.complexity.as_ref().map(|c| ComplexityEntry {
+                functions: c.functions.len(),
+                max_depth: c.max_depth,
+                complexity: c.complexity,
+            }),
+            embedded: file
+                .embedded
+                .iter()
+                .map(|e| EmbeddedEntry {
+                    language: e.language.name.to_string(),
+                    lines: e.counts.lines(),
+                    code: e.counts.code,
+                    comment: e.counts.comment,
+                    blank: e.counts.blank,
+                })
+                .collect(),
+        }
+    }
+
+    /// The file's complexity, or 0 if it wasn't measured.
+    fn complexity(&self) -> usize {
+        self.complexity.map_or(0, |c| c.complexity)
+    }
+}
+
+/// A binary file that was skipped.
+#[derive(Serialize, Deserialize)]
+pub struct BinaryEntry {
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// A path that couldn't be read.
+#[derive(Serialize, Deserialize)]
+pub struct ErrorEntry {
+    pub path: String,
+    pub reason: String,
+}
+
+/// The totals for one language.
+#[derive(Serialize, Deserialize)]
+pub struct LanguageEntry {
+    pub language: String,
+    #[serde(flatten)]
+    pub totals: Totals,
+}
+
+/// The totals for one of the directories (or file lists) that was counted.
+#[derive(Serialize, Deserialize)]
+pub struct RootEntry {
+    pub root: String,
+    #[serde(flatten)]
+    pub totals: Totals,
+}
+
+/// The upper bounds (exclusive) of the histogram buckets, in lines. The last bucket has no
+/// upper bound.
+const BUCKET_BOUNDS: &[usize] = &[50, 100, 250, 500, 1000, 2000, 5000];
+
+/// Summary statistics of how many lines each file has.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Distribution {
+    pub median: usize,
+    pub p90: usize,
+    pub p95: usize,
+    pub p99: usize,
+    pub max: usize,
+    pub histogram: Vec<Bucket>,
+}
+
+/// The number of files whose line count is in `min..=max` (or at least `min`, if there's no
+/// `max`).
+#[derive(Serialize, Deserialize)]
+pub struct Bucket {
+    pub min: usize,
+    pub max: Option<usize>,
+    pub files: usize,
+}
+
+impl Distribution {
+    /// Computes the distribution of the given line counts.
+    fn new(mut lines: Vec<usize>) -> Self {
+        lines.sort_unstable();
+        let mut histogram = vec![];
+        let mut min = 0;
+        for &bound in BUCKET_BOUNDS.iter() {
+            histogram.push(Bucket {
+                min,
+                max: Some(bound - 1),
+                files: lines.iter().filter(|&&l| l >= min && l < bound).count(),
+            });
+            min = bound;
+        }
+
+        histogram.push(Bucket {
+            min,
+            max: None,
+            files: lines.iter().filter(|&&l| l >= min).count(),
+        });
+
+        Distribution {
+            median: percentile(&lines, 50),
+            p90: percentile(&lines, 90),
+            p95: percentile(&lines, 95),
+            p99: percentile(&lines, 99),
+            max: lines.last().copied().unwrap_or(0),
+            histogram,
+        }
+    }
+}
+
+/// Gets the `p`th percentile of `sorted` using the nearest-rank method: the smallest value
+/// that at least `p` percent of the values are less than or equal to.
+fn percentile(sorted: &[usize], p: usize) -> usize {
+    if sorted.is_empty() {
+        return 0;
+    }
+
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// What to sort the file listing by.
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Path,
+    Lines,
+    Code,
+    Bytes,
+    Complexity,
+}
+
+/// One directory in the tree view, with the totals of everything under it.
+#[derive(Serialize, Deserialize)]
+pub struct TreeEntry {
+    /// The root that this directory is under.
+    pub root: String,
+    /// The directory's path relative to its root; empty for the root itself.
+    pub path: String,
+    /// How deep the directory is; 0 for the root itself.
+    pub depth: usize,
+    #[serde(flatten)]
+    pub totals: Totals,
+    pub languages: Vec<LanguageEntry>,
+}
+
+/// The files that have the same contents as each other.
+#[derive(Serialize, Deserialize)]
+pub struct Duplicates {
+    /// How alike files had to be to count as copies.
+    pub mode: Match,
+    /// Whether the extra copies were left out of the file listing and every total.
+    pub deduplicated: bool,
+    /// The totals of every file but the first in each group, which is what deduplicating
+    /// leaves out.
+    pub copies: Totals,
+    pub groups: Vec<DuplicateGroup>,
+}
+
+/// Two or more files with the same contents.
+#[derive(Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub hash: ContentHash,
+    /// The files, starting with the one that is still counted when deduplicating.
+    pub files: Vec<FileEntry>,
+}
+
+impl Duplicates {
+    /// Summarizes groups of copies.
+    ///
+    /// # Parameters
+    /// - `groups`: The groups, from `dedupe::find_groups`.
+    /// - `mode`: How alike files had to be to count as copies.
+    /// - `deduplicated`: Whether the extra copies are left out of the report.
+    ///
+    /// # Returns
+    /// The summary. Groups are ordered from the most extra lines to the fewest.
+    pub fn new(groups: &[Group], mode: Match, deduplicated: bool) -> Self {
+        let mut copies = Totals::default();
+        for file in groups.iter().flat_map(|group| &group.files[1..]) {
+            copies.add(&file.counts, file.bytes);
+        }
+
+        let mut groups = groups
+            .iter()
+            .map(|group| DuplicateGroup {
+                hash: group.hash,
+                files: group
+                    .files
+                    .iter()
+                    .map(|file| FileEntry::new(file))
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        groups
+            .sort_by_key(|group| Reverse(group.files[1..].iter().map(|f| f.lines).sum::<usize>()));
+
+        Duplicates {
+            mode,
+            deduplicated,
+            copies,
+            groups,
+        }
+    }
+}
+
+/// The most complex files and functions.
+#[derive(Serialize, Deserialize)]
+pub struct ComplexityReport {
+    /// The files with the highest complexity, from most to least complex.
+    pub files: Vec<FileEntry>,
+    /// The functions with the highest complexity, from most to least complex.
+    pub functions: Vec<FunctionEntry>,
+}
+
+/// One function, and how complex it is.
+#[derive(Serialize, Deserialize)]
+pub struct FunctionEntry {
+    /// The path of the file that the function is in.
+    pub path: String,
+    pub language: String,
+    pub name: String,
+    /// The line that the function starts on, counting from 1.
+    pub line: usize,
+    pub lines: usize,
+    pub max_depth: usize,
+    pub complexity: usize,
+}
+
+impl ComplexityReport {
+    /// Picks out the most complex files and functions.
+    ///
+    /// # Parameters
+    /// - `roots`: Everything that was counted under each root, with complexity measured.
+    /// - `top`: How many files, and how many functions, to list.
+    ///
+    /// # Returns
+    /// The lists. Ties are broken by the number of code lines (for files) or lines (for
+    /// functions), and then keep the order they were counted in.
+    pub fn new(roots: &[(String, Counted)], top: usize) -> Self {
+        let measured = roots
+            .iter()
+            .flat_map(|(_, counted)| &counted.files)
+            .filter_map(|file| file.complexity.as_ref().map(|c| (file, c)));
+
+        let mut files = measured
+            .clone()
+            .map(|(file, _)| FileEntry::new(file))
+            .collect::<Vec<_>>();
+        files.sort_by_key(|file| Reverse((file.complexity(), file.code)));
+        files.truncate(top);
+
+        let mut functions = measured
+            .flat_map(|(file, c)| c.functions.iter().map(move |function| (file, function)))
+            .map(|(file, function)| FunctionEntry {
+                path: file.path.display().to_string(),
+                language: file.language.name.to_string(),
+                name: function.name.clone(),
+                line: function.line,
+                lines: function.lines,
+                max_depth: function.max_depth,
+                complexity: function.complexity,
+            })
+            .collect::<Vec<_>>();
+        functions.sort_by_key(|function| Reverse((function.complexity, function.lines)));
+        functions.truncate(top);
+
+        ComplexityReport { files, functions }
+    }
+}
+
+/// Every marker comment that was found, and how many there are of each tag.
+#[derive(Serialize, Deserialize)]
+pub struct MarkersReport {
+    /// The number of markers with each tag, in the order the tags were given (including
+    /// tags that weren't found).
+    pub tags: Vec<TagCount>,
+    /// The number of markers with each tag in each directory (not counting the
+    /// directories under it), by directory and then tag. Tags that weren't found in a
+    /// directory are left out.
+    pub directories: Vec<DirectoryTagCount>,
+    /// Every marker, by file and then line.
+    pub markers: Vec<MarkerEntry>,
+}
+
+/// How many markers have a tag.
+#[derive(Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// How many markers in a directory have a tag.
+#[derive(Serialize, Deserialize)]
+pub struct DirectoryTagCount {
+    /// The directory, as reached from its root.
+    pub path: String,
+    pub tag: String,
+    pub count: usize,
+}
+
+/// One marker comment.
+#[derive(Serialize, Deserialize)]
+pub struct MarkerEntry {
+    /// The path of the file that the marker is in.
+    pub path: String,
+    /// The line the marker is on, counting from 1.
+    pub line: usize,
+    pub tag: String,
+    /// Who the marker is for, if it names someone.
+    pub owner: Option<String>,
+    pub text: String,
+}
+
+impl MarkersReport {
+    /// Gathers up the markers in every file.
+    ///
+    /// # Parameters
+    /// - `roots`: Everything that was counted under each root, with markers looked for.
+    /// - `tags`: The tags that were looked for.
+    pub fn new(roots: &[(String, Counted)], tags: &[String]) -> Self {
+        let markers = roots
+            .iter()
+            .flat_map(|(_, counted)| &counted.files)
+            .flat_map(|file| file.markers.iter().map(move |marker| (file, marker)))
+            .map(|(file, marker)| MarkerEntry {
+                path: file.path.display().to_string(),
+                line: marker.line,
+                tag: marker.tag.clone(),
+                owner: marker.owner.clone(),
+                text: marker.text.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut by_directory = BTreeMap::new();
+        for marker in &markers {
+            let dir = directory_of(Path::new(&marker.path));
+            if let Some(tag) = tags.iter().position(|tag| *tag == marker.tag) {
+                *by_directory.entry((dir, tag)).or_insert(0) += 1;
+            }
+        }
+
+        MarkersReport {
+            tags: tags
+                .iter()
+                .map(|tag| TagCount {
+                    tag: tag.clone(),
+                    count: markers.iter().filter(|m| &m.tag == tag).count(),
+                })
+                .collect(),
+            directories: by_directory
+                .into_iter()
+                .map(|((path, tag), count)| DirectoryTagCount {
+                    path,
+                    tag: tags[tag].clone(),
+                    count,
+                })
+                .collect(),
+            markers,
+        }
+    }
+}
+
+/// Who wrote how much of the code, going by `git blame`.
+#[derive(Serialize, Deserialize)]
+pub struct AuthorsReport {
+    /// Each author's lines, from most to fewest.
+    pub authors: Vec<AuthorEntry>,
+    /// Each author's lines in each language, if asked for, by author (in the same order as
+    /// `authors`) and then from most to fewest lines.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub languages: Option<Vec<AuthorLanguageEntry>>,
+    /// Each author's lines in each directory (not counting the directories under it), if
+    /// asked for, by author and then from most to fewest lines.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directories: Option<Vec<AuthorDirectoryEntry>>,
+    /// The lines of the files that aren't in a git repository, or couldn't be blamed.
+    pub unattributed: AuthorTotals,
+}
+
+/// The lines someone wrote, and how many files they're spread over.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AuthorTotals {
+    pub files: usize,
+    pub lines: usize,
+    pub code: usize,
+    pub comment: usize,
+    pub blank: usize,
+}
+
+impl AuthorTotals {
+    /// Adds someone's lines in a single file to these totals.
+    pub fn add(&mut self, counts: &LineCounts) {
+        self.files += 1;
+        self.lines += counts.lines();
+        self.code += counts.code;
+        self.comment += counts.comment;
+        self.blank += counts.blank;
+    }
+}
+
+/// One author's lines.
+#[derive(Serialize, Deserialize)]
+pub struct AuthorEntry {
+    /// The author's name. If the author goes by more than one name (after `.mailmap`), this
+    /// is the first one found.
+    pub name: String,
+    pub email: String,
+    #[serde(flatten)]
+    pub totals: AuthorTotals,
+}
+
+/// One author's lines in one language.
+#[derive(Serialize, Deserialize)]
+pub struct AuthorLanguageEntry {
+    pub email: String,
+    pub language: String,
+    #[serde(flatten)]
+    pub totals: AuthorTotals,
+}
+
+/// One author's lines in one directory.
+#[derive(Serialize, Deserialize)]
+pub struct AuthorDirectoryEntry {
+    pub email: String,
+    /// The directory, as reached from its root.
+    pub path: String,
+    #[serde(flatten)]
+    pub totals: AuthorTotals,
+}
+
+impl AuthorsReport {
+    /// Adds up who wrote the lines of every file. Authors are told apart by their email.
+    ///
+    /// # Parameters
+    /// - `files`: The files that were blamed.
+    /// - `authors`: Who wrote how many lines of each file, in the same order as `files`, or
+    ///   `None` for a file that couldn't be blamed (see `blame::blame_files`).
+    /// - `languages`: Whether to break each author's lines down by language.
+    /// - `directories`: Whether to break each author's lines down by directory.
+    pub fn new(
+        files: &[&FileStats],
+        authors: &[Option<Authors>],
+        languages: bool,
+        directories: bool,
+    ) -> Self {
+        let mut names = HashMap::new();
+        let mut by_author: HashMap<&str, AuthorTotals> = HashMap::new();
+        let mut by_language: HashMap<(&str, &str), AuthorTotals> = HashMap::new();
+        let mut by_directory: HashMap<(&str, String), AuthorTotals> = HashMap::new();
+        let mut unattributed = AuthorTotals::default();
+        for (file, authors) in files.iter().zip(authors) {
+            let authors = match authors {
+                Some(authors) => authors,
+                None => {
+                    unattributed.add(&file.counts);
+                    continue;
+                }
+            };
+
+            let dir = directory_of(&file.path);
+            for (author, counts) in authors {
+                let email = author.email.as_str();
+                names.entry(email).or_insert(author.name.as_str());
+                by_author.entry(email).or_default().add(counts);
+                if languages {
+                    by_language
+                        .entry((email, file.language.name))
+                        .or_default()
+                        .add(counts);
+                }
+                if directories {
+                    by_directory
+                        .entry((email, dir.clone()))
+                        .or_default()
+                        .add(counts);
+                }
+            }
+        }
+
+        let mut authors = by_author
+            .into_iter()
+            .map(|(email, totals)| AuthorEntry {
+                name: names[email].to_string(),
+                email: email.to_string(),
+                totals,
+            })
+            .collect::<Vec<_>>();
+        authors.sort_by(|a, b| {
+            (Reverse(a.totals.lines), &a.email).cmp(&(Reverse(b.totals.lines), &b.email))
+        });
+
+        // Breakdowns are listed by author, in the same order as the authors.
+        let rank = authors
+            .iter()
+            .enumerate()
+            .map(|(i, author)| (author.email.clone(), i))
+            .collect::<HashMap<_, _>>();
+        let breakdown = |email: &str, totals: &AuthorTotals, name: &str| {
+            (rank[email], Reverse(totals.lines), name.to_string())
+        };
+
+        AuthorsReport {
+            languages: Some(by_language).filter(|_| languages).map(|by_language| {
+                let mut entries = by_language
+                    .into_iter()
+                    .map(|((email, language), totals)| AuthorLanguageEntry {
+                        email: email.to_string(),
+                        language: language.to_string(),
+                        totals,
+                    })
+                    .collect::<Vec<_>>();
+                entries.sort_by_key(|e| breakdown(&e.email, &e.totals, &e.language));
+                entries
+            }),
+            directories: Some(by_directory)
+                .filter(|_| directories)
+                .map(|by_directory| {
+                    let mut entries = by_directory
+                        .into_iter()
+                        .map(|((email, path), totals)| AuthorDirectoryEntry {
+                            email: email.to_string(),
+                            path,
+                            totals,
+                        })
+                        .collect::<Vec<_>>();
+                    entries.sort_by_key(|e| breakdown(&e.email, &e.totals, &e.path));
+                    entries
+                }),
+            authors,
+            unattributed,
+        }
+    }
+}
+
+/// How much of the code each owner owns, going by `CODEOWNERS`.
+#[derive(Serialize, Deserialize)]
+pub struct OwnersReport {
+    /// Each owner's files, from most to fewest lines. A file with more than one owner
+    /// counts toward each of them.
+    pub owners: Vec<OwnerEntry>,
+    /// The files that no one owns.
+    pub unowned: Totals,
+}
+
+/// One owner's files.
+#[derive(Serialize, Deserialize)]
+pub struct OwnerEntry {
+    /// The owner, as written in `CODEOWNERS` (such as `@org/team` or an email).
+    pub owner: String,
+    #[serde(flatten)]
+    pub totals: Totals,
+}
+
+impl OwnersReport {
+    /// Adds up the files that each owner owns.
+    ///
+    /// # Parameters
+    /// - `files`: The files.
+    /// - `owners`: The owners of each file, in the same order as `files` (see
+    ///   `owners::find_owners`).
+    pub fn new(files: &[&FileStats], owners: &[Vec<String>]) -> Self {
+        let mut by_owner: HashMap<&str, Totals> = HashMap::new();
+        let mut unowned = Totals::default();
+        for (file, owners) in files.iter().zip(owners) {
+            if owners.is_empty() {
+                unowned.add(&file.counts, file.bytes);
+            }
+            for owner in owners {
+                by_owner
+                    .entry(owner.as_str())
+                    .or_default()
+                    .add(&file.counts, file.bytes);
+            }
+        }
+
+        let mut owners = by_owner
+            .into_iter()
+            .map(|(owner, totals)| OwnerEntry {
+                owner: owner.to_string(),
+                totals,
+            })
+            .collect::<Vec<_>>();
+        owners.sort_by(|a, b| {
+            (Reverse(a.totals.lines), &a.owner).cmp(&(Reverse(b.totals.lines), &b.owner))
+        });
+        OwnersReport { owners, unowned }
+    }
+}
+
+/// The totals of each project that files were found in.
+#[derive(Serialize, Deserialize)]
+pub struct ProjectsReport {
+    /// Each project, from most to fewest lines.
+    pub projects: Vec<ProjectEntry>,
+    /// The files that aren't in any project.
+    pub outside: Totals,
+}
+
+/// The totals of one project.
+#[derive(Serialize, Deserialize)]
+pub struct ProjectEntry {
+    pub name: String,
+    pub kind: Kind,
+    /// The directory that the project's manifest is in.
+    pub path: String,
+    #[serde(flatten)]
+    pub totals: Totals,
+    pub languages: Vec<LanguageEntry>,
+}
+
+impl ProjectsReport {
+    /// Adds up the files in each project.
+    ///
+    /// # Parameters
+    /// - `files`: The files.
+    /// - `projects`: The project that each file is in, in the same order as `files` (see
+    ///   `projects::find_projects`).
+    pub fn new(files: &[&FileStats], projects: &[Option<Project>]) -> Self {
+        let mut by_project: HashMap<&Project, (Totals, HashMap<&'static str, Totals>)> =
+            HashMap::new();
+        let mut outside = Totals::default();
+        for (file, project) in files.iter().zip(projects) {
+            match project {
+                Some(project) => {
+                    let (totals, by_language) = by_project.entry(project).or_default();
+                    totals.add(&file.counts, file.bytes);
+                    add_languages(by_language, file);
+                }
+                None => outside.add(&file.counts, file.bytes),
+            }
+        }
+
+        let mut projects = by_project
+            .into_iter()
+            .map(|(project, (totals, by_language))| ProjectEntry {
+                name: project.name.clone(),
+                kind: project.kind,
+                path: project.path.display().to_string(),
+                totals,
+                languages: sorted_languages(by_language),
+            })
+            .collect::<Vec<_>>();
+        projects.sort_by(|a, b| {
+            (Reverse(a.totals.lines), &a.name, &a.path).cmp(&(
+                Reverse(b.totals.lines),
+                &b.name,
+                &b.path,
+            ))
+        });
+        ProjectsReport { projects, outside }
+    }
+}
+
+/// The directory that a file is in, or `.` for a file given by just its name.
+fn directory_of(path: &Path) -> String {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.display().to_string(),
+        _ => ".".to_string(),
+    }
+}
+
+/// Summed up counts over a group of files.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Totals {
+    pub files: usize,
+    pub lines: usize,
+    pub code: usize,
+    pub comment: usize,
+    pub blank: usize,
+    pub bytes: u64,
+}
+
+impl Totals {
+    /// Adds a single file's counts to these totals.
+    pub fn add(&mut self, counts: &LineCounts, bytes: u64) {
+        self.files += 1;
+        self.bytes += bytes;
+        self.add_lines(counts);
+    }
+
+    /// Adds lines without adding a file, for lines embedded in a file that's counted as
+    /// another language.
+    pub fn add_lines(&mut self, counts: &LineCounts) {
+        self.lines += counts.lines();
+        self.code += counts.code;
+        self.comment += counts.comment;
+        self.blank += counts.blank;
+    }
+}
+
+/// Adds a file to the totals of each language in it: its own lines to its language, and any
+/// embedded lines to theirs.
+pub fn add_languages(by_language: &mut HashMap<&'static str, Totals>, file: &FileStats) {
+    by_language
+        .entry(file.language.name)
+        .or_default()
+        .add(&file.own_counts(), file.bytes);
+    for embedded in &file.embedded {
+        by_language
+            .entry(embedded.language.name)
+            .or_default()
+            .add_lines(&embedded.counts);
+    }
+}
+
+impl Report {
+    /// Builds a report out of the stats of every counted file.
+    ///
+    /// # Parameters
+    /// - `roots`: The name of each root that was counted, along with the files counted under
+    ///   it, in the order that they should be listed.
+    ///
+    /// # Returns
+    /// The report. Languages are ordered from most to fewest lines; roots and files keep
+    /// their order.
+    pub fn new(roots: &[(String, Counted)]) -> Self {
+        let mut by_language: HashMap<&'static str, Totals> = HashMap::new();
+        let mut root_entries = vec![];
+        let mut total = Totals::default();
+        for (root, counted) in roots {
+            let mut root_totals = Totals::default();
+            for file in &counted.files {
+                add_languages(&mut by_language, file);
+                root_totals.add(&file.counts, file.bytes);
+                total.add(&file.counts, file.bytes);
+            }
+
+            root_entries.push(RootEntry {
+                root: root.clone(),
+                totals: root_totals,
+            });
+        }
+
+        Report {
+            version: SCHEMA_VERSION,
+            files: roots
+                .iter()
+                .flat_map(|(_, counted)| &counted.files)
+                .map(FileEntry::new)
+                .collect(),
+            languages: sorted_languages(by_language),
+            roots: root_entries,
+            distribution: Distribution::new(
+                roots
+                    .iter()
+                    .flat_map(|(_, counted)| &counted.files)
+                    .map(|file| file.counts.lines())
+                    .collect(),
+            ),
+            total,
+            binary: roots
+                .iter()
+                .flat_map(|(_, counted)| &counted.binary)
+                .map(|file| BinaryEntry {
+                    path: file.path.display().to_string(),
+                    bytes: file.bytes,
+                })
+                .collect(),
+            tree: None,
+            duplicates: None,
+            complexity: None,
+            markers: None,
+            authors: None,
+            owners: None,
+            projects: None,
+            errors: roots
+                .iter()
+                .flat_map(|(_, counted)| &counted.errors)
+                .map(|error| ErrorEntry {
+                    path: error.path.display().to_string(),
+                    reason: error.reason.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The same as `new`, but with the rolled-up totals of every directory as well.
+    ///
+    /// # Parameters
+    /// - `roots`: The name of each root that was counted, along with the files counted under
+    ///   it.
+    /// - `max_depth`: How many levels below each root to list directories for, if limited.
+    pub fn with_tree(roots: &[(String, Counted)], max_depth: Option<usize>) -> Self {
+        let mut report = Report::new(roots);
+        report.tree = Some(crate::tree::build(roots, max_depth));
+        report
+    }
+
+    /// Sorts the file listing and cuts it down to size. This only changes which files are
+    /// listed, not any of the totals.
+    ///
+    /// # Parameters
+    /// - `key`: What to sort by. Paths are sorted A to Z, and everything else from largest
+    ///   to smallest. Ties keep their order.
+    /// - `reverse`: Whether to flip the order (ties still keep theirs).
+    /// - `top`: How many files to keep, if limited.
+    pub fn sort_files(&mut self, key: SortKey, reverse: bool, top: Option<usize>) {
+        self.files.sort_by(|a, b| {
+            let order = match key {
+                SortKey::Path => a.path.cmp(&b.path),
+                SortKey::Lines => b.lines.cmp(&a.lines),
+                SortKey::Code => b.code.cmp(&a.code),
+                SortKey::Bytes => b.bytes.cmp(&a.bytes),
+                SortKey::Complexity => b.complexity().cmp(&a.complexity()),
+            };
+            if reverse {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+
+        if let Some(top) = top {
+            self.files.truncate(top);
+        }
+    }
+}
+
+/// Turns per-language totals into a list, ordered from most to fewest lines (and then by
+/// name, so that the order is always the same).
+pub fn sorted_languages(by_language: HashMap<&'static str, Totals>) -> Vec<LanguageEntry> {
+    let mut languages = by_language
+        .into_iter()
+        .map(|(language, totals)| LanguageEntry {
+            language: language.to_string(),
+            totals,
+        })
+        .collect::<Vec<_>>();
+    languages.sort_by(|a, b| {
+        b.totals
+            .lines
+            .cmp(&a.totals.lines)
+            .then(a.language.cmp(&b.language))
+    });
+    languages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::complexity::{FileComplexity, FunctionComplexity};
+    use crate::embedded::Embedded;
+    use crate::language;
+    use crate::walk::FileStats;
+    use std::path::PathBuf;
+
+    fn file(path: &str, lang: &str, code: usize, comment: usize, blank: usize) -> FileStats {
+        FileStats {
+            path: PathBuf::from(path),
+            rel_path: PathBuf::from(path),
+            language: language::from_name(lang).unwrap(),
+            counts: LineCounts {
+                code,
+                comment,
+                blank,
+            },
+            bytes: (code + comment + blank) as u64 * 10,
+            hash: None,
+            complexity: None,
+            markers: vec![],
+            embedded: vec![],
+        }
+    }
+
+    fn root(name: &str, files: Vec<FileStats>) -> (String, Counted) {
+        let counted = Counted {
+            files,
+            ..Counted::default()
+        };
+        (name.to_string(), counted)
+    }
+
+    fn paths(report: &Report) -> Vec<&str> {
+        report.files.iter().map(|f| f.path.as_str()).collect()
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let values = (1..=20).collect::<Vec<_>>();
+        assert_eq!(percentile(&values, 50), 10);
+        assert_eq!(percentile(&values, 90), 18);
+        assert_eq!(percentile(&values, 99), 20);
+        assert_eq!(percentile(&[7], 50), 7);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[test]
+    fn histogram_buckets_cover_every_file() {
+        let dist = Distribution::new(vec![0, 49, 50, 99, 5000, 12000]);
+        let files = dist.histogram.iter().map(|b| b.files).collect::<Vec<_>>();
+        assert_eq!(files, [2, 2, 0, 0, 0, 0, 0, 2]);
+        assert_eq!(dist.histogram[1].min, 50);
+        assert_eq!(dist.histogram[1].max, Some(99));
+        assert_eq!(dist.histogram.last().unwrap().max, None);
+        assert_eq!(dist.max, 12000);
+    }
+
+    #[test]
+    fn totals_by_language_and_root() {
+        let report = Report::new(&[
+            root(
+                "a",
+                vec![
+                    file("a/x.rs", "rust", 10, 2, 1),
+                    file("a/y.py", "python", 3, 0, 0),
+                ],
+            ),
+            root("b", vec![file("b/z.rs", "rust", 5, 0, 0)]),
+        ]);
+
+        assert_eq!(report.total.files, 3);
+        assert_eq!(report.total.lines, 21);
+        assert_eq!(report.roots[0].totals.code, 13);
+        assert_eq!(report.roots[1].totals.code, 5);
+
+        let languages = report
+            .languages
+            .iter()
+            .map(|l| (l.language.as_str(), l.totals.lines))
+            .collect::<Vec<_>>();
+        assert_eq!(languages, [("Rust", 18), ("Python", 3)]);
+    }
+
+    #[test]
+    fn embedded_lines_count_toward_their_language() {
+        let mut readme = file("README.md", "markdown", 11, 1, 2);
+        readme.embedded.push(Embedded {
+            language: language::from_name("rust").unwrap(),
+            counts: LineCounts {
+                code: 4,
+                comment: 1,
+                blank: 0,
+            },
+        });
+        let report = Report::new(&[root("r", vec![readme, file("src/lib.rs", "rust", 3, 0, 0)])]);
+
+        assert_eq!(report.total.lines, 17);
+        assert_eq!(report.files[0].lines, 14);
+        assert_eq!(report.files[0].embedded[0].code, 4);
+
+        let languages = report
+            .languages
+            .iter()
+            .map(|l| (l.language.as_str(), l.totals.files, l.totals.lines))
+            .collect::<Vec<_>>();
+        assert_eq!(languages, [("Markdown", 1, 9), ("Rust", 1, 8)]);
+    }
+
+    #[test]
+    fn sorting_keeps_ties_in_order() {
+        let mut report = Report::new(&[root(
+            "r",
+            vec![
+                file("c", "rust", 1, 0, 0),
+                file("a", "rust", 5, 0, 0),
+                file("b", "rust", 1, 0, 0),
+            ],
+        )]);
+
+        report.sort_files(SortKey::Lines, false, None);
+        assert_eq!(paths(&report), ["a", "c", "b"]);
+
+        report.sort_files(SortKey::Lines, true, None);
+        assert_eq!(paths(&report), ["c", "b", "a"]);
+
+        report.sort_files(SortKey::Path, false, Some(2));
+        assert_eq!(paths(&report), ["a", "b"]);
+        assert_eq!(report.total.files, 3);
+    }
+
+    #[test]
+    fn most_complex_files_and_functions() {
+        let function = |name: &str, complexity| FunctionComplexity {
+            name: name.to_string(),
+            line: 1,
+            lines: 5,
+            max_depth: 1,
+            complexity,
+        };
+        let with_complexity = |mut file: FileStats, functions: Vec<FunctionComplexity>| {
+            file.complexity = Some(FileComplexity {
+                complexity: functions.iter().map(|f| f.complexity).sum(),
+                functions,
+                max_depth: 1,
+            });
+            file
+        };
+        let report = ComplexityReport::new(
+            &[root(
+                "r",
+                vec![
+                    with_complexity(file("a.rs", "rust", 10, 0, 0), vec![function("f", 3)]),
+                    file("b.txt", "text", 100, 0, 0),
+                    with_complexity(
+                        file("c.rs", "rust", 20, 0, 0),
+                        vec![function("g", 1), function("h", 2)],
+                    ),
+                    with_complexity(file("d.rs", "rust", 5, 0, 0), vec![function("i", 4)]),
+                ],
+            )],
+            2,
+        );
+
+        assert_eq!(
+            report
+                .files
+                .iter()
+                .map(|f| f.path.as_str())
+                .collect::<Vec<_>>(),
+            ["d.rs", "c.rs"]
+        );
+        assert_eq!(
+            report
+                .functions
+                .iter()
+                .map(|f| (f.path.as_str(), f.name.as_str()))
+                .collect::<Vec<_>>(),
+            [("d.rs", "i"), ("a.rs", "f")]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use crate::diff::Delta;
+use crate::report::{Report, Totals, SCHEMA_VERSION};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Where snapshots are kept, unless told otherwise.
+pub const DEFAULT_DIR: &str = ".tlc/snapshots";
+
+/// A saved report, tagged with a label and the time it was taken.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub label: String,
+    pub timestamp: DateTime<Utc>,
+    /// The full report: every file, and the tree of every directory.
+    pub report: Report,
+}
+
+impl Snapshot {
+    /// Tags a report as taken right now.
+    pub fn new(label: &str, report: Report) -> Self {
+        Snapshot {
+            label: label.to_string(),
+            timestamp: SystemTime::now().into(),
+            report,
+        }
+    }
+
+    /// Writes the snapshot to a new JSON file in `dir`, named after its timestamp and label
+    /// so that the files sort in the order they were taken.
+    ///
+    /// # Parameters
+    /// - `dir`: The snapshot directory. It is created if it doesn't exist yet, along with an
+    ///   `.ignore` file so that the snapshots aren't counted themselves. (git doesn't read
+    ///   `.ignore` files, so the snapshots can still be committed.)
+    ///
+    /// # Returns
+    /// The path of the new file, or the error from writing it.
+    pub fn save(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let ignore = dir.join(".ignore");
+        if !ignore.exists() {
+            fs::write(ignore, "*\n")?;
+        }
+
+        let name = format!(
+            "{}-{}.json",
+            self.timestamp.format("%Y%m%dT%H%M%SZ"),
+            file_safe(&self.label)
+        );
+        let path = dir.join(name);
+        let mut out = BufWriter::new(File::create(&path)?);
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)?;
+        out.flush()?;
+        Ok(path)
+    }
+
+    /// Reads a snapshot file.
+    ///
+    /// # Parameters
+    /// - `path`: The file.
+    ///
+    /// # Returns
+    /// The snapshot, or an error if the file can't be read, isn't a snapshot, or was saved
+    /// by a newer version of `tlc` with an incompatible schema.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| format!("{}: not a snapshot: {}", path.display(), e))?;
+        if snapshot.report.version > SCHEMA_VERSION {
+            return Err(format!(
+                "{}: saved with schema version {}, but this tlc only understands up to {}",
+                path.display(),
+                snapshot.report.version,
+                SCHEMA_VERSION
+            )
+            .into());
+        }
+
+        Ok(snapshot)
+    }
+}
+
+/// Replaces anything in a label that might not be allowed in a file name.
+fn file_safe(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Reads every snapshot in a directory. Files that don't end in `.json` are ignored.
+///
+/// # Parameters
+/// - `dir`: The snapshot directory. If it doesn't exist, there are no snapshots.
+///
+/// # Returns
+/// The snapshots, oldest first, or the first error from reading them.
+pub fn load_all(dir: &Path) -> Result<Vec<Snapshot>, Box<dyn Error>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("{}: {}", dir.display(), e).into()),
+    };
+
+    let mut snapshots = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            snapshots.push(Snapshot::load(&path)?);
+        }
+    }
+
+    snapshots.sort_by(|a, b| {
+        a.timestamp
+            .cmp(&b.timestamp)
+            .then_with(|| a.label.cmp(&b.label))
+    });
+    Ok(snapshots)
+}
+
+/// Finds a snapshot given on the command line.
+///
+/// # Parameters
+/// - `name`: Either the path to a snapshot file, or the label of a snapshot in `dir`. If
+///   more than one snapshot has the label, the newest one is used.
+/// - `dir`: The snapshot directory.
+///
+/// # Returns
+/// The snapshot, or an error if there's no such file or label.
+pub fn find(name: &str, dir: &Path) -> Result<Snapshot, Box<dyn Error>> {
+    let path = Path::new(name);
+    if path.is_file() {
+        return Snapshot::load(path);
+    }
+
+    load_all(dir)?
+        .into_iter()
+        .rev()
+        .find(|snapshot| snapshot.label == name)
+        .ok_or_else(|| {
+            format!(
+                "no snapshot file or label named '{}' (looked in {})",
+                name,
+                dir.display()
+            )
+            .into()
+        })
+}
+
+/// The trend over every stored snapshot.
+#[derive(Serialize)]
+pub struct History {
+    pub version: u32,
+    pub snapshots: Vec<HistoryEntry>,
+}
+
+/// One snapshot's grand total, and how it changed since the snapshot before it.
+#[derive(Serialize)]
+pub struct HistoryEntry {
+    pub label: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub totals: Totals,
+    /// The change since the previous snapshot; all zeros for the first one.
+    pub change: Delta,
+}
+
+impl History {
+    /// Builds the trend out of snapshots that are already in order.
+    pub fn new(snapshots: &[Snapshot]) -> Self {
+        let mut previous: Option<&Totals> = None;
+        let mut entries = vec![];
+        for snapshot in snapshots {
+            let totals = &snapshot.report.total;
+            entries.push(HistoryEntry {
+                label: snapshot.label.clone(),
+                timestamp: snapshot.timestamp,
+                totals: *totals,
+                change: Delta::between(previous.unwrap_or(totals), totals),
+            });
+            previous = Some(totals);
+        }
+
+        History {
+            version: SCHEMA_VERSION,
+            snapshots: entries,
+        }
+    }
+}
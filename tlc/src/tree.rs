@@ -0,0 +1,80 @@
+use crate::report::{self, Totals, TreeEntry};
+use crate::walk::{Counted, FileStats};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// A directory, along with the rolled-up totals of everything under it.
+#[derive(Default)]
+struct Node {
+    totals: Totals,
+    languages: HashMap<&'static str, Totals>,
+    children: BTreeMap<OsString, Node>,
+}
+
+impl Node {
+    /// Turns this node and everything under it into a flat list, parents before their
+    /// children. Children are ordered from most to fewest lines.
+    fn flatten(
+        self,
+        root: &str,
+        path: PathBuf,
+        depth: usize,
+        max_depth: Option<usize>,
+        entries: &mut Vec<TreeEntry>,
+    ) {
+        entries.push(TreeEntry {
+            root: root.to_string(),
+            path: path.display().to_string(),
+            depth,
+            totals: self.totals,
+            languages: report::sorted_languages(self.languages),
+        });
+
+        if max_depth.is_some_and(|max| depth >= max) {
+            return;
+        }
+
+        let mut children = self.children.into_iter().collect::<Vec<_>>();
+        // The sort is stable, so children with the same size stay in name order.
+        children.sort_by_key(|(_, child)| Reverse(child.totals.lines));
+        for (name, child) in children {
+            child.flatten(root, path.join(name), depth + 1, max_depth, entries);
+        }
+    }
+}
+
+/// Builds the directory tree of each root, rolling up the totals of every file into each
+/// directory above it.
+///
+/// # Parameters
+/// - `roots`: The name of each root, along with what was counted under it.
+/// - `max_depth`: How many levels below each root to show, if limited. Deeper directories
+///   are still counted in their ancestors.
+///
+/// # Returns
+/// The directories of every root, each root's directories in tree order.
+pub fn build(roots: &[(String, Counted)], max_depth: Option<usize>) -> Vec<TreeEntry> {
+    let mut entries = vec![];
+    for (root, counted) in roots {
+        let mut top = Node::default();
+        for file in &counted.files {
+            let mut node = &mut top;
+            add(node, file);
+            for dir in file.rel_path.parent().unwrap_or_else(|| Path::new("")) {
+                node = node.children.entry(dir.to_os_string()).or_default();
+                add(node, file);
+            }
+        }
+
+        top.flatten(root, PathBuf::new(), 0, max_depth, &mut entries);
+    }
+
+    entries
+}
+
+fn add(node: &mut Node, file: &FileStats) {
+    node.totals.add(&file.counts, file.bytes);
+    report::add_languages(&mut node.languages, file);
+}
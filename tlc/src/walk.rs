@@ -0,0 +1,1174 @@
+use crate::archive::{self, IgnoreFiles, Kind};
+use crate::cache::{self, Cache, Stamp};
+use crate::complexity::{Analyzer, FileComplexity};
+use crate::count::{self, LineCounts};
+use crate::dedupe::{self, ContentHash, HashingReader, Match};
+use crate::embedded::{self, Embedded};
+use crate::encoding::{self, Encoding};
+use crate::filter::PathFilter;
+use crate::language::{self, Language};
+use crate::markers::{Marker, Scanner};
+use ignore::{DirEntry, WalkBuilder, WalkState};
+use memmap2::Mmap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+
+/// Controls which files are counted and how the directory tree is walked.
+pub struct WalkOptions {
+    /// The name-based rules for skipping files and directories.
+    pub filter: Arc<PathFilter>,
+    /// The extensions and language names to count. If empty, every file in a known language
+    /// is counted.
+    pub to_check: Vec<String>,
+    /// Whether to skip files matched by `.gitignore`, `.ignore`, and friends.
+    pub respect_ignore: bool,
+    /// Whether to follow symbolic links. If not, symbolic links are skipped.
+    pub follow_symlinks: bool,
+    /// The number of threads to walk and count with.
+    pub threads: usize,
+    /// Whether to print every skipped path, and why it was skipped, to standard error.
+    pub explain: bool,
+    /// The directory to keep cached results in, or `None` to not use a cache.
+    pub cache_dir: Option<PathBuf>,
+    /// Whether to ignore previously cached results (but still save new ones).
+    pub rebuild_cache: bool,
+    /// How to hash each file's contents, to find copies with, or `None` to not hash them.
+    pub hash: Option<Match>,
+    /// Whether to measure how complex the code in each file is, in the languages we know
+    /// how to.
+    pub complexity: bool,
+    /// The tags of the marker comments (such as `TODO`) to look for, or `None` to not look
+    /// for them.
+    pub markers: Option<Vec<String>>,
+    /// Whether to count the files inside archives and compressed files (see `archive`),
+    /// instead of treating them as binary files.
+    pub archives: bool,
+}
+
+/// The line counts for a single file.
+#[derive(Clone)]
+pub struct FileStats {
+    pub path: PathBuf,
+    /// The path relative to the root it was found under (see `relative_path`).
+    pub rel_path: PathBuf,
+    pub language: &'static Language,
+    pub counts: LineCounts,
+    /// The size of the file, in bytes.
+    pub bytes: u64,
+    /// The hash of the file's contents, if `WalkOptions::hash` asked for one.
+    pub hash: Option<ContentHash>,
+    /// How complex the file's code is, if `WalkOptions::complexity` asked and the
+    /// language has rules for it.
+    pub complexity: Option<FileComplexity>,
+    /// The marker comments in the file, if `WalkOptions::markers` asked for them.
+    pub markers: Vec<Marker>,
+    /// The lines written in other languages than the file's own, such as code blocks in
+    /// Markdown (see `embedded`). These are included in `counts` too.
+    pub embedded: Vec<Embedded>,
+}
+
+impl FileStats {
+    /// The counts of the lines in the file's own language, leaving out embedded ones.
+    pub fn own_counts(&self) -> LineCounts {
+        let mut counts = self.counts;
+        for embedded in &self.embedded {
+            counts -= embedded.counts;
+        }
+
+        counts
+    }
+}
+
+/// A file that would have been counted, but turned out to be binary.
+#[derive(Clone)]
+pub struct BinaryFile {
+    pub path: PathBuf,
+    /// The size of the file, in bytes.
+    pub bytes: u64,
+}
+
+/// A path that couldn't be read, such as a directory without permission to list it, a
+/// broken symbolic link (when following them), or a file that failed partway through.
+#[derive(Clone, Debug)]
+pub struct ReadError {
+    pub path: PathBuf,
+    /// What went wrong.
+    pub reason: String,
+}
+
+/// Everything found under one root.
+#[derive(Default)]
+pub struct Counted {
+    pub files: Vec<FileStats>,
+    pub binary: Vec<BinaryFile>,
+    /// Files that were looked at but not counted, such as those in no known language.
+    pub skipped: Vec<PathBuf>,
+    /// Paths that couldn't be read, so anything in them is missing from the counts.
+    pub errors: Vec<ReadError>,
+}
+
+/// What happened when we tried to count a file.
+#[derive(Clone)]
+enum Outcome {
+    Counted(FileStats),
+    Binary(BinaryFile),
+    Skipped(PathBuf),
+    Failed(ReadError),
+}
+
+impl Outcome {
+    fn path(&self) -> &Path {
+        match self {
+            Outcome::Counted(stats) => &stats.path,
+            Outcome::Binary(binary) => &binary.path,
+            Outcome::Skipped(path) => path,
+            Outcome::Failed(error) => &error.path,
+        }
+    }
+
+    /// Notes that the file at `path` couldn't be read.
+    fn failed(path: &Path, error: io::Error) -> Self {
+        Outcome::Failed(ReadError {
+            path: path.to_path_buf(),
+            reason: error.to_string(),
+        })
+    }
+}
+
+/// Identifies a file no matter which path it was reached through.
+#[cfg(unix)]
+#[derive(PartialEq, Eq, Hash)]
+struct FileId(u64, u64);
+
+#[cfg(not(unix))]
+#[derive(PartialEq, Eq, Hash)]
+struct FileId(PathBuf);
+
+impl FileId {
+    /// Gets the ID of the file that `path` points to: its device and inode numbers on Unix,
+    /// or its canonical path elsewhere.
+    #[cfg(unix)]
+    fn of(path: &Path) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        let meta = fs::metadata(path).ok()?;
+        Some(FileId(meta.dev(), meta.ino()))
+    }
+
+    #[cfg(not(unix))]
+    fn of(path: &Path) -> Option<Self> {
+        fs::canonicalize(path).ok().map(FileId)
+    }
+}
+
+/// Walks every path in `paths` and counts every matching file. A path can be either a
+/// directory, which is walked, or a single file. Directories are read and files are counted
+/// on a pool of `opts.threads` worker threads, which steal work from each other when they
+/// run out.
+///
+/// # Parameters
+/// - `paths`: The directories and files to count.
+/// - `opts`: The walk options.
+///
+/// If `opts.cache_dir` is set, files that haven't changed (by size, modification time,
+/// and inode) since the last walk of the same paths aren't read again, and the cache is
+/// updated afterwards.
+///
+/// When following symbolic links, the walker won't descend into a directory that is also
+/// one of its own ancestors (comparing device and inode numbers), so cycles are broken. A
+/// file that can be reached through more than one path is only counted once, under the path
+/// that sorts first.
+///
+/// # Returns
+/// The stats for every file that was counted, every binary file that was skipped, and every
+/// path that couldn't be read, all sorted by path so that the output doesn't depend on
+/// thread scheduling.
+pub fn count_files(paths: &[PathBuf], opts: &WalkOptions) -> Counted {
+    recount_files(paths, opts, &Counted::default(), &HashSet::new())
+}
+
+/// The same as `count_files`, but every file that was seen by a previous walk of the same
+/// paths, and isn't in `changed`, gets the same result as last time without being looked at
+/// again. The directories are still walked, so ignore files and the other rules apply just
+/// as they would in a fresh walk.
+///
+/// # Parameters
+/// - `paths`: The directories and files to count.
+/// - `opts`: The walk options.
+/// - `previous`: The result of the previous walk.
+/// - `changed`: The files that might have changed since, as reached from `paths`.
+///
+/// # Returns
+/// The stats for every file, as with `count_files`.
+pub fn recount_files(
+    paths: &[PathBuf],
+    opts: &WalkOptions,
+    previous: &Counted,
+    changed: &HashSet<PathBuf>,
+) -> Counted {
+    let known = previous
+        .files
+        .iter()
+        .map(|stats| Outcome::Counted(stats.clone()))
+        .chain(previous.binary.iter().map(|b| Outcome::Binary(b.clone())))
+        .chain(previous.skipped.iter().map(|p| Outcome::Skipped(p.clone())))
+        .filter(|outcome| !changed.contains(outcome.path()))
+        .map(|outcome| (outcome.path().to_path_buf(), outcome))
+        .collect::<HashMap<_, _>>();
+
+    let (first, rest) = match paths.split_first() {
+        Some(p) => p,
+        None => return Counted::default(),
+    };
+
+    let mut builder = WalkBuilder::new(first);
+    for path in rest {
+        builder.add(path);
+    }
+
+    let check = Check::new(&opts.to_check);
+    let cache = opts
+        .cache_dir
+        .as_ref()
+        .map(|dir| Cache::load(dir, paths, opts.rebuild_cache));
+    let respect_ignore = opts.respect_ignore;
+    let filter = Arc::clone(&opts.filter);
+    let explain = opts.explain;
+    let walker = builder
+        .standard_filters(false)
+        .git_ignore(respect_ignore)
+        .git_global(respect_ignore)
+        .git_exclude(respect_ignore)
+        .ignore(respect_ignore)
+        .parents(respect_ignore)
+        .require_git(false)
+        .follow_links(opts.follow_symlinks)
+        .threads(opts.threads)
+        .filter_entry(move |entry| !is_excluded(entry, &filter, explain))
+        .build_parallel();
+
+    let (tx, rx) = mpsc::channel();
+    walker.run(|| {
+        let tx = tx.clone();
+        let check = &check;
+        let cache = cache.as_ref();
+        let known = &known;
+        Box::new(move |entry| {
+            let entry = match entry {
+                Ok(e) => e,
+                Err(e) => {
+                    for outcome in walk_errors(&e, None, opts.explain) {
+                        let _ = tx.send((None, outcome));
+                    }
+
+                    return WalkState::Continue;
+                }
+            };
+
+            // Ignore files that couldn't be read (or parsed) are still mostly applied, but
+            // what they were meant to skip might have been counted.
+            if let Some(e) = entry.error() {
+                for outcome in walk_errors(e, None, opts.explain) {
+                    let _ = tx.send((None, outcome));
+                }
+            }
+
+            if entry.file_type().is_none_or(|ft| ft.is_dir()) {
+                return WalkState::Continue;
+            }
+
+            // Without following links, the walker still hands us the links themselves.
+            if entry.path_is_symlink() && !opts.follow_symlinks {
+                if opts.explain {
+                    eprintln!("skipped {}: symbolic link", entry.path().display());
+                }
+
+                return WalkState::Continue;
+            }
+
+            // The walker doesn't run the entry filter on the paths it was given, so files
+            // given directly are checked here instead.
+            if entry.depth() == 0 && is_excluded(&entry, &opts.filter, opts.explain) {
+                return WalkState::Continue;
+            }
+
+            // Archives aren't cached, so they're read again even if they haven't changed.
+            if let Some(kind) = Kind::of(entry.path()).filter(|_| opts.archives) {
+                for outcome in count_archive(&entry, kind, check, opts) {
+                    let _ = tx.send((None, outcome));
+                }
+
+                return WalkState::Continue;
+            }
+
+            let outcome = match known.get(entry.path()) {
+                Some(outcome) => {
+                    if let Some(cache) = cache {
+                        cache.keep(entry.path());
+                    }
+
+                    outcome.clone()
+                }
+                None => match count_file(&entry, check, cache, opts) {
+                    Ok(Some(outcome)) => outcome,
+                    Ok(None) => Outcome::Skipped(entry.path().to_path_buf()),
+                    Err(e) => Outcome::failed(entry.path(), e),
+                },
+            };
+            let id = match outcome {
+                Outcome::Skipped(_) | Outcome::Failed(_) => None,
+                _ if opts.follow_symlinks => FileId::of(entry.path()),
+                _ => None,
+            };
+
+            // The receiver outlives the walk, so this can't fail.
+            let _ = tx.send((id, outcome));
+
+            WalkState::Continue
+        })
+    });
+    drop(tx);
+
+    // The cache is only there to save time, so failing to save it isn't fatal.
+    if let Some(cache) = cache {
+        let _ = cache.save();
+    }
+
+    let mut outcomes = rx.into_iter().collect::<Vec<_>>();
+    outcomes.sort_by(|(_, a), (_, b)| a.path().cmp(b.path()));
+
+    let mut first_paths: HashMap<FileId, PathBuf> = HashMap::new();
+    let mut counted = Counted::default();
+    for (id, outcome) in outcomes {
+        if let Some(id) = id {
+            match first_paths.entry(id) {
+                Entry::Occupied(first) => {
+                    if opts.explain {
+                        eprintln!(
+                            "skipped {}: same file as {}",
+                            outcome.path().display(),
+                            first.get().display()
+                        );
+                    }
+
+                    continue;
+                }
+                Entry::Vacant(slot) => {
+                    slot.insert(outcome.path().to_path_buf());
+                }
+            }
+        }
+
+        match outcome {
+            Outcome::Counted(stats) => counted.files.push(stats),
+            Outcome::Binary(binary) => counted.binary.push(binary),
+            Outcome::Skipped(path) => counted.skipped.push(path),
+            Outcome::Failed(error) => counted.errors.push(error),
+        }
+    }
+
+    counted
+}
+
+/// Turns an error from the walker into the paths that couldn't be read.
+///
+/// # Parameters
+/// - `error`: The error.
+/// - `path`: The path the error is about, if an enclosing error said.
+/// - `explain`: Whether to print the symbolic link loops that were skipped.
+///
+/// # Returns
+/// A failure for each path in the error. Symbolic link loops are skipped on purpose, so
+/// they aren't failures.
+fn walk_errors(error: &ignore::Error, path: Option<&Path>, explain: bool) -> Vec<Outcome> {
+    let failed = |reason: String| {
+        vec![Outcome::Failed(ReadError {
+            path: path.map(Path::to_path_buf).unwrap_or_default(),
+            reason,
+        })]
+    };
+    match error {
+        ignore::Error::Partial(errors) => errors
+            .iter()
+            .flat_map(|e| walk_errors(e, path, explain))
+            .collect(),
+        ignore::Error::WithPath { path, err } => walk_errors(err, Some(path), explain),
+        ignore::Error::WithDepth { err, .. } => walk_errors(err, path, explain),
+        ignore::Error::WithLineNumber { line, err } => failed(format!("line {}: {}", line, err)),
+        ignore::Error::Loop { ancestor, child } => {
+            if explain {
+                eprintln!(
+                    "skipped {}: symbolic link loop back to {}",
+                    child.display(),
+                    ancestor.display()
+                );
+            }
+
+            vec![]
+        }
+        e => failed(e.to_string()),
+    }
+}
+
+/// Checks the entry against the name-based rules in `filter`.
+///
+/// # Parameters
+/// - `entry`: The file or directory.
+/// - `filter`: The rules.
+/// - `explain`: Whether to print why the entry was excluded, if it was.
+///
+/// # Returns
+/// Whether the entry should be skipped.
+fn is_excluded(entry: &DirEntry, filter: &PathFilter, explain: bool) -> bool {
+    let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+    let reason = filter.excluded_by(&relative_path(entry), is_dir);
+    if let Some(reason) = &reason {
+        if explain {
+            eprintln!("skipped {}: {}", entry.path().display(), reason);
+        }
+    }
+
+    reason.is_some()
+}
+
+/// Gets the path of the entry relative to the root it was found under. Since the walker
+/// joins each name onto its parent's path, that's just the last `depth` components. Paths
+/// that were given directly are returned as they were given.
+fn relative_path(entry: &DirEntry) -> PathBuf {
+    let path = entry.path();
+    if entry.depth() == 0 {
+        return path.strip_prefix(".").unwrap_or(path).to_path_buf();
+    }
+
+    let skip = path.components().count().saturating_sub(entry.depth());
+    path.components().skip(skip).collect()
+}
+
+/// The parsed `--check` list.
+struct Check<'a> {
+    extensions: &'a [String],
+    languages: Vec<&'static Language>,
+}
+
+impl<'a> Check<'a> {
+    fn new(to_check: &'a [String]) -> Self {
+        Check {
+            extensions: to_check,
+            languages: to_check
+                .iter()
+                .filter_map(|name| language::from_name(name))
+                .collect(),
+        }
+    }
+
+    /// Describes why a file in no language that was asked for was skipped.
+    fn skip_reason(&self) -> &'static str {
+        if self.extensions.is_empty() {
+            "no known language"
+        } else {
+            "extension and language not in --check"
+        }
+    }
+}
+
+/// Counts the lines in a single file. If the file hasn't changed since it was cached, the
+/// cached results are used instead of reading it again.
+///
+/// # Parameters
+/// - `entry`: The file.
+/// - `check`: The extensions and languages that the user asked for.
+/// - `cache`: The cache, if it's being used.
+/// - `opts`: The walk options, which say what to measure besides line counts.
+///
+/// # Returns
+/// The file's stats (or a note that it is binary), `None` if the file shouldn't be counted,
+/// or the error that kept it from being read.
+fn count_file(
+    entry: &DirEntry,
+    check: &Check,
+    cache: Option<&Cache>,
+    opts: &WalkOptions,
+) -> io::Result<Option<Outcome>> {
+    let (hash, explain) = (opts.hash, opts.explain);
+    let path = entry.path();
+    let stamp = Stamp::of(&fs::metadata(path)?);
+    let cached = cache.and_then(|cache| cache.get(path, &stamp));
+
+    // If the file has to be read, the reader is kept around to count with.
+    let mut opened = None;
+    let (detected, binary) = match cached {
+        Some(cached) => (
+            cached.detected.as_deref().and_then(language::from_name),
+            cached.binary,
+        ),
+        None => {
+            let file = open(path)?;
+            let detected = detect_language(path, &file.head_text);
+            let binary = file.encoding == Encoding::Binary;
+            opened = Some(file);
+            (detected, binary)
+        }
+    };
+
+    let mut new_entry = cache::Entry {
+        stamp,
+        detected: detected.map(|lang| lang.name.to_string()),
+        binary,
+        counted: None,
+        embedded: vec![],
+        hash: None,
+        complexity: None,
+        markers: None,
+    };
+
+    let lang = match choose_language(path, detected, check) {
+        Some(lang) => lang,
+        None => {
+            if explain {
+                eprintln!("skipped {}: {}", path.display(), check.skip_reason());
+            }
+
+            if let Some(cache) = cache {
+                cache.insert(path, new_entry);
+            }
+
+            return Ok(None);
+        }
+    };
+
+    if binary {
+        if explain {
+            eprintln!("skipped {}: binary file", path.display());
+        }
+
+        if let Some(cache) = cache {
+            cache.insert(path, new_entry);
+        }
+
+        return Ok(Some(Outcome::Binary(BinaryFile {
+            path: path.to_path_buf(),
+            bytes: stamp.size,
+        })));
+    }
+
+    // Cached counts are only good enough if they come with the kind of hash we want, with
+    // complexity if that's wanted too, and with markers for the same tags if those are.
+    let complexity = opts.complexity && lang.complexity.is_some();
+    let tags = opts.markers.as_deref();
+    let cached_counts = cached
+        .filter(|cached| hash.is_none() || cached.hash.map(|(mode, _)| mode) == hash)
+        .filter(|cached| !complexity || cached.complexity.is_some())
+        .filter(|cached| {
+            tags.is_none_or(|tags| cached.markers.as_ref().is_some_and(|(t, _)| t == tags))
+        })
+        .and_then(|cached| match &cached.counted {
+            Some((name, counts)) if name == lang.name => Some((
+                *counts,
+                cached
+                    .embedded
+                    .iter()
+                    .filter_map(|(name, counts)| {
+                        let language = language::from_name(name)?;
+                        Some(Embedded {
+                            language,
+                            counts: *counts,
+                        })
+                    })
+                    .collect(),
+                cached.hash,
+                cached.complexity.clone(),
+                cached.markers.clone(),
+            )),
+            _ => None,
+        });
+    let (counts, file_embedded, content_hash, file_complexity, file_markers) = match cached_counts {
+        Some(cached) => cached,
+        None => {
+            let file = match opened {
+                Some(file) => file,
+                None => open(path)?,
+            };
+            let measured = count_opened(file, stamp.size, lang, hash, complexity, tags)?;
+            (
+                measured.counts,
+                measured.embedded,
+                hash.zip(measured.hash),
+                measured.complexity,
+                tags.map(<[String]>::to_vec).zip(measured.markers),
+            )
+        }
+    };
+
+    if let Some(cache) = cache {
+        new_entry.counted = Some((lang.name.to_string(), counts));
+        new_entry.embedded = file_embedded
+            .iter()
+            .map(|e| (e.language.name.to_string(), e.counts))
+            .collect();
+        new_entry.hash = content_hash;
+        new_entry.complexity = file_complexity.clone();
+        new_entry.markers = file_markers.clone();
+        cache.insert(path, new_entry);
+    }
+
+    Ok(Some(Outcome::Counted(FileStats {
+        path: path.to_path_buf(),
+        rel_path: relative_path(entry),
+        language: lang,
+        counts,
+        bytes: stamp.size,
+        hash: content_hash
+            .filter(|(mode, _)| Some(*mode) == hash)
+            .map(|(_, content_hash)| content_hash),
+        complexity: file_complexity.filter(|_| complexity),
+        markers: file_markers
+            .filter(|_| tags.is_some())
+            .map(|(_, markers)| markers)
+            .unwrap_or_default(),
+        embedded: file_embedded,
+    })))
+}
+
+/// A file that has been opened, with its start already read in.
+struct Opened {
+    reader: BufReader<File>,
+    encoding: Encoding,
+    /// The start of the file, decoded as text.
+    head_text: String,
+}
+
+/// Opens a file and reads in enough of it to tell its encoding.
+fn open(path: &Path) -> io::Result<Opened> {
+    let mut reader = BufReader::with_capacity(encoding::SNIFF_LEN, File::open(path)?);
+    let head = reader.fill_buf()?;
+    let enc = encoding::detect(head);
+    let head_text = decode_head(head, enc);
+
+    Ok(Opened {
+        reader,
+        encoding: enc,
+        head_text,
+    })
+}
+
+/// Decodes the start of a file as text, to detect its language from.
+fn decode_head(head: &[u8], enc: Encoding) -> String {
+    match enc {
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            encoding::decode_utf16(&head[enc.bom_len()..], enc == Encoding::Utf16Le)
+        }
+        _ => String::from_utf8_lossy(&head[enc.bom_len()..]).into_owned(),
+    }
+}
+
+/// Files at least this big are memory-mapped (or, if that fails, read in `CHUNK_LEN`
+/// chunks) instead of being read through the small buffer used to sniff their encoding.
+const MMAP_THRESHOLD: u64 = 1 << 20;
+
+/// How much of a large file to read at a time when it can't be memory-mapped.
+const CHUNK_LEN: usize = 1 << 20;
+
+/// Everything that was measured about a file's text.
+struct Measured {
+    counts: LineCounts,
+    embedded: Vec<Embedded>,
+    hash: Option<ContentHash>,
+    complexity: Option<FileComplexity>,
+    markers: Option<Vec<Marker>>,
+}
+
+/// Counts the lines in a text file that has been opened, and hashes its contents, measures
+/// its complexity, and finds its markers along the way if asked to. UTF-16 files are hashed
+/// after being decoded, so they match their UTF-8 copies.
+///
+/// # Parameters
+/// - `file`: The file.
+/// - `size`: The size of the file, in bytes.
+/// - `lang`: The language to count it as.
+/// - `hash`: How to hash the file's contents, if at all.
+/// - `complexity`: Whether to measure the complexity of the file's code.
+/// - `tags`: The marker tags to look for in the file's comments, if any.
+///
+/// # Returns
+/// What was measured, or the error that kept the file from being read. Binary files can't
+/// be counted, so they're errors too (but they're normally caught before getting here).
+fn count_opened(
+    file: Opened,
+    size: u64,
+    lang: &'static Language,
+    hash: Option<Match>,
+    complexity: bool,
+    tags: Option<&[String]>,
+) -> io::Result<Measured> {
+    let Opened {
+        mut reader,
+        encoding: enc,
+        ..
+    } = file;
+    match enc {
+        Encoding::Binary => Err(io::Error::new(io::ErrorKind::InvalidData, "binary file")),
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            reader.consume(enc.bom_len());
+            let mut data = vec![];
+            reader.read_to_end(&mut data)?;
+            let text = encoding::decode_utf16(&data, enc == Encoding::Utf16Le);
+            Ok(count_in_memory(
+                text.as_bytes(),
+                lang,
+                hash,
+                complexity,
+                tags,
+            ))
+        }
+        Encoding::Utf8Bom | Encoding::Text => {
+            if size >= MMAP_THRESHOLD {
+                // Safety: the map is only read from, and only while counting. If another
+                // process shrinks the file in the meantime, reading past its new end can
+                // crash, which is the usual risk of memory-mapping (ripgrep takes it too).
+                if let Ok(map) = unsafe { Mmap::map(reader.get_ref()) } {
+                    let text = map.get(enc.bom_len()..).unwrap_or_default();
+                    return Ok(count_in_memory(text, lang, hash, complexity, tags));
+                }
+            }
+
+            reader.consume(enc.bom_len());
+            if lang.embedding.is_some() {
+                // Embedded code is found a line at a time from the whole text.
+                let mut data = vec![];
+                reader.read_to_end(&mut data)?;
+                return Ok(count_in_memory(&data, lang, hash, complexity, tags));
+            }
+
+            if size >= MMAP_THRESHOLD {
+                let reader = BufReader::with_capacity(CHUNK_LEN, reader);
+                return count_read(reader, lang, hash, complexity, tags);
+            }
+
+            count_read(reader, lang, hash, complexity, tags)
+        }
+    }
+}
+
+/// Counts text that is all in memory (along with any code embedded in it), then hashes it.
+fn count_in_memory(
+    text: &[u8],
+    lang: &'static Language,
+    hash: Option<Match>,
+    complexity: bool,
+    tags: Option<&[String]>,
+) -> Measured {
+    let mut sinks = sinks(lang, complexity, tags);
+    let (counts, embedded) = if lang.embedding.is_some() {
+        let split = embedded::count_into(text, lang, &mut sinks);
+        (split.counts, split.embedded)
+    } else {
+        let counts = match &mut sinks {
+            (None, None) => count::count_text(text, lang),
+            sinks => count::count_text_into(text, lang, sinks),
+        };
+        (counts, vec![])
+    };
+    let (analyzer, scanner) = sinks;
+    Measured {
+        counts,
+        embedded,
+        hash: hash.map(|mode| dedupe::hash(mode, text)),
+        complexity: analyzer.map(Analyzer::finish),
+        markers: scanner.map(Scanner::finish),
+    }
+}
+
+/// Counts text as it is read, hashing it on the way through, and stops at the first error.
+fn count_read<R: BufRead>(
+    reader: R,
+    lang: &'static Language,
+    hash: Option<Match>,
+    complexity: bool,
+    tags: Option<&[String]>,
+) -> io::Result<Measured> {
+    let mut reader = HashingReader::new(reader, hash);
+    let mut sinks = sinks(lang, complexity, tags);
+    let counts = match &mut sinks {
+        (None, None) => count::count_lines(&mut reader, lang)?,
+        sinks => count::count_lines_into(&mut reader, lang, sinks)?,
+    };
+    let (analyzer, scanner) = sinks;
+    Ok(Measured {
+        counts,
+        embedded: vec![],
+        hash: reader.finish(),
+        complexity: analyzer.map(Analyzer::finish),
+        markers: scanner.map(Scanner::finish),
+    })
+}
+
+/// Files inside an archive that are bigger than this aren't counted, so that a small archive
+/// that unpacks to something huge can't use up all the memory.
+const MAX_MEMBER_LEN: u64 = 64 << 20;
+
+/// Counts the files inside an archive. Each file gets a virtual path made of the archive's
+/// path and its own (see `archive::member_path`), which `--include` and `--exclude` are
+/// matched against. If ignore files are respected, the `.gitignore` and `.ignore` files
+/// inside the archive apply to the files around them, as they would if it were unpacked.
+/// Each file is counted as it's read, and only what was counted is kept until the end.
+///
+/// # Parameters
+/// - `entry`: The archive.
+/// - `kind`: What kind of archive it is.
+/// - `check`: The extensions and languages that the user asked for.
+/// - `opts`: The walk options.
+///
+/// # Returns
+/// What happened to each file inside the archive, along with the error that stopped the
+/// archive from being read to the end, if any.
+fn count_archive(entry: &DirEntry, kind: Kind, check: &Check, opts: &WalkOptions) -> Vec<Outcome> {
+    let path = entry.path();
+    let rel_path = relative_path(entry);
+    let explain = opts.explain;
+    let mut excluded_dirs = HashSet::new();
+    let mut ignores = IgnoreFiles::default();
+    let mut members = vec![];
+    let read = archive::read_members(path, kind, |name, contents| {
+        let is_ignore_file = opts.respect_ignore && archive::is_ignore_file(name);
+        let excluded = is_member_excluded(path, &rel_path, name, opts, &mut excluded_dirs);
+        if excluded && !is_ignore_file {
+            return;
+        }
+
+        let member = archive::member_path(path, name);
+        let rel_member = archive::member_path(&rel_path, name);
+        let counted = match contents {
+            Ok(contents) if is_ignore_file => match read_capped(contents) {
+                Ok(data) => {
+                    ignores.add(name, &data);
+                    count_member(member, rel_member, &mut &data[..], check, opts)
+                }
+                Err(e) => Ok(Outcome::failed(&member, e)),
+            },
+            Ok(contents) => count_member(member, rel_member, contents, check, opts),
+            Err(e) => Ok(Outcome::failed(&member, e)),
+        };
+        if !excluded {
+            members.push((name.to_string(), counted));
+        }
+    });
+
+    // The files that were read before the archive turned out to be broken still count.
+    let mut outcomes = vec![];
+    if let Err(e) = read {
+        outcomes.push(Outcome::failed(path, e));
+    }
+
+    // Ignore files can come after the files they apply to, so they're only checked once
+    // the whole archive has been read.
+    for (name, counted) in members {
+        let member = archive::member_path(path, &name);
+        if let Some(ignore_file) = ignores.ignored_by(&name) {
+            if explain {
+                let ignore_file = archive::member_path(path, ignore_file);
+                eprintln!(
+                    "skipped {}: ignored by {}",
+                    member.display(),
+                    ignore_file.display()
+                );
+            }
+
+            continue;
+        }
+
+        match counted {
+            Ok(outcome) => {
+                if explain && matches!(outcome, Outcome::Binary(_)) {
+                    eprintln!("skipped {}: binary file", member.display());
+                }
+
+                outcomes.push(outcome);
+            }
+            Err(reason) => {
+                if explain {
+                    eprintln!("skipped {}: {}", member.display(), reason);
+                }
+
+                outcomes.push(Outcome::Skipped(member));
+            }
+        }
+    }
+
+    outcomes
+}
+
+/// Checks a file inside an archive, and the directories it's in, against the name-based
+/// rules in `opts.filter`.
+///
+/// # Parameters
+/// - `archive`: The path of the archive.
+/// - `rel_archive`: The path of the archive relative to its root.
+/// - `name`: The path of the file inside the archive.
+/// - `opts`: The walk options.
+/// - `excluded_dirs`: The directories inside the archive that have already been excluded,
+///   so each one is only explained once.
+///
+/// # Returns
+/// Whether the file should be skipped.
+fn is_member_excluded(
+    archive: &Path,
+    rel_archive: &Path,
+    name: &str,
+    opts: &WalkOptions,
+    excluded_dirs: &mut HashSet<String>,
+) -> bool {
+    let dirs = name.match_indices('/').map(|(i, _)| &name[..i]);
+    for dir in dirs {
+        if excluded_dirs.contains(dir) {
+            return true;
+        }
+
+        let reason = opts
+            .filter
+            .excluded_by(&archive::member_path(rel_archive, dir), true);
+        if let Some(reason) = reason {
+            if opts.explain {
+                let path = archive::member_path(archive, dir);
+                eprintln!("skipped {}: {}", path.display(), reason);
+            }
+
+            excluded_dirs.insert(dir.to_string());
+            return true;
+        }
+    }
+
+    let reason = opts
+        .filter
+        .excluded_by(&archive::member_path(rel_archive, name), false);
+    if let Some(reason) = &reason {
+        if opts.explain {
+            let path = archive::member_path(archive, name);
+            eprintln!("skipped {}: {}", path.display(), reason);
+        }
+    }
+
+    reason.is_some()
+}
+
+/// Reads a file inside an archive into memory, unless it's bigger than `MAX_MEMBER_LEN`.
+fn read_capped(contents: &mut dyn Read) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+    read_capped_into(contents, &mut data)?;
+    Ok(data)
+}
+
+/// Reads the rest of a file inside an archive onto the end of what has been read of it so
+/// far, unless that makes it bigger than `MAX_MEMBER_LEN`.
+fn read_capped_into(contents: &mut dyn Read, data: &mut Vec<u8>) -> io::Result<()> {
+    let limit = (MAX_MEMBER_LEN + 1).saturating_sub(data.len() as u64);
+    Read::take(contents, limit).read_to_end(data)?;
+    if data.len() as u64 > MAX_MEMBER_LEN {
+        let reason = format!("bigger than {} MiB", MAX_MEMBER_LEN >> 20);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, reason));
+    }
+
+    Ok(())
+}
+
+/// Counts the lines in a file inside an archive as it's read. Only as much of the file is
+/// read as is needed: none of it if its name says it isn't counted, its start if its
+/// language has to be detected from a modeline or `#!` line, and the whole of it (up to
+/// `MAX_MEMBER_LEN`) only if it's counted. Binary files are read through without being kept.
+///
+/// # Parameters
+/// - `path`: The file's virtual path.
+/// - `rel_path`: The file's virtual path relative to the root it was found under.
+/// - `contents`: A reader for the file's contents.
+/// - `check`: The extensions and languages that the user asked for.
+/// - `opts`: The walk options, which say what to measure besides line counts.
+///
+/// # Returns
+/// The file's stats (or a note that it is binary, or the error that kept it from being
+/// read), or why it wasn't counted.
+fn count_member(
+    path: PathBuf,
+    rel_path: PathBuf,
+    contents: &mut dyn Read,
+    check: &Check,
+    opts: &WalkOptions,
+) -> Result<Outcome, String> {
+    let named = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .and_then(language::from_file_name);
+    if named.is_some() && choose_language(&path, named, check).is_none() {
+        return Err(check.skip_reason().to_string());
+    }
+
+    let mut data = vec![];
+    let head = Read::take(&mut *contents, encoding::SNIFF_LEN as u64).read_to_end(&mut data);
+    if let Err(e) = head {
+        return Ok(Outcome::failed(&path, e));
+    }
+
+    let enc = encoding::detect(&data);
+    let detected = detect_language(&path, &decode_head(&data, enc));
+    let lang = choose_language(&path, detected, check).ok_or_else(|| check.skip_reason())?;
+    if enc == Encoding::Binary {
+        return Ok(match io::copy(contents, &mut io::sink()) {
+            Ok(rest) => Outcome::Binary(BinaryFile {
+                path,
+                bytes: data.len() as u64 + rest,
+            }),
+            Err(e) => Outcome::failed(&path, e),
+        });
+    }
+
+    if let Err(e) = read_capped_into(contents, &mut data) {
+        return Ok(Outcome::failed(&path, e));
+    }
+
+    let bytes = data.len() as u64;
+    let complexity = opts.complexity && lang.complexity.is_some();
+    let tags = opts.markers.as_deref();
+    let measured = match enc {
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let text = encoding::decode_utf16(&data[enc.bom_len()..], enc == Encoding::Utf16Le);
+            count_in_memory(text.as_bytes(), lang, opts.hash, complexity, tags)
+        }
+        // Binary files have already been read through above.
+        _ => count_in_memory(&data[enc.bom_len()..], lang, opts.hash, complexity, tags),
+    };
+
+    Ok(Outcome::Counted(FileStats {
+        path,
+        rel_path,
+        language: lang,
+        counts: measured.counts,
+        bytes,
+        hash: measured.hash,
+        complexity: measured.complexity,
+        markers: measured.markers.unwrap_or_default(),
+        embedded: measured.embedded,
+    }))
+}
+
+/// Makes the sinks that the code and comments are passed to as they're counted: an
+/// analyzer, if complexity is wanted and the language has rules for it, and a marker
+/// scanner, if there are tags to look for.
+fn sinks<'a>(
+    lang: &'static Language,
+    complexity: bool,
+    tags: Option<&'a [String]>,
+) -> (Option<Analyzer>, Option<Scanner<'a>>) {
+    (
+        lang.complexity.filter(|_| complexity).map(Analyzer::new),
+        tags.map(Scanner::new),
+    )
+}
+
+/// Detects a file's language. In order, this looks at:
+/// 1. the file's name, for well-known files like `Makefile`;
+/// 2. an editor modeline near the start of the file;
+/// 3. the file's extension; and
+/// 4. the file's `#!` line.
+///
+/// # Parameters
+/// - `path`: The path to the file.
+/// - `head`: The start of the file.
+///
+/// # Returns
+/// The language, or `None` if it couldn't be detected.
+fn detect_language(path: &Path, head: &str) -> Option<&'static Language> {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .and_then(language::from_file_name)
+        .or_else(|| language::from_modeline(head))
+        .or_else(|| {
+            path.extension()
+                .and_then(OsStr::to_str)
+                .and_then(language::from_extension)
+        })
+        .or_else(|| language::from_shebang(head))
+}
+
+/// Decides which language a file should be counted as.
+///
+/// If nothing was given to check, every file with a detected language is counted.
+/// Otherwise, only files whose extension or detected language was asked for are counted,
+/// and any of those whose language couldn't be detected are treated as plain text.
+///
+/// # Parameters
+/// - `path`: The path to the file.
+/// - `lang`: The language detected by `detect_language`, if any.
+/// - `check`: The extensions and languages that the user asked for.
+///
+/// # Returns
+/// The language, or `None` if the file shouldn't be counted.
+fn choose_language(
+    path: &Path,
+    lang: Option<&'static Language>,
+    check: &Check,
+) -> Option<&'static Language> {
+    if check.extensions.is_empty() {
+        return lang;
+    }
+
+    let ext_matches = path
+        .extension()
+        .is_some_and(|ext| check.extensions.iter().any(|x| x.as_str() == ext));
+    let lang_matches =
+        lang.is_some_and(|lang| check.languages.iter().any(|x| std::ptr::eq(*x, lang)));
+    if !ext_matches && !lang_matches {
+        return None;
+    }
+
+    Some(lang.unwrap_or(&language::PLAIN_TEXT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> WalkOptions {
+        WalkOptions {
+            filter: Arc::new(PathFilter::new(vec![], true, &[], &[]).unwrap()),
+            to_check: vec![],
+            respect_ignore: true,
+            follow_symlinks: false,
+            threads: 1,
+            explain: false,
+            cache_dir: None,
+            rebuild_cache: false,
+            hash: None,
+            complexity: false,
+            markers: None,
+            archives: true,
+        }
+    }
+
+    fn count(name: &str, contents: &mut dyn Read, check: &[String]) -> Result<Outcome, String> {
+        let path = archive::member_path(Path::new("a.zip"), name);
+        count_member(path.clone(), path, contents, &Check::new(check), &options())
+    }
+
+    #[test]
+    fn archive_members_are_only_read_as_far_as_needed() {
+        // These never end, so they can only be skipped if they aren't read to the end.
+        let rs = ["rs".to_string()];
+        assert!(count("Makefile", &mut io::repeat(b'a'), &rs).is_err());
+        assert!(count("logo.png", &mut io::repeat(0), &[]).is_err());
+
+        let outcome = count("blob.rs", &mut Read::take(io::repeat(0), 1 << 20), &[]);
+        assert!(
+            matches!(outcome, Ok(Outcome::Binary(BinaryFile { bytes, .. })) if bytes == 1 << 20)
+        );
+
+        let outcome = count("big.rs", &mut io::repeat(b'a'), &[]);
+        assert!(
+            matches!(outcome, Ok(Outcome::Failed(error)) if error.reason == "bigger than 64 MiB")
+        );
+
+        let outcome = count("lib.rs", &mut &b"fn main() {}\n"[..], &[]);
+        assert!(matches!(outcome, Ok(Outcome::Counted(stats)) if stats.counts.code == 1));
+    }
+}
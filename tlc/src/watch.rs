@@ -0,0 +1,188 @@
+use crate::walk::{self, Counted, WalkOptions};
+use crate::Root;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// How long things have to be quiet after an event before counting again, so that a burst
+/// of events (like an editor writing a temporary file and renaming it) causes one update
+/// instead of several.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// The longest to keep waiting for things to be quiet, so that a steady stream of events
+/// still gets shown.
+const MAX_WAIT: Duration = Duration::from_secs(2);
+
+/// One of the paths that make up a root.
+struct Watched {
+    /// The path as it was given, which is what the walker joins names onto.
+    given: PathBuf,
+    /// Where the path actually is, which is what events are reported against.
+    canonical: PathBuf,
+    is_dir: bool,
+}
+
+impl Watched {
+    /// Turns the path from an event back into the path the walker would reach it through.
+    fn given_path(&self, path: &Path) -> Option<PathBuf> {
+        if path == self.canonical {
+            return Some(self.given.clone());
+        }
+
+        if !self.is_dir {
+            return None;
+        }
+
+        let rel = path.strip_prefix(&self.canonical).ok()?;
+        Some(self.given.join(rel))
+    }
+}
+
+/// Counts every root, then watches them and counts them again whenever anything under them
+/// changes. Only the files that changed are read again; everything else keeps its counts
+/// from before.
+///
+/// # Parameters
+/// - `roots`: The roots.
+/// - `opts`: The walk options.
+/// - `show`: Called with the counts of every root after the first scan and after every
+///   change, along with the number of paths that changed (0 for the first scan).
+///
+/// # Returns
+/// Only returns if watching fails, or `show` returns an error.
+pub fn run<F>(roots: &[Root], opts: &WalkOptions, mut show: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(&[(String, Counted)], usize) -> io::Result<()>,
+{
+    let mut counted = crate::walk_roots(roots, opts);
+    show(&counted, 0)?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let watched = roots
+        .iter()
+        .map(|root| watched_paths(&root.paths))
+        .collect::<Vec<_>>();
+    watch_all(&mut watcher, watched.iter().flatten())?;
+
+    loop {
+        let (changed, rescan) = next_changes(&rx)?;
+        for (root, ((_, root_counted), root_watched)) in
+            roots.iter().zip(counted.iter_mut().zip(watched.iter()))
+        {
+            if rescan {
+                *root_counted = walk::count_files(&root.paths, opts);
+                continue;
+            }
+
+            let root_changed = changed
+                .iter()
+                .flat_map(|path| root_watched.iter().filter_map(move |w| w.given_path(path)))
+                .collect::<HashSet<_>>();
+            if !root_changed.is_empty() {
+                *root_counted = walk::recount_files(&root.paths, opts, root_counted, &root_changed);
+            }
+        }
+
+        show(&counted, changed.len())?;
+    }
+}
+
+/// Works out where each path is. Paths that don't exist can't be watched, so they're left
+/// out.
+fn watched_paths(paths: &[PathBuf]) -> Vec<Watched> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            let canonical = fs::canonicalize(path).ok()?;
+            Some(Watched {
+                given: path.clone(),
+                is_dir: canonical.is_dir(),
+                canonical,
+            })
+        })
+        .collect()
+}
+
+/// Subscribes to changes under every path. Directories are watched recursively. Files are
+/// watched through the directory they're in, since editors often save by replacing the
+/// file, which would end a watch on the file itself.
+fn watch_all<'a, W: Watcher>(
+    watcher: &mut W,
+    watched: impl Iterator<Item = &'a Watched> + Clone,
+) -> notify::Result<()> {
+    let dirs = watched
+        .clone()
+        .filter(|w| w.is_dir)
+        .map(|w| w.canonical.clone())
+        .collect::<HashSet<_>>();
+    for dir in &dirs {
+        // A directory under another watched one is already covered.
+        if !dirs
+            .iter()
+            .any(|other| other != dir && dir.starts_with(other))
+        {
+            watcher.watch(dir, RecursiveMode::Recursive)?;
+        }
+    }
+
+    let parents = watched
+        .filter(|w| !w.is_dir)
+        .filter_map(|w| w.canonical.parent().map(Path::to_path_buf))
+        .filter(|parent| !dirs.iter().any(|dir| parent.starts_with(dir)))
+        .collect::<HashSet<_>>();
+    for parent in parents {
+        watcher.watch(&parent, RecursiveMode::NonRecursive)?;
+    }
+
+    Ok(())
+}
+
+/// Waits for something to change, then collects events until things are quiet again.
+///
+/// # Returns
+/// The paths that changed, and whether everything has to be counted again because some
+/// events were lost.
+fn next_changes(
+    rx: &mpsc::Receiver<notify::Result<Event>>,
+) -> Result<(HashSet<PathBuf>, bool), Box<dyn Error>> {
+    let mut changed = HashSet::new();
+    let mut rescan = false;
+    let mut deadline = None;
+    loop {
+        let event = match deadline {
+            None => rx.recv()?,
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+
+                match rx.recv_timeout(SETTLE_TIME.min(deadline - now)) {
+                    Ok(event) => event,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+
+        match event {
+            Ok(event) if matches!(event.kind, EventKind::Access(_)) => continue,
+            Ok(event) => {
+                rescan |= event.need_rescan();
+                changed.extend(event.paths);
+            }
+            // The watcher couldn't tell us what changed (e.g. its queue overflowed).
+            Err(_) => rescan = true,
+        }
+
+        deadline.get_or_insert_with(|| Instant::now() + MAX_WAIT);
+    }
+
+    Ok((changed, rescan))
+}
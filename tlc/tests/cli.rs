@@ -0,0 +1,168 @@
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+fn tlc(dir: &Path, args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_tlc"))
+        .current_dir(dir)
+        .arg("--no-cache")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    output
+}
+
+fn json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn csv_report() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mixed");
+    let output = tlc(&fixture, &["-f", "csv", "--check", "rs"]);
+    let csv = String::from_utf8(output.stdout).unwrap();
+    let lines = csv.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[0],
+        "kind,path,language,files,lines,code,comment,blank,bytes,reason"
+    );
+    assert!(lines.contains(&"file,./src/main.rs,Rust,1,11,5,3,3,209,"));
+    assert!(lines.contains(&"total,,,1,11,5,3,3,209,"));
+}
+
+#[test]
+fn csv_columns_keep_their_place() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/projects");
+    let output = tlc(
+        &fixture,
+        &["-f", "csv", "--markers", "--by-owner", "--by-project"],
+    );
+    let csv = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        csv.lines().next().unwrap(),
+        "kind,path,language,files,lines,code,comment,blank,bytes,\
+         line,tag,owner,count,text,code_owner,project,project_kind,reason"
+    );
+
+    let output = tlc(&fixture, &["-f", "csv", "--complexity", "--by-project"]);
+    let csv = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        csv.lines().next().unwrap(),
+        "kind,path,language,files,lines,code,comment,blank,bytes,\
+         function,line,functions,depth,complexity,project,project_kind,reason"
+    );
+}
+
+#[test]
+fn files_from_standard_input() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mixed");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_tlc"))
+        .current_dir(&fixture)
+        .args(["--no-cache", "-f", "json", "--files-from", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"src/main.rs\0Makefile\0")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let report = json(&output);
+    assert_eq!(report["roots"][0]["root"], "<stdin>");
+    assert_eq!(report["total"]["files"], 2);
+    assert_eq!(report["total"]["lines"], 15);
+}
+
+#[test]
+fn snapshots_diff_and_history() {
+    let project = tempfile::tempdir().unwrap();
+    let snapshots = tempfile::tempdir().unwrap();
+    let snapshot_dir = snapshots.path().to_str().unwrap();
+    let dir = project.path();
+
+    fs::write(dir.join("a.rs"), "fn a() {}\n").unwrap();
+    fs::write(dir.join("b.py"), "b = 1\n").unwrap();
+    tlc(
+        dir,
+        &["--snapshot", "before", "--snapshot-dir", snapshot_dir],
+    );
+
+    fs::write(dir.join("a.rs"), "// a\nfn a() {}\n\nfn b() {}\n").unwrap();
+    fs::remove_file(dir.join("b.py")).unwrap();
+    fs::write(dir.join("c.py"), "c = 1\n").unwrap();
+    tlc(
+        dir,
+        &["--snapshot", "after", "--snapshot-dir", snapshot_dir],
+    );
+
+    let diff = json(&tlc(
+        dir,
+        &[
+            "diff",
+            "before",
+            "after",
+            "--snapshot-dir",
+            snapshot_dir,
+            "-f",
+            "json",
+        ],
+    ));
+    let files = diff["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| (f["path"].as_str().unwrap(), f["status"].as_str().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        files,
+        [
+            ("./a.rs", "changed"),
+            ("./b.py", "removed"),
+            ("./c.py", "added")
+        ]
+    );
+    assert_eq!(diff["total"]["delta"]["lines"], 3);
+    assert_eq!(diff["total"]["delta"]["files"], 0);
+
+    let history = json(&tlc(
+        dir,
+        &["history", "--snapshot-dir", snapshot_dir, "-f", "json"],
+    ));
+    let snapshots = history["snapshots"].as_array().unwrap();
+    assert_eq!(snapshots.len(), 2);
+    assert_eq!(snapshots[0]["label"], "before");
+    assert_eq!(snapshots[1]["lines"], 5);
+    assert_eq!(snapshots[1]["change"]["lines"], 3);
+}
+
+#[test]
+fn strict_fails_on_unreadable_paths() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mixed");
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_tlc"))
+            .current_dir(&fixture)
+            .args(["--no-cache", "-f", "json", "src", "missing"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    // Without --strict, the missing path is only listed.
+    let output = run(&[]);
+    assert!(output.status.success());
+    let report = json(&output);
+    assert_eq!(report["errors"][0]["path"], "missing");
+    assert_eq!(report["total"]["files"], 2);
+
+    let output = run(&["--strict"]);
+    assert!(!output.status.success());
+    assert_eq!(json(&output)["errors"].as_array().unwrap().len(), 1);
+}
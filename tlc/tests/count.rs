@@ -0,0 +1,931 @@
+use std::path::{Path, PathBuf};
+use tlc::{Match, Options, Report, Root, SortKey};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn count(options: &Options) -> Report {
+    tlc::count(&[fixture("mixed")], options).unwrap()
+}
+
+/// The paths of every listed file, relative to the fixture.
+fn files(report: &Report) -> Vec<String> {
+    let prefix = fixture("mixed").display().to_string();
+    report
+        .files
+        .iter()
+        .map(|file| file.path[prefix.len() + 1..].replace('\\', "/"))
+        .collect()
+}
+
+fn language_lines(report: &Report) -> Vec<(&str, usize, usize, usize)> {
+    report
+        .languages
+        .iter()
+        .map(|l| {
+            let t = &l.totals;
+            (l.language.as_str(), t.code, t.comment, t.blank)
+        })
+        .collect()
+}
+
+#[test]
+fn counts_every_known_language() {
+    let report = count(&Options::default());
+    assert_eq!(
+        files(&report),
+        [
+            "Makefile",
+            "notes.txt",
+            "scripts/deploy",
+            "src/main.rs",
+            "src/util.py",
+            "vendor/lib.py",
+        ]
+    );
+    assert_eq!(
+        language_lines(&report),
+        [
+            ("Python", 9, 3, 3),
+            ("Rust", 5, 3, 3),
+            ("Makefile", 2, 1, 1),
+            ("Plain Text", 1, 0, 0),
+        ]
+    );
+    assert_eq!(report.total.files, 6);
+    assert_eq!(report.total.lines, 31);
+}
+
+#[test]
+fn binary_files_are_listed_separately() {
+    let report = count(&Options::default());
+    assert_eq!(report.binary.len(), 1);
+    assert!(report.binary[0].path.ends_with("blob.c"));
+}
+
+#[test]
+fn ignore_files_can_be_turned_off() {
+    let report = count(&Options {
+        respect_ignore: false,
+        ..Options::default()
+    });
+    assert!(files(&report).contains(&"generated/gen.rs".to_string()));
+    assert_eq!(report.total.files, 7);
+}
+
+#[test]
+fn check_selects_languages_by_name_or_extension() {
+    let report = count(&Options {
+        check: vec!["python".to_string()],
+        ..Options::default()
+    });
+    assert_eq!(
+        files(&report),
+        ["scripts/deploy", "src/util.py", "vendor/lib.py"]
+    );
+
+    let report = count(&Options {
+        check: vec!["rs".to_string(), "txt".to_string()],
+        ..Options::default()
+    });
+    assert_eq!(files(&report), ["notes.txt", "src/main.rs"]);
+}
+
+#[test]
+fn include_and_exclude_globs() {
+    let report = count(&Options {
+        include: vec!["*.py".to_string()],
+        exclude: vec!["vendor".to_string()],
+        ..Options::default()
+    });
+    assert_eq!(files(&report), ["src/util.py"]);
+
+    let report = count(&Options {
+        ignore_dirs: vec!["src".into(), "scripts".into()],
+        ..Options::default()
+    });
+    assert_eq!(files(&report), ["Makefile", "notes.txt", "vendor/lib.py"]);
+}
+
+#[test]
+fn invalid_globs_are_errors() {
+    let options = Options {
+        exclude: vec!["a[".to_string()],
+        ..Options::default()
+    };
+    assert!(tlc::count(&[fixture("mixed")], &options).is_err());
+}
+
+#[test]
+fn each_root_gets_a_subtotal() {
+    let mixed = fixture("mixed");
+    let roots = [
+        Root::from_path(mixed.join("src")),
+        Root {
+            name: "loose".to_string(),
+            paths: vec![mixed.join("Makefile"), mixed.join("notes.txt")],
+        },
+    ];
+    let report = tlc::count_roots(&roots, &Options::default()).unwrap();
+
+    let roots = report
+        .roots
+        .iter()
+        .map(|r| (r.root.as_str(), r.totals.files, r.totals.lines))
+        .collect::<Vec<_>>();
+    assert_eq!(roots[0].1, 2);
+    assert_eq!(roots[0].2, 20);
+    assert_eq!(roots[1], ("loose", 2, 5));
+    assert_eq!(report.total.lines, 25);
+}
+
+#[test]
+fn sorting_and_trimming_only_change_the_listing() {
+    let report = count(&Options {
+        sort: SortKey::Lines,
+        top: Some(2),
+        ..Options::default()
+    });
+    assert_eq!(files(&report), ["src/main.rs", "src/util.py"]);
+    assert_eq!(report.total.files, 6);
+}
+
+#[test]
+fn tree_rolls_up_directories() {
+    let report = count(&Options {
+        tree: true,
+        depth: Some(1),
+        ..Options::default()
+    });
+    let tree = report.tree.unwrap();
+    let dirs = tree
+        .iter()
+        .map(|d| (d.path.as_str(), d.depth, d.totals.lines))
+        .collect::<Vec<_>>();
+    // Directories come after their parent, largest first.
+    assert_eq!(
+        dirs,
+        [
+            ("", 0, 31),
+            ("src", 1, 20),
+            ("scripts", 1, 4),
+            ("vendor", 1, 2)
+        ]
+    );
+}
+
+#[test]
+fn cached_results_match_fresh_ones() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let options = Options {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        ..Options::default()
+    };
+
+    let fresh = serde_json::to_string(&count(&Options::default())).unwrap();
+    assert_eq!(serde_json::to_string(&count(&options)).unwrap(), fresh);
+    assert_eq!(serde_json::to_string(&count(&options)).unwrap(), fresh);
+}
+
+#[test]
+fn recounting_keeps_the_cache_of_unchanged_files() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let options = Options {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        ..Options::default()
+    };
+    let entries = || {
+        let file = std::fs::read_dir(cache_dir.path()).unwrap().next().unwrap();
+        let text = std::fs::read_to_string(file.unwrap().path()).unwrap();
+        let cache = serde_json::from_str::<serde_json::Value>(&text).unwrap();
+        cache["entries"].as_object().unwrap().len()
+    };
+
+    let paths = [fixture("mixed")];
+    let opts = options.walk_options().unwrap();
+    let counted = tlc::walk::count_files(&paths, &opts);
+    let before = entries();
+    assert!(before > 1);
+
+    let changed = std::iter::once(paths[0].join("Makefile")).collect();
+    tlc::walk::recount_files(&paths, &opts, &counted, &changed);
+    assert_eq!(entries(), before);
+}
+
+/// The paths in each group of copies, relative to the fixture.
+fn duplicate_groups(report: &Report, fixture_name: &str) -> Vec<Vec<String>> {
+    let prefix = fixture(fixture_name).display().to_string();
+    let dups = report.duplicates.as_ref().unwrap();
+    dups.groups
+        .iter()
+        .map(|group| {
+            group
+                .files
+                .iter()
+                .map(|file| file.path[prefix.len() + 1..].replace('\\', "/"))
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn duplicates_are_listed_without_changing_totals() {
+    let options = Options {
+        duplicates: Some(Match::Exact),
+        ..Options::default()
+    };
+    let report = tlc::count(&[fixture("copies")], &options).unwrap();
+    // Empty files are never copies of each other.
+    assert_eq!(
+        duplicate_groups(&report, "copies"),
+        [["src/add.rs", "vendor/add.rs"]]
+    );
+
+    let dups = report.duplicates.unwrap();
+    assert!(!dups.deduplicated);
+    assert_eq!((dups.copies.files, dups.copies.lines), (1, 4));
+    assert_eq!(report.total.files, 6);
+}
+
+#[test]
+fn near_duplicates_ignore_whitespace() {
+    let options = Options {
+        duplicates: Some(Match::Whitespace),
+        ..Options::default()
+    };
+    let report = tlc::count(&[fixture("copies")], &options).unwrap();
+    assert_eq!(
+        duplicate_groups(&report, "copies"),
+        [["src/add.rs", "src/add_reformatted.rs", "vendor/add.rs"]]
+    );
+}
+
+#[test]
+fn dedupe_counts_the_first_copy_only() {
+    let copies = fixture("copies");
+    let roots = [
+        Root::from_path(copies.join("src")),
+        Root::from_path(copies.join("vendor")),
+    ];
+    let options = Options {
+        dedupe: true,
+        ..Options::default()
+    };
+    let report = tlc::count_roots(&roots, &options).unwrap();
+
+    assert!(report.duplicates.as_ref().unwrap().deduplicated);
+    assert_eq!(report.total.files, 5);
+    assert_eq!(report.roots[0].totals.files, 4);
+    assert_eq!(report.roots[1].totals.files, 1);
+    assert!(!report
+        .files
+        .iter()
+        .any(|f| f.path.ends_with("vendor/add.rs")));
+}
+
+#[test]
+fn complexity_lists_the_most_complex_functions() {
+    let options = Options {
+        complexity: true,
+        complexity_top: 3,
+        sort: SortKey::Complexity,
+        ..Options::default()
+    };
+    let report = tlc::count(&[fixture("complex")], &options).unwrap();
+    let prefix = fixture("complex").display().to_string();
+    let relative = |path: &str| path[prefix.len() + 1..].replace('\\', "/");
+
+    // Files in languages without complexity rules are still counted, but sort last.
+    let files = report
+        .files
+        .iter()
+        .map(|f| (relative(&f.path), f.complexity.map(|c| c.complexity)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        files,
+        [
+            ("parse.c".to_string(), Some(6)),
+            ("shapes.py".to_string(), Some(6)),
+            ("notes.txt".to_string(), None),
+        ]
+    );
+
+    let complexity = report.complexity.unwrap();
+    assert_eq!(complexity.files.len(), 2);
+    let functions = complexity
+        .functions
+        .iter()
+        .map(|f| (relative(&f.path), f.name.as_str(), f.line, f.complexity))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        functions,
+        [
+            ("parse.c".to_string(), "parse", 4, 5),
+            ("shapes.py".to_string(), "describe", 9, 4),
+            ("shapes.py".to_string(), "__init__", 2, 1),
+        ]
+    );
+}
+
+#[test]
+fn cached_complexity_matches_fresh() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let count = |options: &Options| {
+        let report = tlc::count(&[fixture("complex")], options).unwrap();
+        serde_json::to_string(&report).unwrap()
+    };
+    let cached = Options {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        ..Options::default()
+    };
+    let measured = Options {
+        complexity: true,
+        ..Options::default()
+    };
+    let cached_and_measured = Options {
+        cache_dir: Some(cache_dir.path().to_path_buf()),
+        complexity: true,
+        ..Options::default()
+    };
+
+    // Counts cached without complexity aren't enough once it's asked for.
+    let plain = count(&Options::default());
+    assert_eq!(count(&cached), plain);
+    let fresh = count(&measured);
+    assert_eq!(count(&cached_and_measured), fresh);
+    assert_eq!(count(&cached_and_measured), fresh);
+}
+
+/// Packs the given files into a `.tar.gz`, a `.zip`, and (for the first one) a `.gz` in a
+/// new directory.
+fn archives(files: &[(&str, &str)]) -> tempfile::TempDir {
+    use std::fs::File;
+    use std::io::Write;
+
+    let dir = tempfile::tempdir().unwrap();
+    let gz = flate2::write::GzEncoder::new(
+        File::create(dir.path().join("release.tar.gz")).unwrap(),
+        flate2::Compression::default(),
+    );
+    let mut tar = tar::Builder::new(gz);
+    let mut zip = zip::ZipWriter::new(File::create(dir.path().join("export.zip")).unwrap());
+    for (name, contents) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, name, contents.as_bytes())
+            .unwrap();
+
+        zip.start_file(*name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(contents.as_bytes()).unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap();
+    zip.finish().unwrap();
+
+    let (name, contents) = files[0];
+    let name = Path::new(name).file_name().unwrap().to_str().unwrap();
+    let mut gz = flate2::write::GzEncoder::new(
+        File::create(dir.path().join(format!("{}.gz", name))).unwrap(),
+        flate2::Compression::default(),
+    );
+    gz.write_all(contents.as_bytes()).unwrap();
+    gz.finish().unwrap();
+    dir
+}
+
+#[test]
+fn archives_are_counted_as_if_unpacked() {
+    let dir = archives(&[
+        ("src/lib.rs", "// Library.\nfn main() {}\n"),
+        ("src/generated.rs", "fn generated() {}\n"),
+        ("vendor/dep.py", "x = 1\n"),
+        ("blob.txt", "\u{0}\u{1}"),
+        ("src/.gitignore", "generated.rs\n"),
+    ]);
+    let options = Options {
+        exclude: vec!["vendor".to_string()],
+        archives: true,
+        ..Options::default()
+    };
+    let report = tlc::count(&[dir.path().to_path_buf()], &options).unwrap();
+
+    let prefix = dir.path().display().to_string();
+    let paths = |paths: Vec<&String>| {
+        paths
+            .into_iter()
+            .map(|path| path[prefix.len() + 1..].to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        paths(report.files.iter().map(|f| &f.path).collect()),
+        [
+            "export.zip!/src/lib.rs",
+            "lib.rs.gz!/lib.rs",
+            "release.tar.gz!/src/lib.rs",
+        ]
+    );
+    assert_eq!(
+        paths(report.binary.iter().map(|f| &f.path).collect()),
+        ["export.zip!/blob.txt", "release.tar.gz!/blob.txt"]
+    );
+    assert_eq!(language_lines(&report), [("Rust", 3, 3, 0)]);
+
+    // Without --archives, the archives are just binary files.
+    let report = tlc::count(&[dir.path().to_path_buf()], &Options::default()).unwrap();
+    assert!(report.files.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn unreadable_paths_are_listed_with_the_reason() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("lib.rs"), "fn main() {}\n").unwrap();
+    std::os::unix::fs::symlink("gone.rs", dir.path().join("broken.rs")).unwrap();
+
+    let options = Options {
+        follow_symlinks: true,
+        ..Options::default()
+    };
+    let report = tlc::count(&[dir.path().to_path_buf()], &options).unwrap();
+    assert_eq!(report.total.files, 1);
+    assert_eq!(report.errors.len(), 1);
+    assert!(report.errors[0].path.ends_with("broken.rs"));
+    assert!(!report.errors[0].reason.is_empty());
+
+    // Without following links, the broken link is skipped like any other.
+    let report = tlc::count(&[dir.path().to_path_buf()], &Options::default()).unwrap();
+    assert!(report.errors.is_empty());
+}
+
+#[test]
+fn markers_are_listed_and_summed_up() {
+    let options = Options {
+        markers: true,
+        marker_tags: vec!["NOTE".to_string()],
+        ..Options::default()
+    };
+    let report = tlc::count(&[fixture("markers")], &options).unwrap();
+    let markers = report.markers.unwrap();
+
+    let prefix = fixture("markers").display().to_string();
+    let listing = markers
+        .markers
+        .iter()
+        .map(|m| {
+            (
+                m.path[prefix.len() + 1..].replace('\\', "/"),
+                m.line,
+                m.tag.as_str(),
+                m.owner.as_deref(),
+                m.text.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        listing,
+        [
+            (
+                "scripts/build.py".to_string(),
+                1,
+                "NOTE",
+                None,
+                "run from the repository root"
+            ),
+            (
+                "scripts/build.py".to_string(),
+                2,
+                "TODO",
+                Some("bob"),
+                "cache the downloads"
+            ),
+            (
+                "src/lib.rs".to_string(),
+                1,
+                "TODO",
+                Some("alice"),
+                "split this module up"
+            ),
+            (
+                "src/lib.rs".to_string(),
+                3,
+                "FIXME",
+                None,
+                "count characters, not bytes"
+            ),
+            (
+                "src/lib.rs".to_string(),
+                7,
+                "HACK",
+                None,
+                "the parser can't handle"
+            ),
+        ]
+    );
+
+    let tags = markers
+        .tags
+        .iter()
+        .map(|t| (t.tag.as_str(), t.count))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tags,
+        [
+            ("TODO", 2),
+            ("FIXME", 1),
+            ("HACK", 1),
+            ("XXX", 0),
+            ("NOTE", 1)
+        ]
+    );
+    let dirs = markers
+        .directories
+        .iter()
+        .map(|d| (d.path.ends_with("src"), d.tag.as_str(), d.count))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        dirs,
+        [
+            (false, "TODO", 1),
+            (false, "NOTE", 1),
+            (true, "TODO", 1),
+            (true, "FIXME", 1),
+            (true, "HACK", 1),
+        ]
+    );
+}
+
+#[test]
+fn cached_markers_follow_the_tags() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let count = |marker_tags: &[&str]| {
+        let options = Options {
+            cache_dir: Some(cache_dir.path().to_path_buf()),
+            markers: true,
+            marker_tags: marker_tags.iter().map(|t| t.to_string()).collect(),
+            ..Options::default()
+        };
+        tlc::count(&[fixture("markers")], &options)
+            .unwrap()
+            .markers
+            .unwrap()
+            .markers
+            .len()
+    };
+
+    assert_eq!(count(&[]), 4);
+    assert_eq!(count(&[]), 4);
+    assert_eq!(count(&["NOTE"]), 5);
+}
+
+#[test]
+fn embedded_code_counts_toward_its_own_language() {
+    let options = Options {
+        markers: true,
+        ..Options::default()
+    };
+    let report = tlc::count(&[fixture("embedded")], &options).unwrap();
+
+    let mut languages = report
+        .languages
+        .iter()
+        .map(|l| {
+            let t = &l.totals;
+            (l.language.as_str(), t.files, t.code, t.comment, t.blank)
+        })
+        .collect::<Vec<_>>();
+    languages.sort();
+    assert_eq!(
+        languages,
+        [
+            ("CSS", 0, 1, 0, 0),
+            ("Jupyter Notebook", 1, 2, 0, 1),
+            ("Markdown", 1, 6, 0, 3),
+            ("Python", 0, 2, 1, 0),
+            ("Rust", 0, 2, 1, 0),
+            ("Shell", 0, 1, 1, 0),
+            ("TypeScript", 0, 3, 0, 0),
+            ("Vue", 1, 7, 0, 2),
+        ]
+    );
+    assert_eq!((report.total.files, report.total.lines), (3, 33));
+
+    // The file itself is listed with all of its lines, and what's embedded in it.
+    let readme = report
+        .files
+        .iter()
+        .find(|f| f.path.ends_with("README.md"))
+        .unwrap();
+    let embedded = readme
+        .embedded
+        .iter()
+        .map(|e| (e.language.as_str(), e.lines))
+        .collect::<Vec<_>>();
+    assert_eq!(readme.lines, 14);
+    assert_eq!(embedded, [("Shell", 2), ("Rust", 3)]);
+
+    let markers = report.markers.unwrap().markers;
+    assert_eq!(markers.len(), 1);
+    assert!(markers[0].path.ends_with("README.md"));
+    assert_eq!(
+        (markers[0].line, markers[0].text.as_str()),
+        (12, "say something")
+    );
+}
+
+/// Writes some files into a repository's working tree and commits them as someone.
+fn commit(repo: &git2::Repository, files: &[(&str, &str)], name: &str, email: &str) {
+    let mut index = repo.index().unwrap();
+    for (path, contents) in files {
+        let full_path = repo.workdir().unwrap().join(path);
+        std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        std::fs::write(full_path, contents).unwrap();
+        index.add_path(Path::new(path)).unwrap();
+    }
+    index.write().unwrap();
+
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now(name, email).unwrap();
+    let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+    let parents = parent.iter().collect::<Vec<_>>();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "Commit",
+        &tree,
+        &parents,
+    )
+    .unwrap();
+}
+
+#[test]
+fn lines_are_attributed_to_their_authors() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let mailmap = "Alice <alice@example.com> <alice@old.example.com>\n";
+    commit(
+        &repo,
+        &[
+            ("lib.rs", "// Library.\nfn a() {}\n"),
+            (".mailmap", mailmap),
+        ],
+        "alice",
+        "alice@old.example.com",
+    );
+    commit(
+        &repo,
+        &[
+            ("lib.rs", "// Library.\nfn a() {}\n\nfn b() {}\n"),
+            ("src/main.py", "print(1)\n"),
+        ],
+        "Bob",
+        "Bob@Example.com",
+    );
+    // A line that hasn't been committed yet, and a file that never was.
+    std::fs::write(
+        dir.path().join("lib.rs"),
+        "// Library.\nfn a() {}\n\nfn b() {}\nfn c() {}\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("new.rs"), "fn d() {}\n").unwrap();
+
+    let outside = tempfile::tempdir().unwrap();
+    std::fs::write(outside.path().join("other.rs"), "fn e() {}\n").unwrap();
+
+    let options = Options {
+        by_author: true,
+        author_languages: true,
+        author_dirs: true,
+        ..Options::default()
+    };
+    let paths = [dir.path().to_path_buf(), outside.path().to_path_buf()];
+    let authors = tlc::count(&paths, &options).unwrap().authors.unwrap();
+
+    let listing = authors
+        .authors
+        .iter()
+        .map(|a| {
+            let t = &a.totals;
+            (
+                a.name.as_str(),
+                a.email.as_str(),
+                t.files,
+                t.code,
+                t.comment,
+                t.blank,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        listing,
+        [
+            ("Bob", "bob@example.com", 2, 2, 0, 1),
+            ("Alice", "alice@example.com", 1, 1, 1, 0),
+            ("Not Committed Yet", "not.committed.yet", 2, 2, 0, 0),
+        ]
+    );
+    assert_eq!(
+        (authors.unattributed.files, authors.unattributed.lines),
+        (1, 1)
+    );
+
+    let languages = authors
+        .languages
+        .unwrap()
+        .into_iter()
+        .filter(|l| l.email == "bob@example.com")
+        .map(|l| (l.language, l.totals.lines))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        languages,
+        [("Rust".to_string(), 2), ("Python".to_string(), 1)]
+    );
+
+    let prefix = dir.path().display().to_string();
+    let directories = authors
+        .directories
+        .unwrap()
+        .into_iter()
+        .filter(|d| d.email == "bob@example.com")
+        .map(|d| (d.path[prefix.len()..].replace('\\', "/"), d.totals.lines))
+        .collect::<Vec<_>>();
+    assert_eq!(directories, [(String::new(), 2), ("/src".to_string(), 1)]);
+}
+
+#[test]
+fn authors_add_up_to_the_totals() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = git2::Repository::init(dir.path()).unwrap();
+    let readme = "# Usage\n\n```rust\n// Say hi.\nfn main() {}\n```\n";
+    let notebook = std::fs::read_to_string(fixture("embedded").join("analysis.ipynb")).unwrap();
+    commit(
+        &repo,
+        &[("README.md", readme), ("analysis.ipynb", &notebook)],
+        "Alice",
+        "alice@example.com",
+    );
+    // UTF-16 with a byte order mark, with a comment that isn't committed yet.
+    let utf16 = "// Hi.\nfn a() {}\n"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect::<Vec<_>>();
+    std::fs::write(
+        dir.path().join("lib.rs"),
+        [&[0xFF, 0xFE][..], &utf16].concat(),
+    )
+    .unwrap();
+
+    let options = Options {
+        by_author: true,
+        ..Options::default()
+    };
+    let report = tlc::count(&[dir.path().to_path_buf()], &options).unwrap();
+    let authors = report.authors.unwrap();
+    let sum = |f: fn(&tlc::report::AuthorTotals) -> usize| {
+        authors.authors.iter().map(|a| f(&a.totals)).sum::<usize>()
+    };
+    assert_eq!(
+        (sum(|t| t.code), sum(|t| t.comment), sum(|t| t.blank)),
+        (report.total.code, report.total.comment, report.total.blank)
+    );
+    let uncommitted = authors
+        .authors
+        .iter()
+        .find(|a| a.name == "Not Committed Yet");
+    let uncommitted = uncommitted.unwrap().totals;
+    assert_eq!((uncommitted.code, uncommitted.comment), (1, 1));
+}
+
+/// Copies a fixture into a temporary directory that's the top of a git repository, so that
+/// nothing above it is looked at.
+fn fixture_repo(name: &str) -> tempfile::TempDir {
+    fn copy(from: &Path, to: &Path) {
+        std::fs::create_dir_all(to).unwrap();
+        for entry in std::fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                copy(&entry.path(), &to.join(entry.file_name()));
+            } else {
+                std::fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+            }
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    copy(&fixture(name), dir.path());
+    std::fs::create_dir(dir.path().join(".git")).unwrap();
+    dir
+}
+
+#[test]
+fn files_are_attributed_to_their_owners() {
+    let options = Options {
+        by_owner: true,
+        ..Options::default()
+    };
+    let repo = fixture_repo("owners");
+    let owners = tlc::count(&[repo.path().to_path_buf()], &options)
+        .unwrap()
+        .owners
+        .unwrap();
+
+    let listing = owners
+        .owners
+        .iter()
+        .map(|o| (o.owner.as_str(), o.totals.files, o.totals.lines))
+        .collect::<Vec<_>>();
+    // `scripts/build.py` has two owners, and `docs/api/index.md` isn't directly in `docs`.
+    assert_eq!(
+        listing,
+        [
+            ("@acme/core", 2, 3),
+            ("@acme/data", 1, 3),
+            ("@acme/docs", 1, 3),
+            ("@dana", 1, 3),
+            ("@acme/frontend", 1, 2),
+            ("@acme/infra", 1, 2),
+        ]
+    );
+    assert_eq!((owners.unowned.files, owners.unowned.lines), (1, 1));
+}
+
+#[test]
+fn codeowners_is_only_read_at_the_top_of_the_repository() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |path: &str, contents: &str| {
+        let path = dir.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    };
+    std::fs::create_dir(dir.path().join(".git")).unwrap();
+    write("docs/CODEOWNERS", "* @core\n/docs/ @docs\n");
+    write("docs/guide.md", "# Guide\n");
+    // Not the repository's `CODEOWNERS`, even though it's closer.
+    write("pkg/docs/CODEOWNERS", "* @pkg\n");
+    write("pkg/lib.rs", "fn a() {}\n");
+
+    let options = Options {
+        by_owner: true,
+        ..Options::default()
+    };
+    let owners = tlc::count(&[dir.path().to_path_buf()], &options)
+        .unwrap()
+        .owners
+        .unwrap();
+    let listing = owners
+        .owners
+        .iter()
+        .map(|o| (o.owner.as_str(), o.totals.files))
+        .collect::<Vec<_>>();
+    assert_eq!(listing, [("@core", 1), ("@docs", 1)]);
+}
+
+#[test]
+fn files_are_totalled_up_by_project() {
+    let repo = fixture_repo("projects");
+    let outside = tempfile::tempdir().unwrap();
+    std::fs::create_dir(outside.path().join(".git")).unwrap();
+    std::fs::write(outside.path().join("loose.rs"), "fn a() {}\n").unwrap();
+
+    let options = Options {
+        by_project: true,
+        ..Options::default()
+    };
+    let paths = [repo.path().to_path_buf(), outside.path().to_path_buf()];
+    let projects = tlc::count(&paths, &options).unwrap().projects.unwrap();
+
+    let prefix = repo.path().display().to_string();
+    let listing = projects
+        .projects
+        .iter()
+        .map(|p| {
+            let languages = p
+                .languages
+                .iter()
+                .map(|lang| lang.language.as_str())
+                .collect::<Vec<_>>();
+            (p.name.as_str(), p.kind.name(), p.totals.lines, languages)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        listing,
+        [
+            ("billing", "maven", 7, vec!["XML", "Java"]),
+            ("engine", "cargo", 5, vec!["TOML", "Rust"]),
+            ("@acme/web", "npm", 4, vec!["JavaScript", "JSON"]),
+            ("etl", "python", 3, vec!["TOML", "Python"]),
+            ("example.com/acme/api", "go", 3, vec!["Go"]),
+        ]
+    );
+    assert_eq!(
+        projects.projects[1].path[prefix.len()..].replace('\\', "/"),
+        "/engine"
+    );
+    // `notes.md` isn't in any of the projects, and neither is `loose.rs`.
+    assert_eq!((projects.outside.files, projects.outside.lines), (2, 2));
+}
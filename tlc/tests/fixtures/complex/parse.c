@@ -0,0 +1,22 @@
+#include <ctype.h>
+
+/* Reads a number, if (and only if) there is one. */
+static int parse(const char *s, int *out)
+{
+    int n = 0;
+    if (s == NULL || *s == '\0') {
+        return -1;
+    }
+
+    for (; *s; s++) {
+        if (!isdigit(*s)) {
+            return -1;
+        }
+        n = n * 10 + (*s - '0');
+    }
+
+    *out = n;
+    return 0;
+}
+
+int is_even(int n) { return n % 2 == 0; }
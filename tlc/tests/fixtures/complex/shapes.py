@@ -0,0 +1,14 @@
+class Square:
+    def __init__(self, side):
+        self.side = side
+
+    def area(self):
+        return self.side * self.side
+
+
+def describe(shape):
+    if shape is None:
+        return "nothing"
+    elif shape.area() > 100 and shape.side > 10:
+        return "big"
+    return "small"
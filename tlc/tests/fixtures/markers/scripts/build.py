@@ -0,0 +1,3 @@
+# NOTE: run from the repository root
+# TODO(bob) - cache the downloads
+print("building")
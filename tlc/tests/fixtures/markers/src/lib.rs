@@ -0,0 +1,12 @@
+// TODO(alice): split this module up
+pub fn parse(input: &str) -> usize {
+    // FIXME: count characters, not bytes
+    input.len() // see the TODO list
+}
+
+/* HACK: the parser can't handle
+ * empty input yet */
+pub fn empty() -> bool {
+    let todo = "TODO: not a comment";
+    todo.is_empty()
+}
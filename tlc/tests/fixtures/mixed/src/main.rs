@@ -0,0 +1,11 @@
+//! The entry point.
+
+use std::env;
+
+/* A block comment
+   over two lines. */
+fn main() {
+    let args = env::args().collect::<Vec<_>>(); // trailing comment
+
+    println!("/* not a comment */ {:?}", args);
+}
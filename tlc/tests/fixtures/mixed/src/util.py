@@ -0,0 +1,9 @@
+# Helpers.
+
+
+def greet(name):
+    """Says hello.
+
+    Returns nothing.
+    """
+    print("hello, " + name)  # code
@@ -0,0 +1,2 @@
+// The library.
+fn main() {}
@@ -0,0 +1,2 @@
+const app = 1;
+export default app;
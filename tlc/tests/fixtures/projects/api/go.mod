@@ -0,0 +1,4 @@
+// The API.
+module example.com/acme/api
+
+go 1.22
@@ -0,0 +1,3 @@
+const app = 1;
+
+export default app;